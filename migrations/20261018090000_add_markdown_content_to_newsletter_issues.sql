@@ -0,0 +1,2 @@
+-- Keep the Markdown source of issues authored in Markdown
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT NULL;
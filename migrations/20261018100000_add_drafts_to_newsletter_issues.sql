@@ -0,0 +1,37 @@
+-- Issues start life as drafts. While an issue is a draft, text_content and
+-- html_content hold what the author typed; they are replaced by the rendered
+-- email bodies when the issue is sent.
+ALTER TABLE newsletter_issues
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'sent'
+        CHECK (status IN ('draft', 'scheduled', 'sending', 'sent', 'cancelled')),
+    ADD COLUMN preheader TEXT NOT NULL DEFAULT '',
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ALTER COLUMN published_at DROP NOT NULL;
+
+UPDATE newsletter_issues
+SET status = 'sending'
+WHERE EXISTS (
+    SELECT 1 FROM issue_delivery_queue q
+    WHERE q.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+);
+
+ALTER TABLE newsletter_issues ALTER COLUMN status SET DEFAULT 'draft';
+
+CREATE TABLE newsletter_issue_revisions (
+    revision_id UUID NOT NULL,
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    preheader TEXT NOT NULL,
+    markdown_content TEXT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    autosaved BOOLEAN NOT NULL,
+    created_by UUID NULL REFERENCES users (user_id),
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (revision_id)
+);
+
+CREATE INDEX newsletter_issue_revisions_issue_idx
+    ON newsletter_issue_revisions (newsletter_issue_id, created_at DESC);
@@ -0,0 +1,5 @@
+ALTER TABLE newsletter_issues ADD COLUMN scheduled_at TIMESTAMPTZ NULL;
+
+CREATE INDEX newsletter_issues_due_idx
+    ON newsletter_issues (scheduled_at)
+    WHERE status = 'scheduled';
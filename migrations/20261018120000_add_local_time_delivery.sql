@@ -0,0 +1,26 @@
+-- IANA timezone of the subscriber, when known.
+ALTER TABLE subscriptions ADD COLUMN timezone TEXT NULL;
+
+-- In local_time mode, local_send_at is the wall-clock time the issue goes out
+-- at in every subscriber's timezone; delivery_timezone stands in for
+-- subscribers whose timezone is unknown.
+ALTER TABLE newsletter_issues
+    ADD COLUMN delivery_mode TEXT NOT NULL DEFAULT 'at_once'
+        CHECK (delivery_mode IN ('at_once', 'local_time')),
+    ADD COLUMN local_send_at TIMESTAMP NULL,
+    ADD COLUMN delivery_timezone TEXT NULL;
+
+-- One wave per timezone, planned when a local_time issue starts going out.
+CREATE TABLE issue_delivery_waves (
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    timezone TEXT NOT NULL,
+    send_at TIMESTAMPTZ NOT NULL,
+    recipients INTEGER NULL,
+    enqueued_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (newsletter_issue_id, timezone)
+);
+
+CREATE INDEX issue_delivery_waves_due_idx
+    ON issue_delivery_waves (send_at)
+    WHERE enqueued_at IS NULL;
@@ -0,0 +1,20 @@
+-- A sending issue can be paused, which leaves its queued deliveries alone
+-- until it is resumed, or cancelled, which marks them as cancelled.
+ALTER TABLE newsletter_issues DROP CONSTRAINT newsletter_issues_status_check;
+ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_status_check
+    CHECK (status IN ('draft', 'scheduled', 'sending', 'paused', 'sent', 'cancelled'));
+
+ALTER TABLE issue_delivery_queue ADD COLUMN cancelled_at TIMESTAMPTZ NULL;
+
+-- Successful deliveries, so that what went out is known once queue rows
+-- are gone.
+CREATE TABLE issue_delivery_log (
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_email TEXT NOT NULL,
+    delivered_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+CREATE INDEX issue_delivery_log_delivered_at_idx
+    ON issue_delivery_log (newsletter_issue_id, delivered_at);
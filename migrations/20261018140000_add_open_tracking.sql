@@ -0,0 +1,18 @@
+-- Open tracking is opt-in per issue.
+ALTER TABLE newsletter_issues
+    ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Every time a tracking pixel is loaded. machine_open says why the open
+-- looks automated (privacy proxies, security scanners, prefetching), if it
+-- does.
+CREATE TABLE issue_opens (
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id UUID NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    opened_at TIMESTAMPTZ NOT NULL,
+    user_agent TEXT NULL,
+    ip_hash TEXT NULL,
+    machine_open TEXT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id, opened_at)
+);
@@ -0,0 +1,15 @@
+-- Every time a tracked link is followed. machine_click says why the click
+-- looks automated (security scanners following every link), if it does.
+CREATE TABLE issue_clicks (
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id UUID NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    clicked_at TIMESTAMPTZ NOT NULL,
+    user_agent TEXT NULL,
+    ip_hash TEXT NULL,
+    machine_click TEXT NULL
+);
+
+CREATE INDEX issue_clicks_issue_url_idx ON issue_clicks (newsletter_issue_id, url);
@@ -0,0 +1,3 @@
+-- When a subscriber left the list, for churn reporting. Subscribers who
+-- unsubscribe keep their row so that their history stays in the analytics.
+ALTER TABLE subscriptions ADD COLUMN unsubscribed_at TIMESTAMPTZ NULL;
@@ -0,0 +1,14 @@
+-- Alternative subject lines, tried against the title on a random slice of
+-- the audience before the best performing one goes to everybody else.
+ALTER TABLE newsletter_issues
+    ADD COLUMN subject_variants TEXT[] NOT NULL DEFAULT '{}',
+    -- Share of the audience the test is run on, split between the subjects.
+    ADD COLUMN ab_test_percent SMALLINT NOT NULL DEFAULT 20,
+    ADD COLUMN ab_test_hours SMALLINT NOT NULL DEFAULT 4,
+    ADD COLUMN ab_test_decide_at TIMESTAMPTZ NULL,
+    ADD COLUMN ab_test_winner SMALLINT NULL;
+
+-- The subject a delivery goes out with: 0 is the title, 1 and up the
+-- variants. Deliveries without one wait for the winner while a test runs.
+ALTER TABLE issue_delivery_queue ADD COLUMN subject_variant SMALLINT NULL;
+ALTER TABLE issue_delivery_log ADD COLUMN subject_variant SMALLINT NULL;
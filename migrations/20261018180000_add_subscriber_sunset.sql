@@ -0,0 +1,24 @@
+-- The sunset policy emails subscribers who stopped reading before taking them
+-- off the list. reengagement_sent_at is when the last of those emails went
+-- out, reengaged_at when the subscriber last asked to stay.
+ALTER TABLE subscriptions ADD COLUMN reengagement_sent_at TIMESTAMPTZ NULL;
+ALTER TABLE subscriptions ADD COLUMN reengaged_at TIMESTAMPTZ NULL;
+
+CREATE INDEX issue_opens_subscriber_idx ON issue_opens (subscriber_id, opened_at);
+CREATE INDEX issue_clicks_subscriber_idx ON issue_clicks (subscriber_id, clicked_at);
+CREATE INDEX issue_delivery_log_subscriber_idx
+    ON issue_delivery_log (subscriber_email, delivered_at);
+
+-- When each subscriber last showed they read us: a human open or click, or
+-- asking to stay on the list.
+CREATE VIEW subscriber_last_engagement AS
+SELECT
+    s.id AS subscriber_id,
+    GREATEST(
+        (SELECT MAX(o.opened_at) FROM issue_opens o
+            WHERE o.subscriber_id = s.id AND o.machine_open IS NULL),
+        (SELECT MAX(c.clicked_at) FROM issue_clicks c
+            WHERE c.subscriber_id = s.id AND c.machine_click IS NULL),
+        s.reengaged_at
+    ) AS last_engaged_at
+FROM subscriptions s;
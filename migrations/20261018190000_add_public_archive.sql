@@ -0,0 +1,18 @@
+-- Issues that went out are listed in the public archive at /archive/{slug},
+-- unless hidden from it.
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT NULL UNIQUE;
+ALTER TABLE newsletter_issues ADD COLUMN hide_from_archive BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The title in lowercase ASCII words, plus the start of the id so that issues
+-- with the same title get their own address, e.g. weekly-digest-1b4e28ba.
+CREATE FUNCTION issue_slug(title TEXT, newsletter_issue_id UUID) RETURNS TEXT
+LANGUAGE SQL IMMUTABLE
+RETURN concat_ws(
+    '-',
+    NULLIF(trim(BOTH '-' FROM regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g')), ''),
+    left(newsletter_issue_id::text, 8)
+);
+
+UPDATE newsletter_issues
+SET slug = issue_slug(title, newsletter_issue_id)
+WHERE published_at IS NOT NULL;
@@ -0,0 +1,4 @@
+-- published_at was stored as text, which sorted and compared by accident at
+-- best. Every value written so far came from NOW() and casts back cleanly.
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE TIMESTAMPTZ USING published_at::timestamptz;
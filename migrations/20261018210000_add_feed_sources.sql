@@ -0,0 +1,40 @@
+-- Blogs whose new posts are turned into issues. Posts become drafts unless
+-- auto_publish is set; with a digest_size, up to that many of the latest
+-- posts are bundled into one issue a week instead of one issue per post.
+CREATE TABLE feed_sources (
+    feed_source_id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL UNIQUE,
+    auto_publish BOOLEAN NOT NULL DEFAULT FALSE,
+    digest_size SMALLINT NULL CHECK (digest_size BETWEEN 1 AND 50),
+    title_template TEXT NOT NULL,
+    body_template TEXT NOT NULL,
+    post_template TEXT NOT NULL,
+    -- Validators of the last response, for conditional requests.
+    etag TEXT NULL,
+    last_modified TEXT NULL,
+    last_polled_at TIMESTAMPTZ NULL,
+    last_error TEXT NULL,
+    -- The digest clock starts when the source is added.
+    last_digest_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Every post seen in a feed, by its GUID, so that none is mailed twice.
+-- Posts that were already in the feed when it was added, or that a digest
+-- had no room for, are skipped.
+CREATE TABLE feed_items (
+    feed_source_id UUID NOT NULL REFERENCES feed_sources (feed_source_id) ON DELETE CASCADE,
+    guid TEXT NOT NULL,
+    title TEXT NOT NULL,
+    link TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    published_at TIMESTAMPTZ NULL,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    newsletter_issue_id UUID NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    skipped BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (feed_source_id, guid)
+);
+
+CREATE INDEX feed_items_pending_idx ON feed_items (feed_source_id)
+    WHERE newsletter_issue_id IS NULL AND NOT skipped;
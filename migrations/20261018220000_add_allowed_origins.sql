@@ -0,0 +1,7 @@
+-- Sites allowed to embed the signup form: they can post to /subscriptions
+-- from the browser and send subscribers back to their own pages. Stored as
+-- serialized origins, e.g. https://www.example.com.
+CREATE TABLE allowed_origins (
+    origin TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
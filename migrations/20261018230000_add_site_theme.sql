@@ -0,0 +1,15 @@
+-- How the public pages look. A single row, absent until an admin first saves
+-- the theme; the application falls back to its defaults until then.
+CREATE TABLE site_theme (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    newsletter_name TEXT NOT NULL,
+    logo_url TEXT NULL,
+    background_color TEXT NOT NULL,
+    text_color TEXT NOT NULL,
+    accent_color TEXT NOT NULL,
+    signup_copy TEXT NOT NULL,
+    check_inbox_copy TEXT NOT NULL,
+    confirmed_copy TEXT NOT NULL,
+    unsubscribed_copy TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,9 @@
+-- Requests counted towards the rate limits of the public endpoints, per
+-- fixed window. Keys are hashed, so no addresses are stored.
+CREATE TABLE rate_limit_hits (
+    bucket TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    window_start TIMESTAMPTZ NOT NULL,
+    hits INTEGER NOT NULL,
+    PRIMARY KEY (bucket, key_hash, window_start)
+);
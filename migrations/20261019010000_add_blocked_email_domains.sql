@@ -0,0 +1,7 @@
+-- Domains admins refuse signups from, on top of the disposable ones the app
+-- knows about. Stored lowercase, without a leading @. Blocking a domain
+-- blocks its subdomains too.
+CREATE TABLE blocked_email_domains (
+    domain TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,34 @@
+-- The form of each address that tells whether two signups reach the same
+-- inbox, so that Alice@Example.com and alice@example.com are one subscriber.
+-- The app also converts internationalized domains to ASCII, which SQL
+-- cannot: the duplicates report catches the addresses this misses.
+ALTER TABLE subscriptions ADD COLUMN normalized_email TEXT NULL;
+UPDATE subscriptions SET normalized_email = lower(email);
+ALTER TABLE subscriptions ALTER COLUMN normalized_email SET NOT NULL;
+
+-- Subscribers that signed up more than once before this point to the one
+-- that counts until an admin merges them: confirmed over pending over
+-- unsubscribed, then the oldest.
+ALTER TABLE subscriptions ADD COLUMN duplicate_of UUID NULL
+    REFERENCES subscriptions (id);
+WITH ranked AS (
+    SELECT id, first_value(id) OVER (
+        PARTITION BY normalized_email
+        ORDER BY
+            CASE status
+                WHEN 'confirmed' THEN 0
+                WHEN 'pending_confirmation' THEN 1
+                ELSE 2
+            END,
+            subscribed_at,
+            id
+    ) AS kept_id
+    FROM subscriptions
+)
+UPDATE subscriptions s
+SET duplicate_of = ranked.kept_id
+FROM ranked
+WHERE ranked.id = s.id AND ranked.kept_id <> s.id;
+
+CREATE UNIQUE INDEX subscriptions_normalized_email_key
+    ON subscriptions (normalized_email) WHERE duplicate_of IS NULL;
@@ -0,0 +1,6 @@
+-- Form tokens that signed someone up, so that each can only be used once.
+-- Rows are only needed until the token would have expired anyway.
+CREATE TABLE used_signup_form_tokens (
+    token_hash TEXT PRIMARY KEY,
+    served_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,14 @@
+-- The wave each subscriber of a local_time issue belongs to, recorded when the
+-- waves are planned, so that moving to another timezone neither sends the
+-- issue twice nor skips it.
+CREATE TABLE issue_wave_recipients (
+    newsletter_issue_id UUID NOT NULL,
+    subscriber_email TEXT NOT NULL,
+    timezone TEXT NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email),
+    FOREIGN KEY (newsletter_issue_id, timezone)
+        REFERENCES issue_delivery_waves (newsletter_issue_id, timezone) ON DELETE CASCADE
+);
+
+CREATE INDEX issue_wave_recipients_wave_idx
+    ON issue_wave_recipients (newsletter_issue_id, timezone);
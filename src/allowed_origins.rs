@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use axum::http::{header, Method};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use sqlx::PgPool;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+// How long browsers may cache the answer to a preflight request
+const PREFLIGHT_MAX_AGE_SECONDS: u64 = 3600;
+
+/// A site allowed to embed the signup form.
+#[derive(Debug)]
+pub struct AllowedOrigin {
+    pub origin: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The origin of a web page, e.g. `https://www.example.com` for
+/// `https://www.example.com/newsletter`, which is what browsers send and what
+/// the allowlist stores.
+pub fn parse_origin(s: &str) -> Result<String, String> {
+    let url = Url::parse(s.trim()).map_err(|_| format!("{} is not a valid URL", s))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(format!("{} is not an http or https site", s));
+    }
+    Ok(url.origin().ascii_serialization())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_allowed_origins(pool: &PgPool) -> Result<Vec<AllowedOrigin>, sqlx::Error> {
+    sqlx::query_as!(
+        AllowedOrigin,
+        "SELECT origin, created_at FROM allowed_origins ORDER BY origin"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns `false` if the origin was already allowed.
+#[tracing::instrument(skip(pool))]
+pub async fn insert_allowed_origin(pool: &PgPool, origin: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO allowed_origins (origin) VALUES ($1) ON CONFLICT DO NOTHING",
+        origin,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns `false` if the origin was not allowed.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_allowed_origin(pool: &PgPool, origin: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM allowed_origins WHERE origin = $1", origin)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn is_allowed_origin(pool: &PgPool, origin: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM allowed_origins WHERE origin = $1) AS "allowed!""#,
+        origin,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// `url`, if it is on this site or an allowed one. Signup forms can send
+/// subscribers back to their own pages, but not anywhere else.
+pub async fn allowed_redirect(
+    pool: &PgPool,
+    base_url: &str,
+    url: &str,
+) -> Result<Option<Url>, sqlx::Error> {
+    let Ok(origin) = parse_origin(url) else {
+        return Ok(None);
+    };
+    if parse_origin(base_url).is_ok_and(|own| own == origin)
+        || is_allowed_origin(pool, &origin).await?
+    {
+        Ok(Url::parse(url.trim()).ok())
+    } else {
+        Ok(None)
+    }
+}
+
+/// Lets scripts on the allowed sites post signups from the browser. Other
+/// sites get no CORS headers, so browsers keep the response from them.
+pub fn signup_cors_layer(pool: PgPool) -> CorsLayer {
+    let allow_origin = AllowOrigin::async_predicate(move |origin, _| {
+        let pool = pool.clone();
+        async move {
+            let Ok(origin) = origin.to_str() else {
+                return false;
+            };
+            is_allowed_origin(&pool, origin).await.unwrap_or_else(|e| {
+                tracing::error!(error.message = %e, "Failed to check the origin of a signup");
+                false
+            })
+        }
+    });
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::ACCEPT])
+        .max_age(Duration::from_secs(PREFLIGHT_MAX_AGE_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_origin;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn pages_are_reduced_to_their_origin() {
+        assert_ok_eq!(
+            parse_origin("https://www.Example.com/newsletter?x=1"),
+            "https://www.example.com"
+        );
+        assert_ok_eq!(
+            parse_origin("http://localhost:3000/"),
+            "http://localhost:3000"
+        );
+    }
+
+    #[test]
+    fn default_ports_are_dropped() {
+        assert_ok_eq!(
+            parse_origin("https://example.com:443"),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn only_web_sites_have_origins() {
+        for s in [
+            "example.com",
+            "file:///etc/passwd",
+            "javascript:alert(1)",
+            "data:text/html,hi",
+        ] {
+            assert_err!(parse_origin(s));
+        }
+    }
+}
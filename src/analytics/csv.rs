@@ -0,0 +1,72 @@
+/// Builds a CSV document, one row at a time.
+#[derive(Debug, Default)]
+pub struct CsvWriter {
+    output: String,
+}
+
+impl CsvWriter {
+    pub fn new(header: &[&str]) -> Self {
+        let mut writer = Self::default();
+        writer.row(header);
+        writer
+    }
+
+    pub fn row<T: AsRef<str>>(&mut self, fields: &[T]) {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.output.push(',');
+            }
+            push_field(&mut self.output, field.as_ref());
+        }
+        self.output.push_str("\r\n");
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Quotes fields that need it, and neutralizes the ones a spreadsheet would
+/// run as a formula, since titles and URLs come from outside.
+fn push_field(output: &mut String, field: &str) {
+    let formula = field.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if !formula && !field.contains([',', '"', '\n', '\r']) {
+        output.push_str(field);
+        return;
+    }
+
+    output.push('"');
+    if formula {
+        output.push('\'');
+    }
+    output.push_str(&field.replace('"', "\"\""));
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvWriter;
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        let mut csv = CsvWriter::new(&["title", "clicks"]);
+        csv.row(&["Hello, \"world\"", "3"]);
+        csv.row(&["Plain", "0"]);
+
+        assert_eq!(
+            csv.finish(),
+            "title,clicks\r\n\"Hello, \"\"world\"\"\",3\r\nPlain,0\r\n"
+        );
+    }
+
+    #[test]
+    fn formulas_are_neutralized() {
+        let mut csv = CsvWriter::new(&["title"]);
+        csv.row(&["=HYPERLINK(\"http://evil\")"]);
+
+        assert_eq!(
+            csv.finish(),
+            "title\r\n\"'=HYPERLINK(\"\"http://evil\"\")\"\r\n"
+        );
+    }
+}
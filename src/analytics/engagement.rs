@@ -0,0 +1,231 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How many links the top links table shows.
+const TOP_LINKS: i64 = 20;
+
+/// How an issue that went out was received. Opens and clicks flagged as
+/// automated are left out, except for the machine opens count.
+#[derive(Debug)]
+pub struct IssueEngagement {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub sent_at: DateTime<Utc>,
+    pub track_opens: bool,
+    pub delivered: i64,
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub machine_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+impl IssueEngagement {
+    /// Share of recipients who opened the issue, in percent, when opens were
+    /// tracked.
+    pub fn open_rate(&self) -> Option<f64> {
+        self.track_opens
+            .then(|| percent(self.unique_opens, self.delivered))
+    }
+
+    /// Share of recipients who clicked a link, in percent.
+    pub fn click_rate(&self) -> f64 {
+        percent(self.unique_clicks, self.delivered)
+    }
+}
+
+/// A link of an issue and how often it was followed.
+#[derive(Debug)]
+pub struct TopLink {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+/// The engagement of the issues sent during one week.
+#[derive(Debug, Default)]
+pub struct EngagementWeek {
+    /// The Monday the week starts on.
+    pub week: NaiveDate,
+    pub issues: i64,
+    pub delivered: i64,
+    /// Deliveries of the issues that tracked opens, to compute the open rate.
+    pub open_tracked: i64,
+    pub unique_opens: i64,
+    pub unique_clicks: i64,
+}
+
+impl EngagementWeek {
+    pub fn open_rate(&self) -> Option<f64> {
+        (self.open_tracked > 0).then(|| percent(self.unique_opens, self.open_tracked))
+    }
+
+    pub fn click_rate(&self) -> f64 {
+        percent(self.unique_clicks, self.delivered)
+    }
+}
+
+pub(super) fn percent(part: i64, whole: i64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+/// Every issue that went out, most recent first.
+#[tracing::instrument(skip_all)]
+pub async fn list_issue_engagement(pool: &PgPool) -> Result<Vec<IssueEngagement>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueEngagement,
+        r#"
+        SELECT
+            i.newsletter_issue_id AS issue_id,
+            i.title,
+            d.sent_at AS "sent_at!",
+            i.track_opens,
+            d.delivered AS "delivered!",
+            COALESCE(o.opens, 0) AS "opens!",
+            COALESCE(o.unique_opens, 0) AS "unique_opens!",
+            COALESCE(o.machine_opens, 0) AS "machine_opens!",
+            COALESCE(c.clicks, 0) AS "clicks!",
+            COALESCE(c.unique_clicks, 0) AS "unique_clicks!"
+        FROM newsletter_issues i
+        JOIN (
+            SELECT newsletter_issue_id, MIN(delivered_at) AS sent_at, COUNT(*) AS delivered
+            FROM issue_delivery_log
+            GROUP BY newsletter_issue_id
+        ) d USING (newsletter_issue_id)
+        LEFT JOIN (
+            SELECT
+                newsletter_issue_id,
+                COUNT(*) FILTER (WHERE machine_open IS NULL) AS opens,
+                COUNT(DISTINCT subscriber_id) FILTER (WHERE machine_open IS NULL) AS unique_opens,
+                COUNT(*) FILTER (WHERE machine_open IS NOT NULL) AS machine_opens
+            FROM issue_opens
+            GROUP BY newsletter_issue_id
+        ) o USING (newsletter_issue_id)
+        LEFT JOIN (
+            SELECT
+                newsletter_issue_id,
+                COUNT(*) AS clicks,
+                COUNT(DISTINCT subscriber_id) AS unique_clicks
+            FROM issue_clicks
+            WHERE machine_click IS NULL
+            GROUP BY newsletter_issue_id
+        ) c USING (newsletter_issue_id)
+        ORDER BY d.sent_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The most followed links across all issues.
+#[tracing::instrument(skip_all)]
+pub async fn list_top_links(pool: &PgPool) -> Result<Vec<TopLink>, sqlx::Error> {
+    sqlx::query_as!(
+        TopLink,
+        r#"
+        SELECT
+            c.newsletter_issue_id AS issue_id,
+            i.title,
+            c.url,
+            COUNT(*) AS "clicks!",
+            COUNT(DISTINCT c.subscriber_id) AS "unique_clicks!"
+        FROM issue_clicks c
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE c.machine_click IS NULL
+        GROUP BY c.newsletter_issue_id, i.title, c.url
+        ORDER BY 4 DESC, 5 DESC, c.url
+        LIMIT $1
+        "#,
+        TOP_LINKS,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Sums up the engagement of `issues` by the week they went out, oldest week
+/// first.
+pub fn engagement_by_week(issues: &[IssueEngagement]) -> Vec<EngagementWeek> {
+    let mut weeks: Vec<EngagementWeek> = Vec::new();
+    for issue in issues {
+        let day = issue.sent_at.date_naive();
+        let week = day - Duration::days(day.weekday().num_days_from_monday().into());
+        let index = match weeks.iter().position(|w| w.week == week) {
+            Some(index) => index,
+            None => {
+                weeks.push(EngagementWeek {
+                    week,
+                    ..Default::default()
+                });
+                weeks.len() - 1
+            }
+        };
+        let totals = &mut weeks[index];
+        totals.issues += 1;
+        totals.delivered += issue.delivered;
+        totals.unique_clicks += issue.unique_clicks;
+        if issue.track_opens {
+            totals.open_tracked += issue.delivered;
+            totals.unique_opens += issue.unique_opens;
+        }
+    }
+    weeks.sort_by_key(|w| w.week);
+    weeks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{engagement_by_week, IssueEngagement};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use claim::assert_none;
+    use uuid::Uuid;
+
+    fn issue(day: u32, track_opens: bool, unique_opens: i64) -> IssueEngagement {
+        IssueEngagement {
+            issue_id: Uuid::new_v4(),
+            title: "Weekly".into(),
+            sent_at: Utc.with_ymd_and_hms(2026, 10, day, 9, 0, 0).unwrap(),
+            track_opens,
+            delivered: 10,
+            opens: unique_opens,
+            unique_opens,
+            machine_opens: 0,
+            clicks: 1,
+            unique_clicks: 1,
+        }
+    }
+
+    #[test]
+    fn issues_are_summed_up_by_the_week_they_went_out() {
+        // Monday 12th and Friday 16th, then Tuesday 20th.
+        let issues = [issue(20, true, 5), issue(16, false, 0), issue(12, true, 4)];
+
+        let weeks = engagement_by_week(&issues);
+
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(
+            weeks[0].week,
+            NaiveDate::from_ymd_opt(2026, 10, 12).unwrap()
+        );
+        assert_eq!(weeks[0].issues, 2);
+        assert_eq!(weeks[0].delivered, 20);
+        assert_eq!(weeks[0].open_rate(), Some(40.0));
+        assert_eq!(weeks[0].click_rate(), 10.0);
+        assert_eq!(
+            weeks[1].week,
+            NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+        );
+    }
+
+    #[test]
+    fn issues_without_open_tracking_have_no_open_rate() {
+        assert_none!(issue(12, false, 0).open_rate());
+        assert_none!(engagement_by_week(&[issue(12, false, 0)])[0].open_rate());
+    }
+}
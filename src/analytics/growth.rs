@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+use sqlx::PgPool;
+
+use super::percent;
+
+/// How many weeks of subscriber growth the analytics cover.
+pub const GROWTH_WEEKS: i32 = 26;
+
+/// How the list changed during one week. Only subscribers who confirmed
+/// count.
+#[derive(Debug)]
+pub struct GrowthWeek {
+    /// The Monday the week starts on.
+    pub week: NaiveDate,
+    pub subscribed: i64,
+    pub unsubscribed: i64,
+    /// Subscribers on the list at the end of the week.
+    pub total: i64,
+}
+
+impl GrowthWeek {
+    /// Share of the list at the start of the week that left during it, in
+    /// percent.
+    pub fn churn_rate(&self) -> f64 {
+        let at_start = self.total - self.subscribed + self.unsubscribed;
+        percent(self.unsubscribed, at_start)
+    }
+}
+
+/// Subscriber growth and churn over the last `weeks` weeks, oldest first.
+#[tracing::instrument(skip(pool))]
+pub async fn get_subscriber_growth(
+    pool: &PgPool,
+    weeks: i32,
+) -> Result<Vec<GrowthWeek>, sqlx::Error> {
+    sqlx::query_as!(
+        GrowthWeek,
+        r#"
+        SELECT
+            w.week::date AS "week!",
+            COUNT(s.id) FILTER (
+                WHERE s.subscribed_at >= w.week AND s.subscribed_at < w.week + interval '1 week'
+            ) AS "subscribed!",
+            COUNT(s.id) FILTER (
+                WHERE s.unsubscribed_at >= w.week AND s.unsubscribed_at < w.week + interval '1 week'
+            ) AS "unsubscribed!",
+            COUNT(s.id) FILTER (
+                WHERE s.subscribed_at < w.week + interval '1 week'
+                    AND (s.unsubscribed_at IS NULL OR s.unsubscribed_at >= w.week + interval '1 week')
+            ) AS "total!"
+        FROM generate_series(
+            date_trunc('week', NOW()) - ($1::int - 1) * interval '1 week',
+            date_trunc('week', NOW()),
+            interval '1 week'
+        ) AS w(week)
+        LEFT JOIN subscriptions s ON s.status <> 'pending_confirmation'
+        GROUP BY w.week
+        ORDER BY w.week
+        "#,
+        weeks,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,9 @@
+mod csv;
+mod engagement;
+mod growth;
+mod subscribers;
+
+pub use csv::*;
+pub use engagement::*;
+pub use growth::*;
+pub use subscribers::*;
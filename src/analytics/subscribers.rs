@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How many of a subscriber's latest issues their engagement score covers.
+pub const SCORED_ISSUES: i64 = 10;
+
+/// How many subscribers the admin subscriber list shows per page.
+pub const SUBSCRIBERS_PER_PAGE: i64 = 50;
+
+/// A subscriber and how they engaged with their latest issues.
+#[derive(Debug)]
+pub struct SubscriberEngagement {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub unsubscribed_at: Option<DateTime<Utc>>,
+    pub reengagement_sent_at: Option<DateTime<Utc>>,
+    /// Issues delivered, out of the last [`SCORED_ISSUES`].
+    pub delivered: i64,
+    /// Of those, the ones opened or clicked through.
+    pub read: i64,
+    /// Of those, the ones clicked through.
+    pub clicked: i64,
+    pub last_engaged_at: Option<DateTime<Utc>>,
+}
+
+impl SubscriberEngagement {
+    /// See [`engagement_score`]. `None` until an issue was delivered.
+    pub fn score(&self) -> Option<i64> {
+        (self.delivered > 0).then(|| engagement_score(self.delivered, self.read, self.clicked))
+    }
+}
+
+/// A score from 0 to 100: reading an issue is worth half of it, clicking
+/// through the other half.
+///
+/// Automated opens and clicks do not count. Issues sent without open
+/// tracking only count as read when a link was clicked.
+pub fn engagement_score(delivered: i64, read: i64, clicked: i64) -> i64 {
+    if delivered == 0 {
+        return 0;
+    }
+    ((read + clicked) * 100 + delivered) / (2 * delivered)
+}
+
+/// One page of subscribers, newest first, with their engagement. Pages start
+/// at 0.
+#[tracing::instrument(skip(pool))]
+pub async fn list_subscriber_engagement(
+    pool: &PgPool,
+    page: i64,
+) -> Result<Vec<SubscriberEngagement>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberEngagement,
+        r#"
+        SELECT
+            s.id AS subscriber_id,
+            s.email,
+            s.name,
+            s.status,
+            s.subscribed_at,
+            s.unsubscribed_at,
+            s.reengagement_sent_at,
+            d.delivered AS "delivered!",
+            d.read AS "read!",
+            d.clicked AS "clicked!",
+            e.last_engaged_at
+        FROM subscriptions s
+        JOIN subscriber_last_engagement e ON e.subscriber_id = s.id
+        CROSS JOIN LATERAL (
+            SELECT
+                COUNT(*) AS delivered,
+                COUNT(*) FILTER (WHERE l.opened OR l.clicked) AS read,
+                COUNT(*) FILTER (WHERE l.clicked) AS clicked
+            FROM (
+                SELECT
+                    EXISTS (
+                        SELECT 1 FROM issue_opens o
+                        WHERE o.newsletter_issue_id = l.newsletter_issue_id
+                            AND o.subscriber_id = s.id
+                            AND o.machine_open IS NULL
+                    ) AS opened,
+                    EXISTS (
+                        SELECT 1 FROM issue_clicks c
+                        WHERE c.newsletter_issue_id = l.newsletter_issue_id
+                            AND c.subscriber_id = s.id
+                            AND c.machine_click IS NULL
+                    ) AS clicked
+                FROM issue_delivery_log l
+                WHERE l.subscriber_email = s.email
+                ORDER BY l.delivered_at DESC
+                LIMIT $1
+            ) l
+        ) d
+        ORDER BY s.subscribed_at DESC, s.email
+        LIMIT $2 OFFSET $3
+        "#,
+        SCORED_ISSUES,
+        SUBSCRIBERS_PER_PAGE,
+        page * SUBSCRIBERS_PER_PAGE,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::engagement_score;
+
+    #[test]
+    fn reading_everything_is_worth_half_the_score() {
+        assert_eq!(engagement_score(10, 10, 0), 50);
+        assert_eq!(engagement_score(10, 10, 10), 100);
+        assert_eq!(engagement_score(10, 0, 0), 0);
+    }
+
+    #[test]
+    fn scores_are_rounded() {
+        assert_eq!(engagement_score(3, 1, 0), 17);
+        assert_eq!(engagement_score(3, 2, 1), 50);
+    }
+
+    #[test]
+    fn nothing_delivered_scores_zero() {
+        assert_eq!(engagement_score(0, 0, 0), 0);
+    }
+}
@@ -0,0 +1,94 @@
+//! Bulk management of dead-lettered deliveries, e.g. to replay everything
+//! that failed during an email provider outage.
+//!
+//! ```text
+//! dead_letters list
+//! dead_letters requeue [--issue <issue_id>] [--error <text>]
+//! dead_letters discard [--issue <issue_id>] [--error <text>]
+//! ```
+use anyhow::{bail, Context};
+use email_newsletter::configuration::get_configuration;
+use email_newsletter::newsletter_issues::{
+    discard_dead_letters, list_dead_letters, requeue_dead_letters, DeadLetterFilter,
+};
+use email_newsletter::startup::get_connection_pool;
+use uuid::Uuid;
+
+const USAGE: &str = "Usage:
+    dead_letters list
+    dead_letters requeue [--issue <issue_id>] [--error <text>]
+    dead_letters discard [--issue <issue_id>] [--error <text>]
+
+--issue only touches the deliveries of one issue, --error only the ones whose
+last error contains the given text. Discarding needs at least one of them.";
+
+enum Command {
+    List,
+    Requeue(DeadLetterFilter),
+    Discard(DeadLetterFilter),
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, anyhow::Error> {
+    let command = args.next().unwrap_or_default();
+    let mut filter = DeadLetterFilter::default();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--issue" => {
+                filter.issue_id = Some(
+                    Uuid::parse_str(&value)
+                        .with_context(|| format!("{} is not an issue id", value))?,
+                )
+            }
+            "--error" => filter.error_contains = Some(value),
+            other => bail!("Unknown option {}", other),
+        }
+    }
+
+    match command.as_str() {
+        "list" => Ok(Command::List),
+        "requeue" => Ok(Command::Requeue(filter)),
+        "discard" if filter.issue_id.is_none() && filter.error_contains.is_none() => {
+            bail!("Refusing to discard every failed delivery - pass --issue or --error")
+        }
+        "discard" => Ok(Command::Discard(filter)),
+        _ => bail!("Unknown command {:?}", command),
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let configuration = get_configuration().expect("Failed to read configuration");
+    let pool = get_connection_pool(&configuration.database);
+
+    match command {
+        Command::List => {
+            for group in list_dead_letters(&pool).await? {
+                println!("{} {}", group.issue_id, group.title);
+                for errors in group.errors {
+                    println!("  {} x {}", errors.deliveries.len(), errors.error);
+                }
+            }
+        }
+        Command::Requeue(filter) => {
+            let requeued = requeue_dead_letters(&pool, &filter).await?;
+            println!("Requeued {} deliveries", requeued);
+        }
+        Command::Discard(filter) => {
+            let discarded = discard_dead_letters(&pool, &filter).await?;
+            println!("Discarded {} deliveries", discarded);
+        }
+    }
+
+    Ok(())
+}
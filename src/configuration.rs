@@ -1,3 +1,6 @@
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+
 use secrecy::ExposeSecret;
 use secrecy::Secret;
 use serde_aux::prelude::deserialize_number_from_string;
@@ -7,6 +10,7 @@ use sqlx::ConnectOptions;
 
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
+use crate::tracking::Tracker;
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
@@ -14,6 +18,10 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub sunset: SunsetSettings,
+    pub rate_limit: RateLimitSettings,
+    pub signup_protection: SignupProtectionSettings,
+    pub email_policy: EmailPolicySettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -55,7 +63,7 @@ impl DatabaseSettings {
         PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
-            .password(&self.password.expose_secret())
+            .password(self.password.expose_secret())
             .port(self.port)
             .ssl_mode(ssl_mode)
     }
@@ -69,6 +77,128 @@ pub struct EmailClientSettings {
     pub timeout_milliseconds: u64,
 }
 
+/// When subscribers who stopped reading are asked whether they want to stay,
+/// and taken off the list if they do not answer.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SunsetSettings {
+    /// Off unless turned on: Apple Mail Privacy Protection hides real opens,
+    /// so people reading in Apple Mail would look inactive.
+    pub enabled: bool,
+    /// How many issues in a row a subscriber has to leave unread.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub inactive_issues: u32,
+    /// How long they have to answer the re-engagement email.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub grace_days: u32,
+}
+
+/// How often clients can call the public endpoints that send emails or check
+/// passwords.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    /// The reverse proxies in front of the application. Only requests they
+    /// forwarded are attributed to the client in their `X-Forwarded-For`
+    /// header.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    pub subscribe_per_ip: RateLimit,
+    pub subscribe_per_email: RateLimit,
+    pub confirm_per_ip: RateLimit,
+    pub login_per_ip: RateLimit,
+    pub login_per_username: RateLimit,
+}
+
+/// At most `requests` requests every `window_seconds` seconds.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct RateLimit {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub requests: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: NonZeroU32,
+}
+
+/// How signups from the forms served here are told apart from bots.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SignupProtectionSettings {
+    /// Off unless turned on: plain HTML forms on other sites cannot carry a
+    /// form token. On, each signup needs the token of a form served here, or
+    /// of `/subscriptions/challenge` for JSON clients, which works once.
+    pub enabled: bool,
+    /// Signups sent sooner than this after their form was served are refused.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_fill_seconds: u64,
+    /// Above 0, every signup needs a proof of work: a nonce that gives the
+    /// SHA-256 hash of its form token this many leading zero bits. Browsers
+    /// find it with JavaScript, so plain HTML forms stop working.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub proof_of_work_bits: u8,
+    /// Every signup needs a solved CAPTCHA, if set. Plain HTML forms stop
+    /// working too.
+    #[serde(default)]
+    pub captcha: Option<CaptchaSettings>,
+}
+
+/// Which addresses cannot sign up, on top of the domains admins block.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct EmailPolicySettings {
+    /// Refuses addresses of disposable email services.
+    pub block_disposable: bool,
+    /// Refuses addresses like postmaster@ or noreply@.
+    pub block_role_accounts: bool,
+    /// Replaces the bundled list of disposable email domains, if set. One
+    /// domain per line.
+    #[serde(default)]
+    pub disposable_domains_file: Option<String>,
+    /// Treats Gmail addresses that differ only in dots or `+tags` as the
+    /// same subscriber. Only new signups are checked this way: the duplicates
+    /// report finds the subscribers it would have merged.
+    #[serde(default)]
+    pub normalize_gmail: bool,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaProvider {
+    HCaptcha,
+    Turnstile,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct CaptchaSettings {
+    pub provider: CaptchaProvider,
+    pub site_key: String,
+    pub secret_key: Secret<String>,
+    /// Where answers are checked, if not at the provider, e.g. a mock when
+    /// testing locally.
+    #[serde(default)]
+    pub verify_url: Option<String>,
+}
+
+impl RateLimitSettings {
+    /// How long the requests counted towards any limit matter.
+    pub fn longest_window(&self) -> std::time::Duration {
+        let seconds = [
+            self.subscribe_per_ip,
+            self.subscribe_per_email,
+            self.confirm_per_ip,
+            self.login_per_ip,
+            self.login_per_username,
+        ]
+        .iter()
+        .map(|limit| limit.window_seconds.get())
+        .max()
+        .unwrap_or(1);
+        std::time::Duration::from_secs(seconds.into())
+    }
+}
+
+impl ApplicationSettings {
+    pub fn tracker(&self) -> Tracker {
+        Tracker::new(self.base_url.clone(), self.hmac_secret.clone())
+    }
+}
+
 impl EmailClientSettings {
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
@@ -0,0 +1,44 @@
+/// How the recipients of an issue are spread over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Everybody is sent the issue at the scheduled time.
+    AtOnce,
+    /// The issue goes out at the same wall-clock time in each subscriber's
+    /// timezone, one wave per timezone.
+    LocalTime,
+}
+
+impl DeliveryMode {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "at_once" => Ok(Self::AtOnce),
+            "local_time" => Ok(Self::LocalTime),
+            other => Err(format!("{} is not a valid delivery mode", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AtOnce => "at_once",
+            Self::LocalTime => "local_time",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeliveryMode;
+    use claim::assert_err;
+
+    #[test]
+    fn modes_round_trip_through_their_string_form() {
+        for mode in [DeliveryMode::AtOnce, DeliveryMode::LocalTime] {
+            assert_eq!(DeliveryMode::parse(mode.as_str()), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn unknown_modes_are_rejected() {
+        assert_err!(DeliveryMode::parse("whenever"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Local parts that reach a team or a machine rather than a reader.
+const ROLE_ACCOUNTS: &[&str] = &[
+    "abuse",
+    "admin",
+    "do-not-reply",
+    "donotreply",
+    "hostmaster",
+    "mailer-daemon",
+    "no-reply",
+    "noreply",
+    "postmaster",
+    "root",
+    "security",
+    "webmaster",
+];
+
+/// The disposable email domains the app ships with.
+pub const BUNDLED_DISPOSABLE_DOMAINS: &str = include_str!("disposable_domains.txt");
+
+/// The domains in a list with one per line. Blank lines and `#` comments are
+/// skipped.
+pub fn parse_domain_list(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Which addresses cannot subscribe, on top of the invalid ones. The default
+/// policy lets every address through.
+#[derive(Debug, Clone, Default)]
+pub struct EmailPolicy {
+    pub disposable_domains: Arc<HashSet<String>>,
+    pub block_role_accounts: bool,
+    pub blocked_domains: Vec<String>,
+    /// Treats Gmail addresses that differ in dots or `+tags` as one.
+    pub normalize_gmail: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    Disposable,
+    RoleAccount,
+    BlockedDomain,
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            PolicyViolation::Disposable => {
+                "Disposable email addresses cannot subscribe, please use a permanent one"
+            }
+            PolicyViolation::RoleAccount => {
+                "Please use a personal email address rather than one like postmaster@ or noreply@"
+            }
+            PolicyViolation::BlockedDomain => "Email addresses at this domain cannot subscribe",
+        };
+        f.write_str(message)
+    }
+}
+
+/// `domain` and the domains above it, e.g. `mx.example.com`, `example.com`
+/// and `com`.
+fn domain_and_parents(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |domain| {
+        domain.split_once('.').map(|(_, parent)| parent)
+    })
+}
+
+impl EmailPolicy {
+    pub fn check(&self, email: &str) -> Result<(), PolicyViolation> {
+        let Some((local, domain)) = email.rsplit_once('@') else {
+            return Ok(());
+        };
+        let domain = domain.trim_end_matches('.').to_lowercase();
+
+        if domain_and_parents(&domain).any(|d| self.blocked_domains.iter().any(|b| b == d)) {
+            return Err(PolicyViolation::BlockedDomain);
+        }
+        if domain_and_parents(&domain).any(|d| self.disposable_domains.contains(d)) {
+            return Err(PolicyViolation::Disposable);
+        }
+        if self.block_role_accounts {
+            // Tags do not change who reads the address.
+            let local = local.split('+').next().unwrap_or(local).to_lowercase();
+            if ROLE_ACCOUNTS.contains(&local.as_str()) {
+                return Err(PolicyViolation::RoleAccount);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::assert_ok;
+
+    fn policy() -> EmailPolicy {
+        EmailPolicy {
+            disposable_domains: Arc::new(parse_domain_list(BUNDLED_DISPOSABLE_DOMAINS)),
+            block_role_accounts: true,
+            blocked_domains: vec!["spam.example".into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_default_policy_lets_everything_through() {
+        assert_ok!(EmailPolicy::default().check("postmaster@mailinator.com"));
+    }
+
+    #[test]
+    fn disposable_domains_and_their_subdomains_are_refused() {
+        assert_eq!(
+            policy().check("ursula@Mailinator.com"),
+            Err(PolicyViolation::Disposable)
+        );
+        assert_eq!(
+            policy().check("ursula@eu.mailinator.com"),
+            Err(PolicyViolation::Disposable)
+        );
+        assert_ok!(policy().check("ursula@notmailinator.com"));
+    }
+
+    #[test]
+    fn role_accounts_are_refused_with_or_without_tags() {
+        assert_eq!(
+            policy().check("PostMaster@example.com"),
+            Err(PolicyViolation::RoleAccount)
+        );
+        assert_eq!(
+            policy().check("noreply+news@example.com"),
+            Err(PolicyViolation::RoleAccount)
+        );
+        assert_ok!(policy().check("post.master@example.com"));
+    }
+
+    #[test]
+    fn blocked_domains_are_refused() {
+        assert_eq!(
+            policy().check("ursula@mail.spam.example"),
+            Err(PolicyViolation::BlockedDomain)
+        );
+    }
+
+    #[test]
+    fn domain_lists_skip_comments_and_blank_lines() {
+        let domains = parse_domain_list("# comment\n\n  Example.COM \n");
+        assert_eq!(domains, HashSet::from(["example.com".to_owned()]));
+    }
+}
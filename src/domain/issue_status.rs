@@ -0,0 +1,83 @@
+/// Where a newsletter issue is in its lifecycle:
+/// draft → scheduled → sending → sent. A sending issue can be paused and
+/// resumed, and cancelled as a way out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueStatus {
+    Draft,
+    Scheduled,
+    Sending,
+    Paused,
+    Sent,
+    Cancelled,
+}
+
+impl IssueStatus {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "draft" => Ok(Self::Draft),
+            "scheduled" => Ok(Self::Scheduled),
+            "sending" => Ok(Self::Sending),
+            "paused" => Ok(Self::Paused),
+            "sent" => Ok(Self::Sent),
+            "cancelled" => Ok(Self::Cancelled),
+            other => Err(format!("{} is not a valid issue status", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Scheduled => "scheduled",
+            Self::Sending => "sending",
+            Self::Paused => "paused",
+            Self::Sent => "sent",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Drafts and scheduled issues can be edited; once delivery has been set
+    /// in motion the content is frozen.
+    pub fn is_editable(&self) -> bool {
+        matches!(self, Self::Draft | Self::Scheduled)
+    }
+}
+
+impl std::fmt::Display for IssueStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueStatus;
+    use claim::assert_err;
+
+    #[test]
+    fn statuses_round_trip_through_their_string_form() {
+        for status in [
+            IssueStatus::Draft,
+            IssueStatus::Scheduled,
+            IssueStatus::Sending,
+            IssueStatus::Paused,
+            IssueStatus::Sent,
+            IssueStatus::Cancelled,
+        ] {
+            assert_eq!(IssueStatus::parse(status.as_str()), Ok(status));
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        assert_err!(IssueStatus::parse("published"));
+    }
+
+    #[test]
+    fn only_issues_that_have_not_gone_out_are_editable() {
+        assert!(IssueStatus::Draft.is_editable());
+        assert!(IssueStatus::Scheduled.is_editable());
+        assert!(!IssueStatus::Sending.is_editable());
+        assert!(!IssueStatus::Paused.is_editable());
+        assert!(!IssueStatus::Sent.is_editable());
+    }
+}
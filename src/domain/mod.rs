@@ -1,11 +1,23 @@
+mod delivery_mode;
+mod email_policy;
+mod issue_status;
 mod new_subscriber;
 mod password;
+mod scheduled_time;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_timezone;
 mod subscription_token;
 
+pub use delivery_mode::DeliveryMode;
+pub use email_policy::{
+    parse_domain_list, EmailPolicy, PolicyViolation, BUNDLED_DISPOSABLE_DOMAINS,
+};
+pub use issue_status::IssueStatus;
 pub use new_subscriber::NewSubscriber;
 pub use password::Password;
-pub use subscriber_email::SubscriberEmail;
+pub use scheduled_time::ScheduledTime;
+pub use subscriber_email::{normalize_domain, SubscriberEmail};
 pub use subscriber_name::SubscriberName;
+pub use subscriber_timezone::SubscriberTimezone;
 pub use subscription_token::SubscriptionToken;
@@ -46,7 +46,5 @@ impl std::fmt::Display for Password {
 }
 
 fn validate_password(candidate_password: &str) -> bool {
-    let correct_length = candidate_password.len() >= 12 && candidate_password.len() <= 128;
-
-    return correct_length;
+    candidate_password.len() >= 12 && candidate_password.len() <= 128
 }
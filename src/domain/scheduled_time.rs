@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// A point in the future at which an issue should go out, along with the
+/// wall-clock time and timezone it was picked in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledTime {
+    at: DateTime<Utc>,
+    local: NaiveDateTime,
+    timezone: Tz,
+}
+
+impl ScheduledTime {
+    /// Parses a wall-clock time as sent by a `datetime-local` input
+    /// (`2026-10-20T09:00`, optionally with seconds) in the given IANA
+    /// timezone.
+    ///
+    /// Times that do not exist in that timezone (e.g. skipped by a DST change)
+    /// and times that are not after `now` are rejected.
+    pub fn parse(local: &str, timezone: &str, now: DateTime<Utc>) -> Result<Self, String> {
+        let timezone = Tz::from_str(timezone.trim())
+            .map_err(|_| format!("{} is not a valid timezone", timezone))?;
+        let local = local.trim();
+        let naive = NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S"))
+            .map_err(|_| format!("{} is not a valid date and time", local))?;
+        let scheduled_at = timezone
+            .from_local_datetime(&naive)
+            .earliest()
+            .ok_or_else(|| format!("{} does not exist in {}", local, timezone))?
+            .with_timezone(&Utc);
+
+        if scheduled_at <= now {
+            return Err("The scheduled time must be in the future".to_string());
+        }
+
+        Ok(Self {
+            at: scheduled_at,
+            local: naive,
+            timezone,
+        })
+    }
+
+    pub fn local(&self) -> NaiveDateTime {
+        self.local
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+}
+
+impl AsRef<DateTime<Utc>> for ScheduledTime {
+    fn as_ref(&self) -> &DateTime<Utc> {
+        &self.at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScheduledTime;
+    use chrono::{TimeZone, Utc};
+    use claim::assert_err;
+
+    fn now() -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn local_time_is_converted_to_utc() {
+        let scheduled =
+            ScheduledTime::parse("2026-10-20T09:00", "America/New_York", now()).unwrap();
+
+        assert_eq!(
+            *scheduled.as_ref(),
+            Utc.with_ymd_and_hms(2026, 10, 20, 13, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn seconds_are_accepted() {
+        let scheduled = ScheduledTime::parse("2026-10-20T09:00:30", "UTC", now()).unwrap();
+
+        assert_eq!(
+            *scheduled.as_ref(),
+            Utc.with_ymd_and_hms(2026, 10, 20, 9, 0, 30).unwrap()
+        );
+    }
+
+    #[test]
+    fn times_in_the_past_are_rejected() {
+        assert_err!(ScheduledTime::parse("2026-10-18T11:59", "UTC", now()));
+    }
+
+    #[test]
+    fn unknown_timezones_are_rejected() {
+        assert_err!(ScheduledTime::parse(
+            "2026-10-20T09:00",
+            "Mars/Olympus",
+            now()
+        ));
+    }
+
+    #[test]
+    fn times_skipped_by_daylight_saving_are_rejected() {
+        assert_err!(ScheduledTime::parse(
+            "2027-03-14T02:30",
+            "America/New_York",
+            now()
+        ));
+    }
+}
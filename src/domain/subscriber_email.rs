@@ -1,34 +1,94 @@
 use validator::validate_email;
 
+use crate::domain::EmailPolicy;
+
+/// Domains whose mailboxes ignore dots and `+tags` in the local part.
+const GMAIL_DOMAINS: &[&str] = &["gmail.com", "googlemail.com"];
+
+/// An email address, with its domain lowercase and in ASCII as it travels on
+/// the wire. The local part is kept as given: it is up to the receiving
+/// server to decide whether case matters.
 #[derive(Debug, Clone)]
-pub struct SubscriberEmail(String);
+pub struct SubscriberEmail {
+    address: String,
+    normalized: String,
+}
+
+/// The ASCII form of a domain, e.g. `xn--bcher-kva.example` for
+/// `Bücher.example`.
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = idna::domain_to_ascii(domain.trim_end_matches('.')).ok()?;
+    (!domain.is_empty()).then_some(domain)
+}
 
 impl SubscriberEmail {
     pub fn parse(s: String) -> Result<Self, String> {
-        if validate_email(&s) {
-            Ok(Self(s))
-        } else {
-            Err(format!("{} is not a valid subscriber email", s))
+        let invalid = || format!("{} is not a valid subscriber email", s);
+        if !validate_email(&s) {
+            return Err(invalid());
+        }
+        let (local, domain) = s.rsplit_once('@').ok_or_else(invalid)?;
+        let domain = normalize_domain(domain).ok_or_else(invalid)?;
+
+        Ok(Self {
+            normalized: format!("{}@{}", local.to_lowercase(), domain),
+            address: format!("{}@{}", local, domain),
+        })
+    }
+
+    /// Parses a new signup, which must also pass `policy`.
+    pub fn parse_with_policy(s: String, policy: &EmailPolicy) -> Result<Self, String> {
+        let email = Self::parse(s)?;
+        policy.check(email.as_ref()).map_err(|e| e.to_string())?;
+        Ok(email.normalize_for(policy))
+    }
+
+    /// Applies the normalization rules of `policy`, without checking the
+    /// address against it.
+    pub fn normalize_for(mut self, policy: &EmailPolicy) -> Self {
+        if policy.normalize_gmail {
+            self.normalized = normalize_gmail(&self.normalized);
+        }
+        self
+    }
+
+    /// The form that tells whether two addresses reach the same inbox:
+    /// lowercase, and for Gmail, without dots or tags if the policy says so.
+    /// Only one subscriber can have each.
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+}
+
+/// `u.le.guin+news@googlemail.com` and `uleguin@gmail.com` are the same
+/// inbox.
+fn normalize_gmail(normalized: &str) -> String {
+    match normalized.rsplit_once('@') {
+        Some((local, domain)) if GMAIL_DOMAINS.contains(&domain) => {
+            let local = local.split('+').next().unwrap_or(local).replace('.', "");
+            format!("{}@gmail.com", local)
         }
+        _ => normalized.to_owned(),
     }
 }
 
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.address
     }
 }
 
 impl std::fmt::Display for SubscriberEmail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.address.fmt(f)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::SubscriberEmail;
-    use claim::assert_err;
+    use crate::domain::EmailPolicy;
+    use claim::{assert_err, assert_ok};
 
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
@@ -51,6 +111,57 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn the_policy_is_checked_after_the_address_is_valid() {
+        let policy = EmailPolicy {
+            block_role_accounts: true,
+            ..Default::default()
+        };
+        assert_err!(SubscriberEmail::parse_with_policy(
+            "postmaster@example.com".into(),
+            &policy
+        ));
+        assert_ok!(SubscriberEmail::parse_with_policy(
+            "ursula@example.com".into(),
+            &policy
+        ));
+    }
+
+    #[test]
+    fn domains_are_lowercase_and_ascii_but_local_parts_are_kept() {
+        let email = SubscriberEmail::parse("Ursula@Bücher.Example".into()).unwrap();
+
+        assert_eq!(email.as_ref(), "Ursula@xn--bcher-kva.example");
+        assert_eq!(email.normalized(), "ursula@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn gmail_dots_and_tags_are_dropped_when_the_policy_says_so() {
+        let mut policy = EmailPolicy::default();
+        let parse = |policy: &EmailPolicy| {
+            SubscriberEmail::parse_with_policy("U.Le.Guin+news@googlemail.com".into(), policy)
+                .unwrap()
+        };
+
+        assert_eq!(parse(&policy).normalized(), "u.le.guin+news@googlemail.com");
+        policy.normalize_gmail = true;
+        assert_eq!(parse(&policy).normalized(), "uleguin@gmail.com");
+        assert_eq!(parse(&policy).as_ref(), "U.Le.Guin+news@googlemail.com");
+    }
+
+    #[test]
+    fn dots_and_tags_matter_outside_gmail() {
+        let policy = EmailPolicy {
+            normalize_gmail: true,
+            ..Default::default()
+        };
+        let email =
+            SubscriberEmail::parse_with_policy("u.le.guin+news@example.com".into(), &policy)
+                .unwrap();
+
+        assert_eq!(email.normalized(), "u.le.guin+news@example.com");
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmailFixture(pub String);
 
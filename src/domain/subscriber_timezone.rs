@@ -0,0 +1,52 @@
+use std::str::FromStr;
+
+use chrono_tz::Tz;
+
+/// The IANA timezone a subscriber reads their email in, e.g. `Europe/Paris`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberTimezone(Tz);
+
+impl SubscriberTimezone {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Tz::from_str(s.trim())
+            .map(Self)
+            .map_err(|_| format!("{} is not a valid timezone", s))
+    }
+}
+
+impl AsRef<str> for SubscriberTimezone {
+    fn as_ref(&self) -> &str {
+        self.0.name()
+    }
+}
+
+impl From<SubscriberTimezone> for Tz {
+    fn from(timezone: SubscriberTimezone) -> Self {
+        timezone.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberTimezone;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn iana_names_are_valid() {
+        assert_ok!(SubscriberTimezone::parse("Asia/Tokyo"));
+        assert_ok!(SubscriberTimezone::parse("UTC"));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        let timezone = SubscriberTimezone::parse(" Europe/Paris ").unwrap();
+        assert_eq!(timezone.as_ref(), "Europe/Paris");
+    }
+
+    #[test]
+    fn offsets_and_garbage_are_rejected() {
+        assert_err!(SubscriberTimezone::parse("+02:00"));
+        assert_err!(SubscriberTimezone::parse(""));
+        assert_err!(SubscriberTimezone::parse("Mars/Olympus"));
+    }
+}
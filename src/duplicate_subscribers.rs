@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{EmailPolicy, SubscriberEmail};
+
+/// A subscriber who shares their inbox with others.
+#[derive(Debug)]
+pub struct DuplicateSubscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// Subscribers whose addresses reach the same inbox. The first one is kept
+/// when they are merged.
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub normalized_email: String,
+    pub subscribers: Vec<DuplicateSubscriber>,
+}
+
+/// Confirmed subscribers are kept over pending ones, and those over the ones
+/// who left. Same as the migration that first marked duplicates.
+fn status_rank(status: &str) -> u8 {
+    match status {
+        "confirmed" => 0,
+        "pending_confirmation" => 1,
+        _ => 2,
+    }
+}
+
+/// The normalized form of a stored address under today's rules, which may
+/// differ from the one stored when the subscriber signed up.
+fn normalize(email: &str, policy: &EmailPolicy) -> String {
+    match SubscriberEmail::parse(email.to_owned()) {
+        Ok(email) => email.normalize_for(policy).normalized().to_owned(),
+        Err(_) => email.to_lowercase(),
+    }
+}
+
+/// Every group of subscribers that reach the same inbox, by normalized
+/// address.
+#[tracing::instrument(skip(pool, policy))]
+pub async fn find_duplicate_subscribers(
+    pool: &PgPool,
+    policy: &EmailPolicy,
+) -> Result<Vec<DuplicateGroup>, sqlx::Error> {
+    let subscribers = sqlx::query_as!(
+        DuplicateSubscriber,
+        "SELECT id, email, name, status, subscribed_at FROM subscriptions"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut groups: BTreeMap<String, Vec<DuplicateSubscriber>> = BTreeMap::new();
+    for subscriber in subscribers {
+        groups
+            .entry(normalize(&subscriber.email, policy))
+            .or_default()
+            .push(subscriber);
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|(_, subscribers)| subscribers.len() > 1)
+        .map(|(normalized_email, mut subscribers)| {
+            subscribers.sort_by_key(|s| (status_rank(&s.status), s.subscribed_at, s.id));
+            DuplicateGroup {
+                normalized_email,
+                subscribers,
+            }
+        })
+        .collect())
+}
+
+/// Folds the group of `normalized_email` into its first subscriber: their
+/// opens, clicks, deliveries and dead letters move over, the others' pending
+/// deliveries are cancelled and they are deleted. Returns the kept subscriber and how many were
+/// merged into them, or `None` if there is no such group.
+#[tracing::instrument(skip(pool, policy))]
+pub async fn merge_duplicate_subscribers(
+    pool: &PgPool,
+    policy: &EmailPolicy,
+    normalized_email: &str,
+) -> Result<Option<(DuplicateSubscriber, usize)>, sqlx::Error> {
+    let Some(group) = find_duplicate_subscribers(pool, policy)
+        .await?
+        .into_iter()
+        .find(|group| group.normalized_email == normalized_email)
+    else {
+        return Ok(None);
+    };
+    let mut subscribers = group.subscribers.into_iter();
+    let kept = subscribers
+        .next()
+        .expect("Groups have more than one subscriber");
+    let (ids, emails): (Vec<Uuid>, Vec<String>) = subscribers.map(|s| (s.id, s.email)).unzip();
+
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        "UPDATE issue_opens SET subscriber_id = $1 WHERE subscriber_id = ANY($2)",
+        kept.id,
+        &ids,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE issue_clicks SET subscriber_id = $1 WHERE subscriber_id = ANY($2)",
+        kept.id,
+        &ids,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    // Deliveries are keyed by address, and an issue may have reached several
+    // addresses of the group: one row per issue moves over.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_log l
+        WHERE l.subscriber_email = ANY($2) AND EXISTS (
+            SELECT 1 FROM issue_delivery_log o
+            WHERE o.newsletter_issue_id = l.newsletter_issue_id
+                AND (o.subscriber_email = $1
+                    OR (o.subscriber_email = ANY($2) AND o.subscriber_email < l.subscriber_email))
+        )
+        "#,
+        kept.email,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE issue_delivery_log SET subscriber_email = $1 WHERE subscriber_email = ANY($2)",
+        kept.email,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM dead_letter_queue l
+        WHERE l.subscriber_email = ANY($2) AND EXISTS (
+            SELECT 1 FROM dead_letter_queue o
+            WHERE o.newsletter_issue_id = l.newsletter_issue_id
+                AND (o.subscriber_email = $1
+                    OR (o.subscriber_email = ANY($2) AND o.subscriber_email < l.subscriber_email))
+        )
+        "#,
+        kept.email,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE dead_letter_queue SET subscriber_email = $1 WHERE subscriber_email = ANY($2)",
+        kept.email,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET cancelled_at = NOW()
+        WHERE subscriber_email = ANY($1) AND cancelled_at IS NULL
+        "#,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)",
+        &ids
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE subscriptions SET duplicate_of = NULL WHERE id = ANY($1)",
+        &ids
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!("DELETE FROM subscriptions WHERE id = ANY($1)", &ids)
+        .execute(transaction.as_mut())
+        .await?;
+    // New signups of any of the merged addresses find the kept subscriber.
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET normalized_email = $2, duplicate_of = NULL
+        WHERE id = $1
+        "#,
+        kept.id,
+        normalized_email,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+
+    Ok(Some((kept, ids.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmed_subscribers_are_kept_first() {
+        let mut statuses = ["unsubscribed", "pending_confirmation", "confirmed"];
+        statuses.sort_by_key(|status| status_rank(status));
+
+        assert_eq!(
+            statuses,
+            ["confirmed", "pending_confirmation", "unsubscribed"]
+        );
+    }
+
+    #[test]
+    fn stored_addresses_are_normalized_with_todays_rules() {
+        let policy = EmailPolicy {
+            normalize_gmail: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normalize("U.Le.Guin@GMail.com", &policy),
+            "uleguin@gmail.com"
+        );
+        assert_eq!(normalize("not an email", &policy), "not an email");
+    }
+}
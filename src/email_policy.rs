@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::EmailPolicySettings;
+use crate::domain::{
+    normalize_domain, parse_domain_list, EmailPolicy, PolicyViolation, BUNDLED_DISPOSABLE_DOMAINS,
+};
+
+/// A domain admins refuse signups from.
+#[derive(Debug)]
+pub struct BlockedDomain {
+    pub domain: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An active subscriber whose address would not pass the policy today.
+#[derive(Debug)]
+pub struct FlaggedSubscriber {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub violation: PolicyViolation,
+}
+
+/// The domain of an email address as stored in the blocklist, e.g.
+/// `example.com` for `@Example.com`. Internationalized domains are stored in
+/// their ASCII form, which is what addresses carry on the wire.
+pub fn parse_blocked_domain(s: &str) -> Result<String, String> {
+    let invalid = || format!("{} is not a valid domain", s);
+    let domain = s.trim().trim_start_matches('@').trim_end_matches('.');
+    if domain.contains(|c: char| "@/:?#[]\\".contains(c) || c.is_whitespace()) {
+        return Err(invalid());
+    }
+    match normalize_domain(domain) {
+        Some(domain) if domain.contains('.') && domain.parse::<IpAddr>().is_err() => Ok(domain),
+        _ => Err(invalid()),
+    }
+}
+
+/// Builds the policy signups are checked against from the configuration and
+/// the domains admins blocked.
+#[derive(Clone)]
+pub struct EmailPolicyLoader {
+    settings: Arc<EmailPolicySettings>,
+    disposable_domains: Arc<HashSet<String>>,
+}
+
+impl EmailPolicyLoader {
+    /// Reads the list of disposable domains, if the policy needs it.
+    pub fn new(settings: EmailPolicySettings) -> Result<Self, anyhow::Error> {
+        let disposable_domains =
+            match (&settings.disposable_domains_file, settings.block_disposable) {
+                (_, false) => HashSet::new(),
+                (Some(path), true) => {
+                    parse_domain_list(&std::fs::read_to_string(path).with_context(|| {
+                        format!("Failed to read the disposable domains in {}", path)
+                    })?)
+                }
+                (None, true) => parse_domain_list(BUNDLED_DISPOSABLE_DOMAINS),
+            };
+        Ok(Self {
+            settings: Arc::new(settings),
+            disposable_domains: Arc::new(disposable_domains),
+        })
+    }
+
+    pub fn settings(&self) -> &EmailPolicySettings {
+        &self.settings
+    }
+
+    /// How many disposable domains signups are checked against.
+    pub fn disposable_domain_count(&self) -> usize {
+        self.disposable_domains.len()
+    }
+
+    pub async fn load(&self, pool: &PgPool) -> Result<EmailPolicy, sqlx::Error> {
+        let blocked_domains = sqlx::query_scalar!("SELECT domain FROM blocked_email_domains")
+            .fetch_all(pool)
+            .await?;
+        Ok(EmailPolicy {
+            disposable_domains: self.disposable_domains.clone(),
+            block_role_accounts: self.settings.block_role_accounts,
+            blocked_domains,
+            normalize_gmail: self.settings.normalize_gmail,
+        })
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_blocked_domains(pool: &PgPool) -> Result<Vec<BlockedDomain>, sqlx::Error> {
+    sqlx::query_as!(
+        BlockedDomain,
+        "SELECT domain, created_at FROM blocked_email_domains ORDER BY domain"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns `false` if the domain was already blocked.
+#[tracing::instrument(skip(pool))]
+pub async fn insert_blocked_domain(pool: &PgPool, domain: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO blocked_email_domains (domain) VALUES ($1) ON CONFLICT DO NOTHING",
+        domain,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns `false` if the domain was not blocked.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_blocked_domain(pool: &PgPool, domain: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM blocked_email_domains WHERE domain = $1",
+        domain
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Subscribers who signed up before the policy refused their address, newest
+/// first. Unsubscribed ones are left out, as they get no more emails.
+#[tracing::instrument(skip(pool, policy))]
+pub async fn list_flagged_subscribers(
+    pool: &PgPool,
+    policy: &EmailPolicy,
+) -> Result<Vec<FlaggedSubscriber>, sqlx::Error> {
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, name, status
+        FROM subscriptions
+        WHERE status <> 'unsubscribed'
+        ORDER BY subscribed_at DESC, email
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscribers
+        .into_iter()
+        .filter_map(|s| {
+            let violation = policy.check(&s.email).err()?;
+            Some(FlaggedSubscriber {
+                subscriber_id: s.id,
+                email: s.email,
+                name: s.name,
+                status: s.status,
+                violation,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_blocked_domain;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn domains_are_stored_lowercase_without_the_at_sign() {
+        assert_ok_eq!(parse_blocked_domain(" @Spam.Example. "), "spam.example");
+    }
+
+    #[test]
+    fn internationalized_domains_are_stored_in_ascii() {
+        assert_ok_eq!(
+            parse_blocked_domain("bücher.example"),
+            "xn--bcher-kva.example"
+        );
+    }
+
+    #[test]
+    fn hosts_that_are_not_domains_are_rejected() {
+        assert_err!(parse_blocked_domain("localhost"));
+        assert_err!(parse_blocked_domain("127.0.0.1"));
+        assert_err!(parse_blocked_domain("user@example.com"));
+        assert_err!(parse_blocked_domain(""));
+    }
+}
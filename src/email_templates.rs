@@ -25,3 +25,19 @@ pub struct AlreadySubscribedEmailHtml {
 pub struct AlreadySubscribedEmailText {
     pub subscriber_name: String,
 }
+
+#[derive(Template)]
+#[template(path = "emails/reengagement.html")]
+pub struct ReengagementEmailHtml {
+    pub subscriber_name: String,
+    pub keep_link: String,
+    pub grace_days: u32,
+}
+
+#[derive(Template)]
+#[template(path = "emails/reengagement.txt")]
+pub struct ReengagementEmailText {
+    pub subscriber_name: String,
+    pub keep_link: String,
+    pub grace_days: u32,
+}
@@ -0,0 +1,7 @@
+mod poller;
+mod sources;
+mod templates;
+
+pub use poller::*;
+pub use sources::*;
+pub use templates::*;
@@ -0,0 +1,495 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use reqwest::{header, StatusCode};
+use scraper::Html;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{list_feed_sources, render_feed_issue, FeedPost, FeedSource};
+use crate::{
+    configuration::Settings,
+    newsletter_issues::{excerpt, insert_draft, publish_issue, DraftContent},
+    rendering::render_issue_body,
+    startup::get_connection_pool,
+};
+
+// How often to check the feeds for new posts
+const POLL_INTERVAL_MINUTES: u64 = 15;
+
+// How often a digest goes out
+const DIGEST_INTERVAL_DAYS: i64 = 7;
+
+const FETCH_TIMEOUT_SECONDS: u64 = 30;
+
+// How long the summary of a post gets, in characters
+const SUMMARY_LENGTH: usize = 300;
+
+/// What one round of polling did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PollOutcome {
+    pub new_posts: u64,
+    pub issues: u64,
+}
+
+/// A post as stored, waiting for its issue.
+struct FeedItem {
+    guid: String,
+    title: String,
+    link: String,
+    summary: String,
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl From<FeedItem> for FeedPost {
+    fn from(item: FeedItem) -> Self {
+        FeedPost {
+            title: item.title,
+            link: item.link,
+            summary: item.summary,
+            published_at: item.published_at,
+        }
+    }
+}
+
+/// A feed as fetched, with the validators to ask for it again.
+struct FetchedFeed {
+    items: Vec<FeedItem>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// The client feeds are fetched with.
+pub fn feed_http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECONDS))
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+        .unwrap()
+}
+
+pub async fn run_feed_poller_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let http_client = feed_http_client();
+    poller_loop(
+        &connection_pool,
+        &http_client,
+        &configuration.application.base_url,
+    )
+    .await
+}
+
+async fn poller_loop(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match poll_feed_sources(pool, http_client, base_url).await {
+            Ok(outcome) => {
+                if outcome != PollOutcome::default() {
+                    tracing::info!(
+                        "Found {} new posts, created {} issues",
+                        outcome.new_posts,
+                        outcome.issues
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to poll the feed sources"
+                );
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_MINUTES * 60)).await;
+    }
+}
+
+/// Polls every feed source and turns their new posts into issues.
+///
+/// A source that fails, e.g. because its feed is down, keeps the error for
+/// the admin to see and does not hold the others up.
+pub async fn poll_feed_sources(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    base_url: &str,
+) -> Result<PollOutcome, anyhow::Error> {
+    let mut total = PollOutcome::default();
+    for source in list_feed_sources(pool).await? {
+        let error = match poll_feed_source(pool, http_client, base_url, &source).await {
+            Ok(outcome) => {
+                total.new_posts += outcome.new_posts;
+                total.issues += outcome.issues;
+                None
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    feed_source_id = %source.feed_source_id,
+                    "Failed to poll a feed source"
+                );
+                Some(format!("{:#}", e))
+            }
+        };
+        record_poll_error(pool, source.feed_source_id, error.as_deref()).await?;
+    }
+
+    Ok(total)
+}
+
+/// Fetches the feed of `source`, stores the posts it has not seen yet and
+/// writes the issues that are due.
+///
+/// Posts already in the feed the first time it is fetched are skipped, so
+/// adding a blog does not mail its whole history. Without a digest, each new
+/// post gets an issue of its own. With one, the latest posts are bundled once
+/// a week and the posts it has no room for are skipped.
+#[tracing::instrument(skip_all, fields(feed_source_id=%source.feed_source_id))]
+pub async fn poll_feed_source(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    base_url: &str,
+    source: &FeedSource,
+) -> Result<PollOutcome, anyhow::Error> {
+    let first_poll = source.last_polled_at.is_none();
+    let feed = fetch_feed(http_client, source).await?;
+    let new_posts = store_feed(pool, source.feed_source_id, feed, first_poll)
+        .await
+        .context("Failed to store the posts")?;
+
+    let issues = match source.digest_size {
+        None => create_post_issues(pool, base_url, source).await?,
+        Some(size) => u64::from(create_digest_issue(pool, base_url, source, size).await?),
+    };
+
+    Ok(PollOutcome { new_posts, issues })
+}
+
+/// Fetches the feed, unless it did not change since it was last fetched.
+async fn fetch_feed(
+    http_client: &reqwest::Client,
+    source: &FeedSource,
+) -> Result<Option<FetchedFeed>, anyhow::Error> {
+    let mut request = http_client.get(&source.url);
+    if let Some(etag) = &source.etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &source.last_modified {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await.context("Failed to fetch the feed")?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response
+        .error_for_status()
+        .context("Failed to fetch the feed")?;
+
+    let validator = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned)
+    };
+    let etag = validator(header::ETAG);
+    let last_modified = validator(header::LAST_MODIFIED);
+
+    let bytes = response.bytes().await.context("Failed to fetch the feed")?;
+    let feed = feed_rs::parser::parse(bytes.as_ref()).context("The feed is not valid")?;
+    let items = feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let link = entry.links.first()?.href.clone();
+            let summary = entry
+                .summary
+                .as_ref()
+                .map(text_content)
+                .or_else(|| entry.content.as_ref()?.body.as_deref().map(html_text))
+                .unwrap_or_default();
+            Some(FeedItem {
+                title: entry.title.as_ref().map(text_content).unwrap_or_default(),
+                summary: excerpt(&summary, SUMMARY_LENGTH),
+                published_at: entry.published.or(entry.updated),
+                guid: entry.id,
+                link,
+            })
+        })
+        .collect();
+
+    Ok(Some(FetchedFeed {
+        items,
+        etag,
+        last_modified,
+    }))
+}
+
+/// A title or summary as plain text.
+fn text_content(text: &feed_rs::model::Text) -> String {
+    if text.content_type.subty() == "plain" {
+        text.content.clone()
+    } else {
+        html_text(&text.content)
+    }
+}
+
+fn html_text(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let text = fragment.root_element().text().collect::<String>();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Stores the posts of `feed` that were not seen before, and when it was
+/// fetched. Returns how many new posts wait for an issue.
+#[tracing::instrument(skip(pool, feed))]
+async fn store_feed(
+    pool: &PgPool,
+    feed_source_id: Uuid,
+    feed: Option<FetchedFeed>,
+    first_poll: bool,
+) -> Result<u64, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let mut new_posts = 0;
+
+    if let Some(feed) = feed {
+        for item in &feed.items {
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO feed_items (
+                    feed_source_id,
+                    guid,
+                    title,
+                    link,
+                    summary,
+                    published_at,
+                    skipped
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT DO NOTHING
+                "#,
+                feed_source_id,
+                item.guid,
+                item.title,
+                item.link,
+                item.summary,
+                item.published_at,
+                first_poll,
+            )
+            .execute(transaction.as_mut())
+            .await?;
+            new_posts += result.rows_affected();
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE feed_sources
+            SET etag = $2, last_modified = $3
+            WHERE feed_source_id = $1
+            "#,
+            feed_source_id,
+            feed.etag,
+            feed.last_modified,
+        )
+        .execute(transaction.as_mut())
+        .await?;
+    }
+
+    sqlx::query!(
+        "UPDATE feed_sources SET last_polled_at = NOW() WHERE feed_source_id = $1",
+        feed_source_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+
+    if first_poll {
+        return Ok(0);
+    }
+    Ok(new_posts)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn record_poll_error(
+    pool: &PgPool,
+    feed_source_id: Uuid,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE feed_sources SET last_error = $2 WHERE feed_source_id = $1",
+        feed_source_id,
+        error,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Writes an issue for each post waiting for one, oldest first. Returns how
+/// many issues were written.
+async fn create_post_issues(
+    pool: &PgPool,
+    base_url: &str,
+    source: &FeedSource,
+) -> Result<u64, anyhow::Error> {
+    let mut issues = 0;
+    loop {
+        let mut transaction = pool.begin().await?;
+        // Skip locked posts, which another poller is writing about.
+        let item = sqlx::query_as!(
+            FeedItem,
+            r#"
+            SELECT guid, title, link, summary, published_at
+            FROM feed_items
+            WHERE feed_source_id = $1 AND newsletter_issue_id IS NULL AND NOT skipped
+            ORDER BY COALESCE(published_at, fetched_at), guid
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            source.feed_source_id,
+        )
+        .fetch_optional(transaction.as_mut())
+        .await?;
+        let Some(item) = item else {
+            return Ok(issues);
+        };
+
+        create_issue(&mut transaction, base_url, source, vec![item]).await?;
+        transaction.commit().await?;
+        issues += 1;
+    }
+}
+
+/// Bundles the latest waiting posts into an issue, if the last digest went
+/// out a week ago or more. Returns whether an issue was written.
+async fn create_digest_issue(
+    pool: &PgPool,
+    base_url: &str,
+    source: &FeedSource,
+    digest_size: i16,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    // Lock the source, so that two pollers cannot both send this week's digest.
+    let last_digest_at = sqlx::query_scalar!(
+        r#"
+        SELECT last_digest_at
+        FROM feed_sources
+        WHERE feed_source_id = $1
+        FOR UPDATE
+        "#,
+        source.feed_source_id,
+    )
+    .fetch_one(transaction.as_mut())
+    .await?;
+    if last_digest_at > Utc::now() - chrono::Duration::days(DIGEST_INTERVAL_DAYS) {
+        return Ok(false);
+    }
+
+    let mut items = sqlx::query_as!(
+        FeedItem,
+        r#"
+        SELECT guid, title, link, summary, published_at
+        FROM feed_items
+        WHERE feed_source_id = $1 AND newsletter_issue_id IS NULL AND NOT skipped
+        ORDER BY COALESCE(published_at, fetched_at) DESC, guid
+        "#,
+        source.feed_source_id,
+    )
+    .fetch_all(transaction.as_mut())
+    .await?;
+    if items.is_empty() {
+        return Ok(false);
+    }
+
+    let left_over = items.split_off(items.len().min(digest_size as usize));
+    let left_over = left_over
+        .into_iter()
+        .map(|item| item.guid)
+        .collect::<Vec<_>>();
+    sqlx::query!(
+        r#"
+        UPDATE feed_items
+        SET skipped = TRUE
+        WHERE feed_source_id = $1 AND guid = ANY($2)
+        "#,
+        source.feed_source_id,
+        &left_over,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE feed_sources SET last_digest_at = NOW() WHERE feed_source_id = $1",
+        source.feed_source_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    create_issue(&mut transaction, base_url, source, items).await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Writes an issue about `items`, latest first, through the same path as an
+/// issue written by hand: as a draft, or published right away.
+async fn create_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    base_url: &str,
+    source: &FeedSource,
+    items: Vec<FeedItem>,
+) -> Result<Uuid, anyhow::Error> {
+    let guids = items
+        .iter()
+        .map(|item| item.guid.clone())
+        .collect::<Vec<_>>();
+    let posts = items.into_iter().map(FeedPost::from).collect::<Vec<_>>();
+    let content = render_feed_issue(source.templates(), &posts);
+
+    let issue_id = if source.auto_publish {
+        let body = render_issue_body(&content.markdown, "", "", "", base_url)
+            .context("Failed to render the issue")?;
+        publish_issue(
+            transaction,
+            &content.title,
+            "",
+            Some(&content.markdown),
+            &body,
+        )
+        .await?
+    } else {
+        let draft = DraftContent {
+            title: content.title,
+            markdown: Some(content.markdown),
+            ..Default::default()
+        };
+        insert_draft(transaction, &draft)
+            .await
+            .context("Failed to store the draft")?
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE feed_items
+        SET newsletter_issue_id = $3
+        WHERE feed_source_id = $1 AND guid = ANY($2)
+        "#,
+        source.feed_source_id,
+        &guids,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(issue_id)
+}
@@ -0,0 +1,258 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{DEFAULT_BODY_TEMPLATE, DEFAULT_POST_TEMPLATE, DEFAULT_TITLE_TEMPLATE};
+
+/// The largest number of posts a digest can bundle.
+pub const MAX_DIGEST_SIZE: i16 = 50;
+
+/// A blog whose new posts are turned into issues.
+#[derive(Debug, Clone)]
+pub struct FeedSource {
+    pub feed_source_id: Uuid,
+    pub name: String,
+    pub url: String,
+    /// Sends the issues right away instead of leaving them as drafts.
+    pub auto_publish: bool,
+    /// Bundles up to this many of the latest posts into one issue a week,
+    /// instead of one issue per post.
+    pub digest_size: Option<i16>,
+    pub title_template: String,
+    pub body_template: String,
+    pub post_template: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_digest_at: DateTime<Utc>,
+    /// How many of its posts made it into an issue.
+    pub mailed_posts: i64,
+}
+
+/// What an admin chooses about a feed source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedSourceSettings {
+    pub name: String,
+    pub url: String,
+    pub auto_publish: bool,
+    pub digest_size: Option<i16>,
+    pub title_template: String,
+    pub body_template: String,
+    pub post_template: String,
+}
+
+impl FeedSourceSettings {
+    /// Settings for a new source, with the default templates.
+    pub fn new(name: String, url: String) -> Self {
+        Self {
+            name,
+            url,
+            auto_publish: false,
+            digest_size: None,
+            title_template: DEFAULT_TITLE_TEMPLATE.to_owned(),
+            body_template: DEFAULT_BODY_TEMPLATE.to_owned(),
+            post_template: DEFAULT_POST_TEMPLATE.to_owned(),
+        }
+    }
+
+    /// Checks the settings make sense, returning what is wrong otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The feed needs a name".into());
+        }
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err("The feed URL must be an http or https URL".into()),
+        }
+        if let Some(size) = self.digest_size {
+            if !(1..=MAX_DIGEST_SIZE).contains(&size) {
+                return Err(format!(
+                    "A digest bundles between 1 and {} posts",
+                    MAX_DIGEST_SIZE
+                ));
+            }
+        }
+        if self.title_template.trim().is_empty() || self.body_template.trim().is_empty() {
+            return Err("The title and body templates cannot be empty".into());
+        }
+        Ok(())
+    }
+}
+
+/// Every feed source, in the order they were added.
+#[tracing::instrument(skip(pool))]
+pub async fn list_feed_sources(pool: &PgPool) -> Result<Vec<FeedSource>, sqlx::Error> {
+    sqlx::query_as!(
+        FeedSource,
+        r#"
+        SELECT
+            feed_source_id, name, url, auto_publish, digest_size, title_template,
+            body_template, post_template, etag, last_modified, last_polled_at, last_error,
+            last_digest_at,
+            (
+                SELECT COUNT(*) FROM feed_items i
+                WHERE i.feed_source_id = s.feed_source_id AND i.newsletter_issue_id IS NOT NULL
+            ) AS "mailed_posts!"
+        FROM feed_sources s
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_feed_source(
+    pool: &PgPool,
+    feed_source_id: Uuid,
+) -> Result<Option<FeedSource>, sqlx::Error> {
+    sqlx::query_as!(
+        FeedSource,
+        r#"
+        SELECT
+            feed_source_id, name, url, auto_publish, digest_size, title_template,
+            body_template, post_template, etag, last_modified, last_polled_at, last_error,
+            last_digest_at,
+            (
+                SELECT COUNT(*) FROM feed_items i
+                WHERE i.feed_source_id = s.feed_source_id AND i.newsletter_issue_id IS NOT NULL
+            ) AS "mailed_posts!"
+        FROM feed_sources s
+        WHERE feed_source_id = $1
+        "#,
+        feed_source_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Whether `e` is due to another source having the same URL.
+pub fn is_duplicate_feed_url(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.is_unique_violation())
+}
+
+/// Adds a feed source. Its first digest goes out a week from now.
+#[tracing::instrument(skip(pool))]
+pub async fn create_feed_source(
+    pool: &PgPool,
+    settings: &FeedSourceSettings,
+) -> Result<Uuid, sqlx::Error> {
+    let feed_source_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO feed_sources (
+            feed_source_id,
+            name,
+            url,
+            auto_publish,
+            digest_size,
+            title_template,
+            body_template,
+            post_template
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        feed_source_id,
+        settings.name,
+        settings.url,
+        settings.auto_publish,
+        settings.digest_size,
+        settings.title_template,
+        settings.body_template,
+        settings.post_template,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(feed_source_id)
+}
+
+/// Returns `false` if there is no such source.
+#[tracing::instrument(skip(pool))]
+pub async fn update_feed_source(
+    pool: &PgPool,
+    feed_source_id: Uuid,
+    settings: &FeedSourceSettings,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE feed_sources
+        SET name = $2,
+            url = $3,
+            auto_publish = $4,
+            digest_size = $5,
+            title_template = $6,
+            body_template = $7,
+            post_template = $8
+        WHERE feed_source_id = $1
+        "#,
+        feed_source_id,
+        settings.name,
+        settings.url,
+        settings.auto_publish,
+        settings.digest_size,
+        settings.title_template,
+        settings.body_template,
+        settings.post_template,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Stops following a feed and forgets its posts. The issues made from them
+/// are left alone. Returns `false` if there is no such source.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_feed_source(pool: &PgPool, feed_source_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM feed_sources WHERE feed_source_id = $1",
+        feed_source_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeedSourceSettings;
+    use claim::{assert_err, assert_ok};
+
+    fn settings() -> FeedSourceSettings {
+        FeedSourceSettings::new("Blog".into(), "https://example.com/feed.xml".into())
+    }
+
+    #[test]
+    fn new_sources_are_valid() {
+        assert_ok!(settings().validate());
+    }
+
+    #[test]
+    fn only_web_urls_are_accepted() {
+        for url in [
+            "example.com/feed.xml",
+            "file:///etc/passwd",
+            "ftp://example.com/",
+        ] {
+            let settings = FeedSourceSettings {
+                url: url.into(),
+                ..settings()
+            };
+            assert_err!(settings.validate());
+        }
+    }
+
+    #[test]
+    fn digests_bundle_at_least_one_post() {
+        for (size, valid) in [(0, false), (1, true), (50, true), (51, false)] {
+            let settings = FeedSourceSettings {
+                digest_size: Some(size),
+                ..settings()
+            };
+            assert_eq!(settings.validate().is_ok(), valid);
+        }
+    }
+}
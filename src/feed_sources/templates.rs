@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+
+use super::FeedSource;
+use crate::rendering::replace_tags;
+
+pub const DEFAULT_TITLE_TEMPLATE: &str = "{{ post.title }}";
+pub const DEFAULT_BODY_TEMPLATE: &str = "{{ posts }}";
+pub const DEFAULT_POST_TEMPLATE: &str =
+    "## [{{ post.title }}]({{ post.link }})\n\n{{ post.summary }}\n\n[Read the post]({{ post.link }})";
+
+/// A post of a feed, as the templates see it.
+#[derive(Debug, Clone)]
+pub struct FeedPost {
+    pub title: String,
+    pub link: String,
+    /// Plain text, on a single line.
+    pub summary: String,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// The templates an issue is written from.
+///
+/// The title and body templates see the latest post as `{{ post.title }}`,
+/// `{{ post.link }}`, `{{ post.summary }}` and `{{ post.date }}`, and how many
+/// posts the issue has as `{{ count }}`. The body template gets every post,
+/// each written with the post template, as `{{ posts }}`. Other tags, like
+/// `{{ name }}`, are left for the delivery to fill.
+#[derive(Debug, Clone, Copy)]
+pub struct FeedTemplates<'a> {
+    pub title: &'a str,
+    /// Markdown.
+    pub body: &'a str,
+    /// Markdown, for each post.
+    pub post: &'a str,
+}
+
+impl FeedSource {
+    pub fn templates(&self) -> FeedTemplates<'_> {
+        FeedTemplates {
+            title: &self.title_template,
+            body: &self.body_template,
+            post: &self.post_template,
+        }
+    }
+}
+
+/// What an issue made from posts says.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FeedIssueContent {
+    pub title: String,
+    pub markdown: String,
+}
+
+/// Writes an issue about `posts`, latest first.
+pub fn render_feed_issue(templates: FeedTemplates<'_>, posts: &[FeedPost]) -> FeedIssueContent {
+    let latest = posts.first();
+    let count = posts.len().to_string();
+
+    let title = replace_tags(templates.title, |key, fallback| match key {
+        "count" => Some(count.clone()),
+        _ => post_tag(latest?, key, fallback, false),
+    });
+    let rendered_posts = posts
+        .iter()
+        .map(|post| {
+            replace_tags(templates.post, |key, fallback| {
+                post_tag(post, key, fallback, true)
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let markdown = replace_tags(templates.body, |key, fallback| match key {
+        "count" => Some(count.clone()),
+        "posts" => Some(rendered_posts.clone()),
+        _ => post_tag(latest?, key, fallback, true),
+    });
+
+    FeedIssueContent {
+        title: title.split_whitespace().collect::<Vec<_>>().join(" "),
+        markdown,
+    }
+}
+
+/// The value of a `post.*` tag, escaped for Markdown if need be.
+fn post_tag(post: &FeedPost, key: &str, fallback: &str, markdown: bool) -> Option<String> {
+    let value = match key {
+        "post.title" => post.title.clone(),
+        "post.summary" => post.summary.clone(),
+        "post.link" if markdown => escape_url(&post.link),
+        "post.link" => post.link.clone(),
+        "post.date" => post
+            .published_at
+            .map(|at| at.format("%B %-d, %Y").to_string())
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    if value.trim().is_empty() {
+        return Some(fallback.to_owned());
+    }
+    Some(match key {
+        "post.title" | "post.summary" if markdown => escape_markdown(&value),
+        _ => value,
+    })
+}
+
+/// Keeps Markdown and HTML in what a feed says from being interpreted.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '&' | '#' | '|' | '~' | '!'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Keeps a link from ending the Markdown link it is the destination of.
+fn escape_url(url: &str) -> String {
+    url.trim()
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TEMPLATES: FeedTemplates = FeedTemplates {
+        title: DEFAULT_TITLE_TEMPLATE,
+        body: DEFAULT_BODY_TEMPLATE,
+        post: DEFAULT_POST_TEMPLATE,
+    };
+
+    fn post(title: &str) -> FeedPost {
+        FeedPost {
+            title: title.into(),
+            link: "https://blog.example.com/posts/1".into(),
+            summary: "What we did this week.".into(),
+            published_at: Some(Utc.with_ymd_and_hms(2026, 10, 5, 12, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn the_default_templates_link_to_the_post() {
+        let content = render_feed_issue(TEMPLATES, &[post("Hello")]);
+
+        assert_eq!(content.title, "Hello");
+        assert_eq!(
+            content.markdown,
+            "## [Hello](https://blog.example.com/posts/1)\n\nWhat we did this week.\n\n\
+             [Read the post](https://blog.example.com/posts/1)"
+        );
+    }
+
+    #[test]
+    fn digests_list_every_post() {
+        let templates = FeedTemplates {
+            title: "{{ count }} new posts, starting with {{ post.title }}",
+            body: "Hi {{ name | there }}, since {{ post.date }}:\n\n{{ posts }}",
+            post: "- {{ post.title }}",
+        };
+
+        let content = render_feed_issue(templates, &[post("Latest"), post("Older")]);
+
+        assert_eq!(content.title, "2 new posts, starting with Latest");
+        assert_eq!(
+            content.markdown,
+            "Hi {{ name | there }}, since October 5, 2026:\n\n- Latest\n\n- Older"
+        );
+    }
+
+    #[test]
+    fn what_the_feed_says_is_not_interpreted_as_markdown() {
+        let mut post = post("<b>Bold</b> *claims*");
+        post.link = "https://example.com/a (b)".into();
+
+        let content = render_feed_issue(TEMPLATES, &[post]);
+
+        assert_eq!(content.title, "<b>Bold</b> *claims*");
+        assert!(content
+            .markdown
+            .starts_with(r"## [\<b\>Bold\</b\> \*claims\*](https://example.com/a%20%28b%29)"));
+    }
+
+    #[test]
+    fn empty_values_use_the_fallback() {
+        let mut post = post("Hello");
+        post.published_at = None;
+        let templates = FeedTemplates {
+            title: "{{ post.date | This week }}",
+            ..TEMPLATES
+        };
+
+        assert_eq!(render_feed_issue(templates, &[post]).title, "This week");
+    }
+}
@@ -3,7 +3,10 @@ use std::time::Duration;
 use chrono::Utc;
 use sqlx::PgPool;
 
-use crate::{configuration::Settings, startup::get_connection_pool};
+use crate::{
+    configuration::Settings, rate_limit::delete_expired_hits,
+    signup_protection::delete_expired_form_tokens, startup::get_connection_pool,
+};
 
 // Retention period for idempotency keys (30 days)
 const RETENTION_DAYS: i64 = 30;
@@ -13,10 +16,10 @@ const CLEANUP_INTERVAL_HOURS: u64 = 24;
 
 pub async fn run_cleanup_worker(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    cleanup_loop(&connection_pool).await
+    cleanup_loop(&connection_pool, configuration.rate_limit.longest_window()).await
 }
 
-async fn cleanup_loop(pool: &PgPool) -> Result<(), anyhow::Error> {
+async fn cleanup_loop(pool: &PgPool, rate_limit_window: Duration) -> Result<(), anyhow::Error> {
     loop {
         match delete_stale_idempotency_keys(pool).await {
             Ok(deleted_count) => {
@@ -31,6 +34,35 @@ async fn cleanup_loop(pool: &PgPool) -> Result<(), anyhow::Error> {
             }
         }
 
+        // Counts only matter until their window is over
+        let cutoff = Utc::now()
+            - chrono::Duration::from_std(rate_limit_window).unwrap_or(chrono::Duration::days(1));
+        match delete_expired_hits(pool, cutoff).await {
+            Ok(deleted_count) => {
+                tracing::info!("Deleted {} expired rate limit counts", deleted_count);
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to delete expired rate limit counts"
+                );
+            }
+        }
+
+        match delete_expired_form_tokens(pool).await {
+            Ok(deleted_count) => {
+                tracing::info!("Deleted {} expired signup form tokens", deleted_count);
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to delete expired signup form tokens"
+                );
+            }
+        }
+
         // Sleep for the cleanup interval
         tokio::time::sleep(Duration::from_secs(CLEANUP_INTERVAL_HOURS * 3600)).await;
     }
@@ -7,8 +7,16 @@ use tracing::{field::display, Span};
 use uuid::Uuid;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::Settings,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    newsletter_issues::{
+        get_recipient, mark_issue_as_sent_if_delivered, personalize_issue, subject_for_variant,
+    },
+    rendering::{IssueBody, MergeFields},
     startup::get_connection_pool,
+    subscriber_preferences::add_subscription_links,
+    tracking::{inject_open_pixel, OpenToken, Tracker},
 };
 
 // Number of tasks to process concurrently
@@ -26,6 +34,16 @@ struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    track_opens: bool,
+    subject_variants: Vec<String>,
+    ab_test_winner: Option<i16>,
+}
+
+struct Task {
+    transaction: PgTransaction,
+    issue_id: Uuid,
+    email: String,
+    subject_variant: Option<i16>,
 }
 
 pub enum ExecutionOutcome {
@@ -36,12 +54,17 @@ pub enum ExecutionOutcome {
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(&connection_pool, &email_client).await
+    let tracker = configuration.application.tracker();
+    worker_loop(&connection_pool, &email_client, &tracker).await
 }
 
-async fn worker_loop(pool: &PgPool, email_client: &EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    tracker: &Tracker,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_tasks(&pool, &email_client).await {
+        match try_execute_tasks(pool, email_client, tracker).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -58,6 +81,7 @@ async fn worker_loop(pool: &PgPool, email_client: &EmailClient) -> Result<(), an
 pub async fn try_execute_tasks(
     pool: &PgPool,
     email_client: &EmailClient,
+    tracker: &Tracker,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     // Dequeue multiple tasks at once
     let tasks = dequeue_tasks(pool, CONCURRENT_TASKS).await?;
@@ -72,12 +96,13 @@ pub async fn try_execute_tasks(
     // Process tasks concurrently using JoinSet
     let mut join_set = JoinSet::new();
 
-    for (transaction, issue_id, email) in tasks {
+    for task in tasks {
         let pool_clone = pool.clone();
         let email_client_clone = email_client.clone();
+        let tracker_clone = tracker.clone();
 
         join_set.spawn(async move {
-            execute_single_task(pool_clone, email_client_clone, transaction, issue_id, email).await
+            execute_single_task(pool_clone, email_client_clone, tracker_clone, task).await
         });
     }
 
@@ -113,13 +138,18 @@ pub async fn try_execute_tasks(
 async fn execute_single_task(
     pool: PgPool,
     email_client: EmailClient,
-    transaction: PgTransaction,
-    issue_id: Uuid,
-    email: String,
+    tracker: Tracker,
+    task: Task,
 ) -> Result<(), anyhow::Error> {
+    let Task {
+        mut transaction,
+        issue_id,
+        email,
+        subject_variant,
+    } = task;
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
+        .record("newsletter_issue_id", display(issue_id))
+        .record("subscriber_email", display(&email));
 
     // Get current attempt count
     let attempt_count = get_attempt_count(&pool, issue_id, &email).await?;
@@ -144,15 +174,52 @@ async fn execute_single_task(
         }
     }
 
+    let issue = get_issue(&pool, issue_id).await?;
+    // Deliveries requeued after a subject line test get the winner.
+    let subject_variant = subject_variant.or(issue.ab_test_winner);
     let send_result = match SubscriberEmail::parse(email.clone()) {
         Ok(email_addr) => {
-            let issue = get_issue(&pool, issue_id).await?;
+            let subject = subject_for_variant(
+                &issue.title,
+                &issue.subject_variants,
+                subject_variant.unwrap_or(0),
+            );
+            let recipient = get_recipient(&pool, &email).await?;
+            let body = IssueBody {
+                text: issue.text_content,
+                html: issue.html_content,
+            };
+            let mut message = personalize_issue(
+                issue_id,
+                subject,
+                &body,
+                &MergeFields {
+                    name: &recipient.name,
+                    email: &email,
+                },
+            );
+            if let Some(subscriber_id) = recipient.subscriber_id {
+                message.html = tracker.track_links(&message.html, issue_id, subscriber_id)?;
+                add_subscription_links(
+                    &mut message,
+                    &tracker.preferences_url(subscriber_id),
+                    &tracker.unsubscribe_url(subscriber_id),
+                );
+                if issue.track_opens {
+                    let pixel_url = tracker.open_pixel_url(OpenToken {
+                        issue_id,
+                        subscriber_id,
+                    });
+                    message.html = inject_open_pixel(&message.html, &pixel_url);
+                }
+            }
             email_client
-                .send_email(
+                .send_email_with_headers(
                     &email_addr,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
+                    &message.subject,
+                    &message.html,
+                    &message.text,
+                    &message.headers,
                 )
                 .await
         }
@@ -165,7 +232,7 @@ async fn execute_single_task(
             );
             move_to_dead_letter_queue(&pool, issue_id, &email, attempt_count, &e.to_string())
                 .await?;
-            delete_task(transaction, issue_id, &email).await?;
+            complete_task(&pool, transaction, issue_id, &email).await?;
             return Ok(());
         }
     };
@@ -174,7 +241,8 @@ async fn execute_single_task(
         Ok(_) => {
             // Success - delete from queue
             tracing::info!("Successfully sent email to {}", email);
-            delete_task(transaction, issue_id, &email).await?;
+            log_delivery(&mut transaction, issue_id, &email, subject_variant).await?;
+            complete_task(&pool, transaction, issue_id, &email).await?;
         }
         Err(e) => {
             let error_message = e.to_string();
@@ -203,12 +271,19 @@ async fn execute_single_task(
                     &error_message,
                 )
                 .await?;
-                delete_task(transaction, issue_id, &email).await?;
+                complete_task(&pool, transaction, issue_id, &email).await?;
             } else {
-                // Update retry tracking and keep in queue
-                update_retry_tracking(&pool, issue_id, &email, new_attempt_count, &error_message)
-                    .await?;
-                transaction.rollback().await?;
+                // Update retry tracking and keep in queue. The row is locked by
+                // our own transaction, so the update has to go through it.
+                update_retry_tracking(
+                    &mut transaction,
+                    issue_id,
+                    &email,
+                    new_attempt_count,
+                    &error_message,
+                )
+                .await?;
+                transaction.commit().await?;
             }
         }
     }
@@ -217,21 +292,28 @@ async fn execute_single_task(
 }
 
 #[tracing::instrument(skip_all)]
-async fn dequeue_tasks(
-    pool: &PgPool,
-    limit: usize,
-) -> Result<Vec<(PgTransaction, Uuid, String)>, anyhow::Error> {
+async fn dequeue_tasks(pool: &PgPool, limit: usize) -> Result<Vec<Task>, anyhow::Error> {
     let mut tasks = Vec::new();
 
     // Dequeue tasks one by one to get separate transactions for each
     // This allows parallel processing without holding locks
     for _ in 0..limit {
         let mut transaction = pool.begin().await?;
+        // Paused and cancelled issues are left alone, and so are deliveries
+        // waiting for the winner of a subject line test.
         let r = sqlx::query!(
             r#"
-            SELECT newsletter_issue_id, subscriber_email
-            FROM issue_delivery_queue
-            FOR UPDATE
+            SELECT q.newsletter_issue_id, q.subscriber_email, q.subject_variant
+            FROM issue_delivery_queue q
+            JOIN newsletter_issues i USING (newsletter_issue_id)
+            WHERE i.status = 'sending'
+                AND q.cancelled_at IS NULL
+                AND (
+                    q.subject_variant IS NOT NULL
+                    OR i.ab_test_decide_at IS NULL
+                    OR i.ab_test_winner IS NOT NULL
+                )
+            FOR UPDATE OF q
             SKIP LOCKED
             LIMIT 1
             "#,
@@ -240,7 +322,12 @@ async fn dequeue_tasks(
         .await?;
 
         if let Some(r) = r {
-            tasks.push((transaction, r.newsletter_issue_id, r.subscriber_email));
+            tasks.push(Task {
+                transaction,
+                issue_id: r.newsletter_issue_id,
+                email: r.subscriber_email,
+                subject_variant: r.subject_variant,
+            });
         } else {
             // No more tasks available
             break;
@@ -250,6 +337,19 @@ async fn dequeue_tasks(
     Ok(tasks)
 }
 
+/// Removes a task from the queue and, if it was the last one for its issue,
+/// marks the issue as sent.
+async fn complete_task(
+    pool: &PgPool,
+    transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    delete_task(transaction, issue_id, email).await?;
+    mark_issue_as_sent_if_delivered(pool, issue_id).await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn delete_task(
     mut transaction: PgTransaction,
@@ -275,12 +375,42 @@ async fn delete_task(
     Ok(())
 }
 
+#[tracing::instrument(skip_all)]
+async fn log_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    subject_variant: Option<i16>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_log (
+            newsletter_issue_id,
+            subscriber_email,
+            delivered_at,
+            subject_variant
+        )
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        email,
+        Utc::now(),
+        subject_variant,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
     let issue: NewsletterIssue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT title, text_content, html_content, track_opens, subject_variants,
+            ab_test_winner
         FROM newsletter_issues
         WHERE
             newsletter_issue_id = $1
@@ -337,7 +467,7 @@ async fn get_last_attempted(
 
 #[tracing::instrument(skip_all)]
 async fn update_retry_tracking(
-    pool: &PgPool,
+    transaction: &mut PgTransaction,
     issue_id: Uuid,
     email: &str,
     attempt_count: i32,
@@ -357,7 +487,7 @@ async fn update_retry_tracking(
         Utc::now(),
         error_message,
     )
-    .execute(pool)
+    .execute(transaction.as_mut())
     .await?;
 
     Ok(())
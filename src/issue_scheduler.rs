@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::{
+    configuration::Settings,
+    newsletter_issues::{
+        begin_delivery, enqueue_delivery_wave, finish_ab_test, mark_issue_as_sent_if_delivered,
+        unschedule_issue, DraftContent,
+    },
+    startup::get_connection_pool,
+};
+
+// How often to look for issues that are due
+const POLL_INTERVAL_SECONDS: u64 = 15;
+
+pub enum SchedulerOutcome {
+    IssueDispatched,
+    WaveEnqueued,
+    WinnerPicked,
+    NothingDue,
+}
+
+pub async fn run_scheduler_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    scheduler_loop(&connection_pool, &configuration.application.base_url).await
+}
+
+async fn scheduler_loop(pool: &PgPool, base_url: &str) -> Result<(), anyhow::Error> {
+    loop {
+        match try_dispatch_due_work(pool, base_url).await {
+            Ok(
+                SchedulerOutcome::IssueDispatched
+                | SchedulerOutcome::WaveEnqueued
+                | SchedulerOutcome::WinnerPicked,
+            ) => {}
+            Ok(SchedulerOutcome::NothingDue) => {
+                tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS)).await;
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to dispatch a scheduled issue"
+                );
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Dispatches a due issue or, if there is none, enqueues a due wave or picks
+/// the winner of a subject line test that has run its course.
+pub async fn try_dispatch_due_work(
+    pool: &PgPool,
+    base_url: &str,
+) -> Result<SchedulerOutcome, anyhow::Error> {
+    match try_dispatch_due_issue(pool, base_url).await? {
+        SchedulerOutcome::NothingDue => match try_enqueue_due_wave(pool).await? {
+            SchedulerOutcome::NothingDue => try_pick_due_winner(pool).await,
+            outcome => Ok(outcome),
+        },
+        outcome => Ok(outcome),
+    }
+}
+
+/// Hands the earliest due issue over to the delivery queue, or plans its
+/// waves when it goes out in each subscriber's local time.
+///
+/// Rendering the issue, flagging it as sending and enqueuing its delivery
+/// tasks happen in a single transaction, so a scheduler that dies halfway
+/// leaves the issue scheduled and the next attempt starts from scratch.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty))]
+pub async fn try_dispatch_due_issue(
+    pool: &PgPool,
+    base_url: &str,
+) -> Result<SchedulerOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let Some((issue_id, content)) = dequeue_due_issue(&mut transaction).await? else {
+        return Ok(SchedulerOutcome::NothingDue);
+    };
+    Span::current().record("newsletter_issue_id", display(issue_id));
+
+    match content.render(base_url) {
+        Ok(body) => {
+            begin_delivery(&mut transaction, issue_id, &body, None).await?;
+            tracing::info!("Scheduled issue handed over to the delivery queue");
+        }
+        Err(e) => {
+            // Retrying will not fix the content, so give it back to its
+            // author rather than blocking the scheduler.
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Scheduled issue could not be rendered - moving it back to drafts"
+            );
+            unschedule_issue(transaction.as_mut(), issue_id).await?;
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(SchedulerOutcome::IssueDispatched)
+}
+
+/// Enqueues the earliest due wave of an issue that is being sent.
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id=tracing::field::Empty, timezone=tracing::field::Empty)
+)]
+pub async fn try_enqueue_due_wave(pool: &PgPool) -> Result<SchedulerOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let wave = sqlx::query!(
+        r#"
+        SELECT w.newsletter_issue_id, w.timezone
+        FROM issue_delivery_waves w
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE w.enqueued_at IS NULL AND w.send_at <= NOW() AND i.status = 'sending'
+        ORDER BY w.send_at
+        FOR UPDATE OF w
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await?;
+    let Some(wave) = wave else {
+        return Ok(SchedulerOutcome::NothingDue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(wave.newsletter_issue_id))
+        .record("timezone", display(&wave.timezone));
+
+    let recipients =
+        enqueue_delivery_wave(&mut transaction, wave.newsletter_issue_id, &wave.timezone).await?;
+    mark_issue_as_sent_if_delivered(transaction.as_mut(), wave.newsletter_issue_id).await?;
+    transaction.commit().await?;
+    tracing::info!("Enqueued a delivery wave for {} subscribers", recipients);
+
+    Ok(SchedulerOutcome::WaveEnqueued)
+}
+
+/// Picks the winning subject of the earliest test that is over and releases
+/// the deliveries that were waiting for it.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty))]
+pub async fn try_pick_due_winner(pool: &PgPool) -> Result<SchedulerOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let issue_id = sqlx::query_scalar!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE ab_test_decide_at <= NOW() AND ab_test_winner IS NULL
+        ORDER BY ab_test_decide_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await?;
+    let Some(issue_id) = issue_id else {
+        return Ok(SchedulerOutcome::NothingDue);
+    };
+    Span::current().record("newsletter_issue_id", display(issue_id));
+
+    let winner = finish_ab_test(&mut transaction, issue_id).await?;
+    transaction.commit().await?;
+    tracing::info!("Subject {} won the subject line test", winner);
+
+    Ok(SchedulerOutcome::WinnerPicked)
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_due_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<(Uuid, DraftContent)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, preheader, markdown_content, text_content, html_content
+        FROM newsletter_issues
+        WHERE status = 'scheduled' AND scheduled_at <= NOW()
+        ORDER BY scheduled_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            row.newsletter_issue_id,
+            DraftContent {
+                title: row.title,
+                preheader: row.preheader,
+                markdown: row.markdown_content,
+                text: row.text_content,
+                html: row.html_content,
+            },
+        )
+    }))
+}
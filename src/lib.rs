@@ -1,14 +1,28 @@
+pub mod allowed_origins;
+pub mod analytics;
 pub mod authentication;
 pub mod configuration;
 pub mod domain;
+pub mod duplicate_subscribers;
 pub mod email_client;
+pub mod email_policy;
 pub mod email_templates;
+pub mod feed_sources;
 pub mod idempotency;
 pub mod idempotency_cleanup;
 pub mod issue_delivery_queue;
+pub mod issue_scheduler;
+pub mod newsletter_issues;
+pub mod rate_limit;
+pub mod rendering;
 pub mod routes;
 pub mod session_state;
+pub mod signup_protection;
+pub mod site_theme;
 pub mod startup;
+pub mod subscriber_preferences;
+pub mod sunset;
 pub mod telemetry;
+pub mod tracking;
 pub mod utils;
 pub mod web_templates;
@@ -1,7 +1,10 @@
 use email_newsletter::configuration::get_configuration;
+use email_newsletter::feed_sources::run_feed_poller_until_stopped;
 use email_newsletter::idempotency_cleanup::run_cleanup_worker;
 use email_newsletter::issue_delivery_queue::run_worker_until_stopped;
+use email_newsletter::issue_scheduler::run_scheduler_until_stopped;
 use email_newsletter::startup::Application;
+use email_newsletter::sunset::run_sunset_worker_until_stopped;
 use email_newsletter::telemetry::{get_subscriber, init_subscriber};
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
@@ -18,12 +21,21 @@ async fn main() -> anyhow::Result<()> {
 
     let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
 
-    let cleanup_task = tokio::spawn(run_cleanup_worker(configuration));
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(configuration.clone()));
+
+    let cleanup_task = tokio::spawn(run_cleanup_worker(configuration.clone()));
+
+    let sunset_task = tokio::spawn(run_sunset_worker_until_stopped(configuration.clone()));
+
+    let feed_task = tokio::spawn(run_feed_poller_until_stopped(configuration));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = scheduler_task => report_exit("Issue scheduler", o),
         o = cleanup_task => report_exit("Idempotency cleanup worker", o),
+        o = sunset_task => report_exit("Sunset worker", o),
+        o = feed_task => report_exit("Feed poller", o),
     };
 
     Ok(())
@@ -0,0 +1,316 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// At most this many subjects, the title included, can be tested against
+/// each other.
+pub const MAX_SUBJECTS: usize = 4;
+
+/// How one subject line did in an A/B test. Automated opens and clicks are
+/// left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantStats {
+    /// 0 for the title, 1 and up for the variants.
+    pub variant: i16,
+    pub subject: String,
+    pub delivered: i64,
+    pub unique_opens: i64,
+    pub unique_clicks: i64,
+}
+
+impl VariantStats {
+    pub fn open_rate(&self) -> f64 {
+        rate(self.unique_opens, self.delivered)
+    }
+
+    pub fn click_rate(&self) -> f64 {
+        rate(self.unique_clicks, self.delivered)
+    }
+}
+
+fn rate(part: i64, whole: i64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+/// Where the subject line test of an issue stands.
+#[derive(Debug)]
+pub struct AbTestResults {
+    pub decide_at: DateTime<Utc>,
+    pub winner: Option<i16>,
+    /// Deliveries waiting for the winner.
+    pub waiting: i64,
+    pub variants: Vec<VariantStats>,
+}
+
+impl AbTestResults {
+    pub fn is_winner(&self, stats: &VariantStats) -> bool {
+        self.winner == Some(stats.variant)
+    }
+}
+
+/// The subject a delivery goes out with.
+pub fn subject_for_variant<'a>(title: &'a str, variants: &'a [String], variant: i16) -> &'a str {
+    usize::try_from(variant)
+        .ok()
+        .and_then(|v| v.checked_sub(1))
+        .and_then(|v| variants.get(v))
+        .map_or(title, String::as_str)
+}
+
+/// The best performing subject: the highest open rate when opens were
+/// tracked, then the highest click rate. Ties go to the earliest subject.
+pub fn pick_winner(variants: &[VariantStats], track_opens: bool) -> i16 {
+    let score = |s: &VariantStats| {
+        let opens = if track_opens { s.open_rate() } else { 0.0 };
+        (opens, s.click_rate())
+    };
+    variants
+        .iter()
+        .fold(None::<&VariantStats>, |best, s| match best {
+            Some(best) if score(best) >= score(s) => Some(best),
+            _ => Some(s),
+        })
+        .map_or(0, |s| s.variant)
+}
+
+/// Starts the subject line test of an issue that was just queued, if it has
+/// subject variants: a random slice of the queue is split between the
+/// subjects and everybody else waits for the winner.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn start_ab_test(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT cardinality(subject_variants) AS "variants!", ab_test_percent, ab_test_hours
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .fetch_one(transaction.as_mut())
+    .await?;
+    if issue.variants == 0 {
+        return Ok(());
+    }
+    let subjects = issue.variants + 1;
+
+    // Every subject gets at least one recipient.
+    sqlx::query!(
+        r#"
+        WITH shuffled AS (
+            SELECT
+                subscriber_email,
+                row_number() OVER (ORDER BY random()) - 1 AS position,
+                COUNT(*) OVER () AS audience
+            FROM issue_delivery_queue
+            WHERE newsletter_issue_id = $1
+        )
+        UPDATE issue_delivery_queue q
+        SET subject_variant = (s.position % $2)::smallint
+        FROM shuffled s
+        WHERE q.newsletter_issue_id = $1
+            AND q.subscriber_email = s.subscriber_email
+            AND s.position < GREATEST($2, CEIL(s.audience * $3::int / 100.0))
+        "#,
+        issue_id,
+        subjects as i64,
+        i32::from(issue.ab_test_percent),
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET ab_test_decide_at = NOW() + make_interval(hours => $2),
+            ab_test_winner = NULL
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+        i32::from(issue.ab_test_hours),
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Picks the winner of a test and releases the deliveries waiting for it.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn finish_ab_test(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<i16, sqlx::Error> {
+    let track_opens = sqlx::query_scalar!(
+        "SELECT track_opens FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_one(transaction.as_mut())
+    .await?;
+    let variants = list_variant_stats(transaction.as_mut(), issue_id).await?;
+    let winner = pick_winner(&variants, track_opens);
+
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET ab_test_winner = $2, updated_at = NOW()
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+        winner,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET subject_variant = $2
+        WHERE newsletter_issue_id = $1 AND subject_variant IS NULL
+        "#,
+        issue_id,
+        winner,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(winner)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_ab_test_results(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<AbTestResults>, sqlx::Error> {
+    let test = sqlx::query!(
+        r#"
+        SELECT
+            ab_test_decide_at AS "decide_at!",
+            ab_test_winner AS winner,
+            (SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+                    AND subject_variant IS NULL
+                    AND cancelled_at IS NULL) AS "waiting!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND ab_test_decide_at IS NOT NULL
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(test) = test else {
+        return Ok(None);
+    };
+
+    let mut connection = pool.acquire().await?;
+    Ok(Some(AbTestResults {
+        decide_at: test.decide_at,
+        winner: test.winner,
+        waiting: test.waiting,
+        variants: list_variant_stats(&mut connection, issue_id).await?,
+    }))
+}
+
+/// How each subject did with the deliveries it went out with. The winner's
+/// numbers include the deliveries made after the test.
+async fn list_variant_stats(
+    connection: &mut sqlx::PgConnection,
+    issue_id: Uuid,
+) -> Result<Vec<VariantStats>, sqlx::Error> {
+    let issue = sqlx::query!(
+        "SELECT title, subject_variants FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            l.subject_variant AS "variant!",
+            COUNT(*) AS "delivered!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM issue_opens o
+                WHERE o.newsletter_issue_id = $1
+                    AND o.subscriber_id = s.id
+                    AND o.machine_open IS NULL
+            )) AS "unique_opens!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM issue_clicks c
+                WHERE c.newsletter_issue_id = $1
+                    AND c.subscriber_id = s.id
+                    AND c.machine_click IS NULL
+            )) AS "unique_clicks!"
+        FROM issue_delivery_log l
+        LEFT JOIN subscriptions s ON s.email = l.subscriber_email
+        WHERE l.newsletter_issue_id = $1 AND l.subject_variant IS NOT NULL
+        GROUP BY l.subject_variant
+        "#,
+        issue_id,
+    )
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let subjects = 1 + issue.subject_variants.len() as i16;
+    Ok((0..subjects)
+        .map(|variant| {
+            let row = rows.iter().find(|r| r.variant == variant);
+            VariantStats {
+                variant,
+                subject: subject_for_variant(&issue.title, &issue.subject_variants, variant)
+                    .to_owned(),
+                delivered: row.map_or(0, |r| r.delivered),
+                unique_opens: row.map_or(0, |r| r.unique_opens),
+                unique_clicks: row.map_or(0, |r| r.unique_clicks),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pick_winner, subject_for_variant, VariantStats};
+
+    fn stats(variant: i16, unique_opens: i64, unique_clicks: i64) -> VariantStats {
+        VariantStats {
+            variant,
+            subject: format!("Subject {}", variant),
+            delivered: 100,
+            unique_opens,
+            unique_clicks,
+        }
+    }
+
+    #[test]
+    fn variant_zero_is_the_title() {
+        let variants = vec!["B".to_string(), "C".to_string()];
+        assert_eq!(subject_for_variant("A", &variants, 0), "A");
+        assert_eq!(subject_for_variant("A", &variants, 2), "C");
+        assert_eq!(subject_for_variant("A", &variants, 7), "A");
+    }
+
+    #[test]
+    fn the_most_opened_subject_wins_when_opens_are_tracked() {
+        let variants = [stats(0, 20, 9), stats(1, 35, 2), stats(2, 30, 5)];
+        assert_eq!(pick_winner(&variants, true), 1);
+    }
+
+    #[test]
+    fn clicks_decide_without_open_tracking_and_break_ties() {
+        let variants = [stats(0, 0, 3), stats(1, 0, 8)];
+        assert_eq!(pick_winner(&variants, false), 1);
+
+        let variants = [stats(0, 30, 3), stats(1, 30, 4), stats(2, 10, 9)];
+        assert_eq!(pick_winner(&variants, true), 1);
+    }
+
+    #[test]
+    fn ties_go_to_the_earliest_subject() {
+        let variants = [stats(0, 10, 1), stats(1, 10, 1)];
+        assert_eq!(pick_winner(&variants, true), 0);
+    }
+}
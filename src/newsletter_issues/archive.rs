@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::rendering::{fill_merge_tags, MergeFields, MergeTarget};
+
+/// How many issues a page of the public archive lists.
+pub const ARCHIVE_PAGE_SIZE: i64 = 20;
+
+/// How many of the latest issues the feeds carry.
+pub const FEED_LENGTH: i64 = 20;
+
+/// How long the description of an issue in its meta tags gets, in characters.
+const DESCRIPTION_LENGTH: usize = 160;
+
+/// An issue as listed in the public archive.
+#[derive(Debug)]
+pub struct ArchiveEntry {
+    pub slug: String,
+    pub title: String,
+    pub preheader: String,
+    pub published_at: DateTime<Utc>,
+}
+
+/// One page of the public archive.
+#[derive(Debug)]
+pub struct ArchivePage {
+    pub entries: Vec<ArchiveEntry>,
+    pub has_next_page: bool,
+}
+
+/// The public copy of an issue, with merge tags filled anonymously.
+#[derive(Debug)]
+pub struct ArchivedIssue {
+    pub issue_id: Uuid,
+    pub slug: String,
+    pub title: String,
+    pub html: String,
+    pub published_at: DateTime<Utc>,
+    /// For search engines and link previews: the preheader, or the start of
+    /// the issue if it has none.
+    pub description: String,
+}
+
+fn fill(template: &str, target: MergeTarget) -> String {
+    fill_merge_tags(template, &MergeFields::ANONYMOUS, target)
+}
+
+/// The start of `text` on a single line, cut at a word boundary.
+pub(crate) fn excerpt(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+
+    let cut = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(i, _)| i);
+    let cut = text[..cut].rfind(' ').unwrap_or(cut);
+    format!("{}…", text[..cut].trim_end())
+}
+
+/// Issues that went out and are not hidden from the archive, latest first.
+/// Pages start at 0.
+#[tracing::instrument(skip(pool))]
+pub async fn list_archived_issues(pool: &PgPool, page: i64) -> Result<ArchivePage, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT slug AS "slug!", title, preheader, published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE status IN ('sending', 'paused', 'sent')
+            AND NOT hide_from_archive
+            AND slug IS NOT NULL
+            AND published_at IS NOT NULL
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1 OFFSET $2
+        "#,
+        ARCHIVE_PAGE_SIZE + 1,
+        page * ARCHIVE_PAGE_SIZE,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let has_next_page = rows.len() as i64 > ARCHIVE_PAGE_SIZE;
+    let entries = rows
+        .into_iter()
+        .take(ARCHIVE_PAGE_SIZE as usize)
+        .map(|row| ArchiveEntry {
+            slug: row.slug,
+            title: fill(&row.title, MergeTarget::Text),
+            preheader: fill(&row.preheader, MergeTarget::Text),
+            published_at: row.published_at,
+        })
+        .collect();
+
+    Ok(ArchivePage {
+        entries,
+        has_next_page,
+    })
+}
+
+struct ArchivedIssueRow {
+    newsletter_issue_id: Uuid,
+    slug: String,
+    title: String,
+    preheader: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+impl From<ArchivedIssueRow> for ArchivedIssue {
+    fn from(row: ArchivedIssueRow) -> Self {
+        let description = if row.preheader.trim().is_empty() {
+            &row.text_content
+        } else {
+            &row.preheader
+        };
+        ArchivedIssue {
+            issue_id: row.newsletter_issue_id,
+            description: excerpt(&fill(description, MergeTarget::Text), DESCRIPTION_LENGTH),
+            slug: row.slug,
+            title: fill(&row.title, MergeTarget::Text),
+            html: fill(&row.html_content, MergeTarget::Html),
+            published_at: row.published_at,
+        }
+    }
+}
+
+/// The archived issue at `slug`, unless it is hidden or never went out.
+#[tracing::instrument(skip(pool))]
+pub async fn get_archived_issue(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<ArchivedIssue>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        ArchivedIssueRow,
+        r#"
+        SELECT newsletter_issue_id, slug AS "slug!", title, preheader, text_content,
+            html_content, published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE slug = $1
+            AND status IN ('sending', 'paused', 'sent')
+            AND NOT hide_from_archive
+            AND published_at IS NOT NULL
+        "#,
+        slug,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(ArchivedIssue::from))
+}
+
+/// The latest [`FEED_LENGTH`] archived issues, latest first.
+#[tracing::instrument(skip(pool))]
+pub async fn list_feed_issues(pool: &PgPool) -> Result<Vec<ArchivedIssue>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        ArchivedIssueRow,
+        r#"
+        SELECT newsletter_issue_id, slug AS "slug!", title, preheader, text_content,
+            html_content, published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE status IN ('sending', 'paused', 'sent')
+            AND NOT hide_from_archive
+            AND slug IS NOT NULL
+            AND published_at IS NOT NULL
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1
+        "#,
+        FEED_LENGTH,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(ArchivedIssue::from).collect())
+}
+
+/// Hides an issue from the public archive, or lists it again. Returns `false`
+/// if there is no such issue.
+#[tracing::instrument(skip(pool))]
+pub async fn set_hidden_from_archive(
+    pool: &PgPool,
+    issue_id: Uuid,
+    hidden: bool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET hide_from_archive = $2
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+        hidden,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::excerpt;
+
+    #[test]
+    fn short_texts_are_kept_whole_on_one_line() {
+        assert_eq!(excerpt("Hello\n\n  world", 20), "Hello world");
+    }
+
+    #[test]
+    fn long_texts_are_cut_between_words() {
+        assert_eq!(excerpt("The quick brown fox jumps", 12), "The quick…");
+        assert_eq!(excerpt("Ünïcödé wörds everywhere", 10), "Ünïcödé…");
+    }
+}
@@ -0,0 +1,93 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::mark_issue_as_sent_if_delivered;
+
+/// Stops the delivery worker from picking up the issue's queued emails.
+/// Emails already being sent still go out.
+///
+/// Returns `false` if the issue was not sending.
+#[tracing::instrument(skip(pool))]
+pub async fn pause_sending(pool: &PgPool, issue_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'paused', updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status = 'sending'
+        "#,
+        issue_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Hands a paused issue back to the delivery worker.
+///
+/// Returns `false` if the issue was not paused.
+#[tracing::instrument(skip(pool))]
+pub async fn resume_sending(pool: &PgPool, issue_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status = 'paused'
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    // The last emails may have gone out while the issue was being paused.
+    mark_issue_as_sent_if_delivered(transaction.as_mut(), issue_id).await?;
+    transaction.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Cancels a sending or paused issue: its queued emails are marked as
+/// cancelled and will not go out.
+///
+/// Returns the number of emails that were cancelled, or `None` if the issue
+/// was neither sending nor paused.
+#[tracing::instrument(skip(pool))]
+pub async fn cancel_sending(pool: &PgPool, issue_id: Uuid) -> Result<Option<u64>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'cancelled', updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status IN ('sending', 'paused')
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let cancelled = cancel_queued_deliveries(&mut transaction, issue_id).await?;
+    transaction.commit().await?;
+
+    Ok(Some(cancelled))
+}
+
+async fn cancel_queued_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET cancelled_at = NOW()
+        WHERE newsletter_issue_id = $1 AND cancelled_at IS NULL
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How many dead-lettered deliveries the admin page lists.
+const LISTED_DEAD_LETTERS: i64 = 1000;
+
+/// Dead-lettered deliveries of one issue.
+#[derive(Debug)]
+pub struct DeadLetterGroup {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub errors: Vec<DeadLetterErrorGroup>,
+}
+
+/// Dead-lettered deliveries of one issue that failed with the same error.
+#[derive(Debug)]
+pub struct DeadLetterErrorGroup {
+    pub error: String,
+    pub deliveries: Vec<DeadLetter>,
+}
+
+#[derive(Debug)]
+pub struct DeadLetter {
+    pub subscriber_email: String,
+    pub attempt_count: i32,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Which dead-lettered deliveries to requeue or discard. Unset fields match
+/// everything.
+#[derive(Debug, Default)]
+pub struct DeadLetterFilter {
+    pub issue_id: Option<Uuid>,
+    pub subscriber_emails: Option<Vec<String>>,
+    /// Matches deliveries whose last error contains this text.
+    pub error_contains: Option<String>,
+}
+
+/// Dead-lettered deliveries grouped by issue, most recently failed first,
+/// then by error.
+#[tracing::instrument(skip_all)]
+pub async fn list_dead_letters(pool: &PgPool) -> Result<Vec<DeadLetterGroup>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            d.newsletter_issue_id,
+            i.title,
+            d.subscriber_email,
+            d.attempt_count,
+            d.last_error,
+            d.failed_at
+        FROM dead_letter_queue d
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        ORDER BY
+            MAX(d.failed_at) OVER (PARTITION BY d.newsletter_issue_id) DESC,
+            d.newsletter_issue_id,
+            d.last_error,
+            d.subscriber_email
+        LIMIT $1
+        "#,
+        LISTED_DEAD_LETTERS,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut groups: Vec<DeadLetterGroup> = Vec::new();
+    for row in rows {
+        let delivery = DeadLetter {
+            subscriber_email: row.subscriber_email,
+            attempt_count: row.attempt_count,
+            failed_at: row.failed_at,
+        };
+        let group = match groups.last_mut() {
+            Some(group) if group.issue_id == row.newsletter_issue_id => group,
+            _ => {
+                groups.push(DeadLetterGroup {
+                    issue_id: row.newsletter_issue_id,
+                    title: row.title,
+                    errors: Vec::new(),
+                });
+                groups.last_mut().unwrap()
+            }
+        };
+        match group.errors.last_mut() {
+            Some(errors) if errors.error == row.last_error => errors.deliveries.push(delivery),
+            _ => group.errors.push(DeadLetterErrorGroup {
+                error: row.last_error,
+                deliveries: vec![delivery],
+            }),
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Puts dead-lettered deliveries back in the delivery queue with a clean retry
+/// count, and sends their issues again if they were done.
+///
+/// Deliveries of cancelled issues are left where they are. Returns the number
+/// of deliveries requeued.
+#[tracing::instrument(skip(pool))]
+pub async fn requeue_dead_letters(
+    pool: &PgPool,
+    filter: &DeadLetterFilter,
+) -> Result<u64, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let issue_ids = sqlx::query_scalar!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM dead_letter_queue d
+            USING newsletter_issues i
+            WHERE d.newsletter_issue_id = i.newsletter_issue_id
+                AND i.status IN ('sending', 'paused', 'sent')
+                AND ($1::uuid IS NULL OR d.newsletter_issue_id = $1)
+                AND ($2::text[] IS NULL OR d.subscriber_email = ANY($2))
+                AND ($3::text IS NULL OR strpos(d.last_error, $3) > 0)
+            RETURNING d.newsletter_issue_id, d.subscriber_email
+        )
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email FROM requeued
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET attempt_count = 0,
+            last_attempted_at = NULL,
+            error_message = NULL,
+            cancelled_at = NULL
+        RETURNING newsletter_issue_id
+        "#,
+        filter.issue_id,
+        filter.subscriber_emails.as_deref(),
+        filter.error_contains,
+    )
+    .fetch_all(transaction.as_mut())
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = NOW()
+        WHERE newsletter_issue_id = ANY($1) AND status = 'sent'
+        "#,
+        &issue_ids,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+
+    Ok(issue_ids.len() as u64)
+}
+
+/// Drops dead-lettered deliveries for good. Returns how many were dropped.
+#[tracing::instrument(skip(pool))]
+pub async fn discard_dead_letters(
+    pool: &PgPool,
+    filter: &DeadLetterFilter,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM dead_letter_queue
+        WHERE ($1::uuid IS NULL OR newsletter_issue_id = $1)
+            AND ($2::text[] IS NULL OR subscriber_email = ANY($2))
+            AND ($3::text IS NULL OR strpos(last_error, $3) > 0)
+        "#,
+        filter.issue_id,
+        filter.subscriber_emails.as_deref(),
+        filter.error_contains,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
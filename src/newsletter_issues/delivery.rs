@@ -0,0 +1,129 @@
+use sqlx::{PgExecutor, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{plan_delivery_waves, start_ab_test};
+use crate::domain::DeliveryMode;
+use crate::rendering::IssueBody;
+
+/// Freezes an issue that has not gone out yet with its rendered bodies and
+/// queues it for every confirmed subscriber, either all at once or in
+/// timezone waves.
+///
+/// Issues sent all at once start with their subject line test, if they have
+/// subject variants. Timezone waves go out with the title.
+///
+/// `mode` overrides the delivery mode the issue was scheduled with.
+///
+/// Returns `false` if the issue had already gone out, e.g. because it was sent
+/// from another tab or by the scheduler.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn begin_delivery(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    body: &IssueBody,
+    mode: Option<DeliveryMode>,
+) -> Result<bool, anyhow::Error> {
+    let Some(mode) = start_sending(transaction, issue_id, body, mode).await? else {
+        return Ok(false);
+    };
+    match mode {
+        DeliveryMode::AtOnce => {
+            enqueue_delivery_tasks(transaction, issue_id).await?;
+            start_ab_test(transaction, issue_id).await?;
+        }
+        DeliveryMode::LocalTime => plan_delivery_waves(transaction, issue_id).await?,
+    }
+    // Nobody to send to means there is nothing left to wait for.
+    mark_issue_as_sent_if_delivered(transaction.as_mut(), issue_id).await?;
+
+    Ok(true)
+}
+
+async fn start_sending(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    body: &IssueBody,
+    mode: Option<DeliveryMode>,
+) -> Result<Option<DeliveryMode>, anyhow::Error> {
+    let mode = sqlx::query_scalar!(
+        r#"
+        UPDATE newsletter_issues
+        SET text_content = $2,
+            html_content = $3,
+            delivery_mode = COALESCE($4, delivery_mode),
+            status = 'sending',
+            published_at = NOW(),
+            slug = COALESCE(slug, issue_slug(title, newsletter_issue_id)),
+            updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        RETURNING delivery_mode
+        "#,
+        issue_id,
+        body.text,
+        body.html,
+        mode.map(|m| m.as_str()),
+    )
+    .fetch_optional(transaction.as_mut())
+    .await?;
+
+    mode.map(|m| DeliveryMode::parse(&m).map_err(anyhow::Error::msg))
+        .transpose()
+}
+
+/// Queues the issue for every confirmed subscriber.
+///
+/// Subscribers that already have a task for this issue are skipped, so running
+/// this twice does not send anybody the issue twice.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed' AND duplicate_of IS NULL
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Moves an issue from sending to sent once nothing is left in the delivery
+/// queue for it and all its waves have been enqueued.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn mark_issue_as_sent_if_delivered(
+    executor: impl PgExecutor<'_>,
+    issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sent', updated_at = NOW()
+        WHERE newsletter_issue_id = $1
+            AND status = 'sending'
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_waves
+                WHERE newsletter_issue_id = $1 AND enqueued_at IS NULL
+            )
+        "#,
+        issue_id,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
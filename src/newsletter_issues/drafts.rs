@@ -0,0 +1,315 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::record_revision;
+use crate::domain::{DeliveryMode, IssueStatus};
+use crate::rendering::{render_issue_body, IssueBody};
+use crate::routes::error_chain_fmt;
+
+/// What the author wrote, before it is rendered into email bodies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DraftContent {
+    pub title: String,
+    pub preheader: String,
+    pub markdown: Option<String>,
+    pub text: String,
+    pub html: String,
+}
+
+impl DraftContent {
+    /// Renders the draft into the bodies that go out to subscribers.
+    pub fn render(&self, base_url: &str) -> Result<IssueBody, anyhow::Error> {
+        render_issue_body(
+            self.markdown.as_deref().unwrap_or_default(),
+            &self.text,
+            &self.html,
+            &self.preheader,
+            base_url,
+        )
+    }
+}
+
+/// Settings saved along with the draft that are not part of what the author
+/// wrote, so restoring a revision leaves them alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftOptions {
+    /// Adds a tracking pixel to every email to count opens.
+    pub track_opens: bool,
+    /// Subjects tested against the title before the issue goes out to
+    /// everybody. No variants, no test.
+    pub subject_variants: Vec<String>,
+    /// Share of the audience the subjects are tested on, in percent.
+    pub ab_test_percent: i16,
+    /// How long the test runs before the winner is picked.
+    pub ab_test_hours: i16,
+}
+
+impl Default for DraftOptions {
+    fn default() -> Self {
+        Self {
+            track_opens: false,
+            subject_variants: Vec::new(),
+            ab_test_percent: 20,
+            ab_test_hours: 4,
+        }
+    }
+}
+
+pub struct Issue {
+    pub issue_id: Uuid,
+    pub status: IssueStatus,
+    pub content: DraftContent,
+    pub options: DraftOptions,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    pub delivery_mode: DeliveryMode,
+    /// Wall-clock send time in local time mode.
+    pub local_send_at: Option<NaiveDateTime>,
+    /// Where the issue is in the public archive, once it went out.
+    pub slug: Option<String>,
+    pub hide_from_archive: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct IssueSummary {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub status: IssueStatus,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Delivery waves enqueued so far, out of the total, in local time mode.
+    pub waves_enqueued: i64,
+    pub waves_total: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(thiserror::Error)]
+pub enum SaveDraftError {
+    #[error("The issue does not exist")]
+    NotFound,
+
+    #[error("The issue is {0} and can no longer be edited")]
+    NotEditable(IssueStatus),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SaveDraftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_draft(pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            status
+        )
+        VALUES ($1, '', '', '', 'draft')
+        "#,
+        issue_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(issue_id)
+}
+
+/// Creates a draft that already has content, e.g. one written from a feed
+/// rather than by an author.
+#[tracing::instrument(skip_all)]
+pub async fn insert_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    content: &DraftContent,
+) -> Result<Uuid, sqlx::Error> {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            preheader,
+            markdown_content,
+            text_content,
+            html_content,
+            status
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, 'draft')
+        "#,
+        issue_id,
+        content.title,
+        content.preheader,
+        content.markdown,
+        content.text,
+        content.html,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(issue_id)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<Option<Issue>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT title, preheader, markdown_content, text_content, html_content, status,
+            scheduled_at, delivery_mode, local_send_at, track_opens, subject_variants,
+            ab_test_percent, ab_test_hours, slug, hide_from_archive, updated_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue")?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(Issue {
+        issue_id,
+        status: IssueStatus::parse(&row.status).map_err(anyhow::Error::msg)?,
+        content: DraftContent {
+            title: row.title,
+            preheader: row.preheader,
+            markdown: row.markdown_content,
+            text: row.text_content,
+            html: row.html_content,
+        },
+        options: DraftOptions {
+            track_opens: row.track_opens,
+            subject_variants: row.subject_variants,
+            ab_test_percent: row.ab_test_percent,
+            ab_test_hours: row.ab_test_hours,
+        },
+        scheduled_at: row.scheduled_at,
+        delivery_mode: DeliveryMode::parse(&row.delivery_mode).map_err(anyhow::Error::msg)?,
+        local_send_at: row.local_send_at,
+        slug: row.slug,
+        hide_from_archive: row.hide_from_archive,
+        updated_at: row.updated_at,
+    }))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_issues(pool: &PgPool) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.status, i.scheduled_at, i.updated_at,
+            COUNT(w.enqueued_at) AS "waves_enqueued!",
+            COUNT(w.timezone) AS "waves_total!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_delivery_waves w USING (newsletter_issue_id)
+        GROUP BY i.newsletter_issue_id
+        ORDER BY i.updated_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list newsletter issues")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(IssueSummary {
+                issue_id: row.newsletter_issue_id,
+                title: row.title,
+                status: IssueStatus::parse(&row.status).map_err(anyhow::Error::msg)?,
+                scheduled_at: row.scheduled_at,
+                waves_enqueued: row.waves_enqueued,
+                waves_total: row.waves_total,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect()
+}
+
+/// Stores the latest content of a draft and records it in the edit history.
+/// The options of the draft are only updated when given.
+///
+/// Returns when the draft was last updated.
+#[tracing::instrument(skip(pool, content, options))]
+pub async fn save_draft(
+    pool: &PgPool,
+    issue_id: Uuid,
+    user_id: Uuid,
+    content: &DraftContent,
+    options: Option<&DraftOptions>,
+    autosaved: bool,
+) -> Result<DateTime<Utc>, SaveDraftError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    // Lock the row so that a concurrent send cannot freeze the issue halfway
+    // through an edit.
+    let status = sqlx::query_scalar!(
+        r#"
+        SELECT status
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        FOR UPDATE
+        "#,
+        issue_id,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await
+    .context("Failed to retrieve the newsletter issue")?
+    .ok_or(SaveDraftError::NotFound)?;
+
+    let status = IssueStatus::parse(&status).map_err(anyhow::Error::msg)?;
+    if !status.is_editable() {
+        return Err(SaveDraftError::NotEditable(status));
+    }
+
+    let updated_at = sqlx::query_scalar!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2,
+            preheader = $3,
+            markdown_content = $4,
+            text_content = $5,
+            html_content = $6,
+            track_opens = COALESCE($7, track_opens),
+            subject_variants = COALESCE($8, subject_variants),
+            ab_test_percent = COALESCE($9, ab_test_percent),
+            ab_test_hours = COALESCE($10, ab_test_hours),
+            updated_at = NOW()
+        WHERE newsletter_issue_id = $1
+        RETURNING updated_at
+        "#,
+        issue_id,
+        content.title,
+        content.preheader,
+        content.markdown,
+        content.text,
+        content.html,
+        options.map(|o| o.track_opens),
+        options.map(|o| o.subject_variants.as_slice()),
+        options.map(|o| o.ab_test_percent),
+        options.map(|o| o.ab_test_hours),
+    )
+    .fetch_one(transaction.as_mut())
+    .await
+    .context("Failed to update the draft")?;
+
+    record_revision(&mut transaction, issue_id, user_id, content, autosaved).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the draft")?;
+
+    Ok(updated_at)
+}
@@ -0,0 +1,59 @@
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::email_client::EmailHeader;
+use crate::rendering::{fill_merge_tags, IssueBody, MergeFields, MergeTarget};
+
+/// One recipient's copy of an issue, ready for the email client.
+///
+/// Both the delivery worker and test sends go through
+/// [`personalize_issue`], so a test email is exactly what subscribers get.
+#[derive(Debug)]
+pub struct IssueEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    pub headers: Vec<EmailHeader>,
+}
+
+pub fn personalize_issue(
+    issue_id: Uuid,
+    title: &str,
+    body: &IssueBody,
+    recipient: &MergeFields,
+) -> IssueEmail {
+    IssueEmail {
+        subject: fill_merge_tags(title, recipient, MergeTarget::Text),
+        html: fill_merge_tags(&body.html, recipient, MergeTarget::Html),
+        text: fill_merge_tags(&body.text, recipient, MergeTarget::Text),
+        headers: vec![EmailHeader {
+            name: "X-Newsletter-Issue".into(),
+            value: issue_id.to_string(),
+        }],
+    }
+}
+
+/// Who an email is going to, as far as the subscriber list knows.
+#[derive(Debug, Default)]
+pub struct Recipient {
+    /// Unset for addresses that are not on the list, e.g. test recipients.
+    pub subscriber_id: Option<Uuid>,
+    /// What merge tags are filled with: the subscriber's name, or nothing.
+    pub name: String,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_recipient(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> Result<Recipient, sqlx::Error> {
+    let recipient = sqlx::query_as!(
+        Recipient,
+        r#"SELECT id AS "subscriber_id?", name FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(recipient.unwrap_or_default())
+}
@@ -0,0 +1,25 @@
+mod ab_tests;
+mod archive;
+mod controls;
+mod dead_letters;
+mod delivery;
+mod drafts;
+mod message;
+mod publish;
+mod revisions;
+mod schedule;
+mod stats;
+mod waves;
+
+pub use ab_tests::*;
+pub use archive::*;
+pub use controls::*;
+pub use dead_letters::*;
+pub use delivery::*;
+pub use drafts::*;
+pub use message::*;
+pub use publish::*;
+pub use revisions::*;
+pub use schedule::*;
+pub use stats::*;
+pub use waves::*;
@@ -0,0 +1,67 @@
+use anyhow::Context;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{enqueue_delivery_tasks, mark_issue_as_sent_if_delivered};
+use crate::rendering::IssueBody;
+
+/// Stores a new issue that goes out right away and queues it for every
+/// confirmed subscriber, skipping the drafts. Returns the id of the issue.
+#[tracing::instrument(skip_all)]
+pub async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+    preheader: &str,
+    markdown: Option<&str>,
+    body: &IssueBody,
+) -> Result<Uuid, anyhow::Error> {
+    let issue_id = insert_newsletter_issue(transaction, title, preheader, markdown, body)
+        .await
+        .context("Failed to store newsletter issue details")?;
+
+    enqueue_delivery_tasks(transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+    mark_issue_as_sent_if_delivered(transaction.as_mut(), issue_id)
+        .await
+        .context("Failed to update the issue status")?;
+
+    Ok(issue_id)
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+    preheader: &str,
+    markdown: Option<&str>,
+    body: &IssueBody,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            preheader,
+            text_content,
+            html_content,
+            markdown_content,
+            status,
+            published_at,
+            slug
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, 'sending', NOW(), issue_slug($2, $1))
+        "#,
+        newsletter_issue_id,
+        title,
+        preheader,
+        body.text,
+        body.html,
+        markdown,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
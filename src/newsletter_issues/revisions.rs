@@ -0,0 +1,203 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::DraftContent;
+
+// Autosaves are folded into the previous autosaved revision for this long,
+// so that typing does not produce a revision every few seconds.
+const AUTOSAVE_COALESCING_MINUTES: i64 = 10;
+
+pub struct Revision {
+    pub revision_id: Uuid,
+    pub content: DraftContent,
+    pub autosaved: bool,
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Adds `content` to the edit history of an issue.
+///
+/// Nothing is recorded if the content did not change since the last revision,
+/// and consecutive autosaves by the same user are coalesced.
+#[tracing::instrument(skip(transaction, content))]
+pub(crate) async fn record_revision(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    user_id: Uuid,
+    content: &DraftContent,
+    autosaved: bool,
+) -> Result<(), anyhow::Error> {
+    let latest = sqlx::query!(
+        r#"
+        SELECT revision_id, title, preheader, markdown_content, text_content, html_content,
+            autosaved, created_by, created_at
+        FROM newsletter_issue_revisions
+        WHERE newsletter_issue_id = $1
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        issue_id,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await
+    .context("Failed to retrieve the latest revision")?;
+
+    if let Some(latest) = latest {
+        let latest_content = DraftContent {
+            title: latest.title,
+            preheader: latest.preheader,
+            markdown: latest.markdown_content,
+            text: latest.text_content,
+            html: latest.html_content,
+        };
+
+        if latest_content == *content {
+            if !autosaved && latest.autosaved {
+                // An explicit save pins the autosaved revision.
+                sqlx::query!(
+                    r#"
+                    UPDATE newsletter_issue_revisions
+                    SET autosaved = FALSE
+                    WHERE revision_id = $1
+                    "#,
+                    latest.revision_id,
+                )
+                .execute(transaction.as_mut())
+                .await
+                .context("Failed to pin the latest revision")?;
+            }
+            return Ok(());
+        }
+
+        let is_recent =
+            Utc::now() - latest.created_at < chrono::Duration::minutes(AUTOSAVE_COALESCING_MINUTES);
+        if autosaved && latest.autosaved && latest.created_by == Some(user_id) && is_recent {
+            sqlx::query!(
+                r#"
+                UPDATE newsletter_issue_revisions
+                SET title = $2,
+                    preheader = $3,
+                    markdown_content = $4,
+                    text_content = $5,
+                    html_content = $6
+                WHERE revision_id = $1
+                "#,
+                latest.revision_id,
+                content.title,
+                content.preheader,
+                content.markdown,
+                content.text,
+                content.html,
+            )
+            .execute(transaction.as_mut())
+            .await
+            .context("Failed to update the latest revision")?;
+            return Ok(());
+        }
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_revisions (
+            revision_id,
+            newsletter_issue_id,
+            title,
+            preheader,
+            markdown_content,
+            text_content,
+            html_content,
+            autosaved,
+            created_by,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+        content.title,
+        content.preheader,
+        content.markdown,
+        content.text,
+        content.html,
+        autosaved,
+        user_id,
+    )
+    .execute(transaction.as_mut())
+    .await
+    .context("Failed to record a revision")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_revisions(pool: &PgPool, issue_id: Uuid) -> Result<Vec<Revision>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT r.revision_id, r.title, r.preheader, r.markdown_content, r.text_content,
+            r.html_content, r.autosaved, r.created_at, u.username AS "author?"
+        FROM newsletter_issue_revisions r
+        LEFT JOIN users u ON u.user_id = r.created_by
+        WHERE r.newsletter_issue_id = $1
+        ORDER BY r.created_at DESC
+        "#,
+        issue_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list revisions")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Revision {
+            revision_id: row.revision_id,
+            content: DraftContent {
+                title: row.title,
+                preheader: row.preheader,
+                markdown: row.markdown_content,
+                text: row.text_content,
+                html: row.html_content,
+            },
+            autosaved: row.autosaved,
+            author: row.author,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_revision(
+    pool: &PgPool,
+    issue_id: Uuid,
+    revision_id: Uuid,
+) -> Result<Option<Revision>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT r.title, r.preheader, r.markdown_content, r.text_content, r.html_content,
+            r.autosaved, r.created_at, u.username AS "author?"
+        FROM newsletter_issue_revisions r
+        LEFT JOIN users u ON u.user_id = r.created_by
+        WHERE r.newsletter_issue_id = $1 AND r.revision_id = $2
+        "#,
+        issue_id,
+        revision_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the revision")?;
+
+    Ok(row.map(|row| Revision {
+        revision_id,
+        content: DraftContent {
+            title: row.title,
+            preheader: row.preheader,
+            markdown: row.markdown_content,
+            text: row.text_content,
+            html: row.html_content,
+        },
+        autosaved: row.autosaved,
+        author: row.author,
+        created_at: row.created_at,
+    }))
+}
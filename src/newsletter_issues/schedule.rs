@@ -0,0 +1,123 @@
+use anyhow::Context;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::domain::{DeliveryMode, ScheduledTime};
+use crate::routes::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+pub enum ScheduleIssueError {
+    #[error("The issue has already been sent")]
+    AlreadySent,
+
+    #[error(
+        "It is already past that time in {}, so the issue would reach them late - pick a later time",
+        .0.join(", ")
+    )]
+    LateTimezones(Vec<String>),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ScheduleIssueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Schedules, or reschedules, an issue that has not gone out yet.
+///
+/// In local time mode the issue is due as soon as the first timezone among
+/// confirmed subscribers reaches the chosen wall-clock time; the timezone it
+/// was picked in stands in for subscribers whose timezone is unknown. It is
+/// refused if that time has already passed in any of their timezones.
+#[tracing::instrument(skip(pool))]
+pub async fn schedule_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+    scheduled_at: ScheduledTime,
+    mode: DeliveryMode,
+) -> Result<(), ScheduleIssueError> {
+    let (local_send_at, delivery_timezone) = match mode {
+        DeliveryMode::AtOnce => (None, None),
+        DeliveryMode::LocalTime => (
+            Some(scheduled_at.local()),
+            Some(scheduled_at.timezone().name()),
+        ),
+    };
+    if let (Some(local_send_at), Some(delivery_timezone)) = (local_send_at, delivery_timezone) {
+        let late_timezones = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT COALESCE(timezone, $2, 'UTC') AS "timezone!"
+            FROM subscriptions
+            WHERE status = 'confirmed'
+                AND duplicate_of IS NULL
+                AND $1::timestamp AT TIME ZONE COALESCE(timezone, $2, 'UTC') <= NOW()
+            ORDER BY 1
+            "#,
+            local_send_at,
+            delivery_timezone,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to look for timezones that are past the scheduled time")?;
+        if !late_timezones.is_empty() {
+            return Err(ScheduleIssueError::LateTimezones(late_timezones));
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'scheduled',
+            delivery_mode = $3,
+            local_send_at = $4::timestamp,
+            delivery_timezone = $5::text,
+            scheduled_at = CASE WHEN $3 = 'local_time' THEN LEAST($2, (
+                SELECT MIN($4::timestamp AT TIME ZONE COALESCE(timezone, $5::text, 'UTC'))
+                FROM subscriptions
+                WHERE status = 'confirmed' AND duplicate_of IS NULL
+            )) ELSE $2 END,
+            updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        "#,
+        issue_id,
+        scheduled_at.as_ref(),
+        mode.as_str(),
+        local_send_at,
+        delivery_timezone,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to schedule the issue")?;
+    if result.rows_affected() == 0 {
+        return Err(ScheduleIssueError::AlreadySent);
+    }
+
+    Ok(())
+}
+
+/// Turns a scheduled issue back into a draft.
+///
+/// Returns `false` if the issue was not scheduled.
+#[tracing::instrument(skip(executor))]
+pub async fn unschedule_issue(
+    executor: impl PgExecutor<'_>,
+    issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'draft',
+            scheduled_at = NULL,
+            updated_at = NOW()
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        issue_id,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{get_ab_test_results, AbTestResults};
+
+/// How many failing deliveries are listed on the issue page.
+const LISTED_FAILURES: i64 = 50;
+
+/// Where the deliveries of an issue stand.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeliveryCounts {
+    pub sent: i64,
+    /// Queued, including the ones being retried.
+    pub pending: i64,
+    /// Queued after at least one failed attempt.
+    pub retrying: i64,
+    /// Moved to the dead letter queue.
+    pub failed: i64,
+    pub cancelled: i64,
+}
+
+impl DeliveryCounts {
+    pub fn total(&self) -> i64 {
+        self.sent + self.pending + self.failed + self.cancelled
+    }
+}
+
+/// A queued delivery whose last attempt failed.
+#[derive(Debug)]
+pub struct RetryingDelivery {
+    pub subscriber_email: String,
+    pub attempt_count: i32,
+    pub error_message: Option<String>,
+    pub last_attempted_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery that gave up and was moved to the dead letter queue.
+#[derive(Debug)]
+pub struct FailedDelivery {
+    pub subscriber_email: String,
+    pub attempt_count: i32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Emails delivered during one minute.
+#[derive(Debug)]
+pub struct SendRate {
+    pub minute: DateTime<Utc>,
+    pub sent: i64,
+}
+
+/// Everything the issue page shows about an issue that has gone out.
+#[derive(Debug)]
+pub struct DeliveryStats {
+    pub counts: DeliveryCounts,
+    pub retrying: Vec<RetryingDelivery>,
+    pub failed: Vec<FailedDelivery>,
+    pub send_rate: Vec<SendRate>,
+    /// Set when the issue tested subject lines.
+    pub ab_test: Option<AbTestResults>,
+}
+
+impl DeliveryStats {
+    /// The busiest minute so far, to scale the send rate chart.
+    pub fn peak_rate(&self) -> i64 {
+        self.send_rate.iter().map(|r| r.sent).max().unwrap_or(0)
+    }
+
+    /// The width of a minute's bar in the send rate chart, in percent.
+    pub fn bar_width(&self, rate: &SendRate) -> i64 {
+        (rate.sent * 100 / self.peak_rate().max(1)).max(1)
+    }
+}
+
+pub async fn get_delivery_stats(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<DeliveryStats, sqlx::Error> {
+    Ok(DeliveryStats {
+        counts: get_delivery_counts(pool, issue_id).await?,
+        retrying: list_retrying_deliveries(pool, issue_id).await?,
+        failed: list_failed_deliveries(pool, issue_id).await?,
+        send_rate: get_send_rate(pool, issue_id).await?,
+        ab_test: get_ab_test_results(pool, issue_id).await?,
+    })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_delivery_counts(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<DeliveryCounts, sqlx::Error> {
+    let counts = sqlx::query_as!(
+        DeliveryCounts,
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM issue_delivery_log
+                WHERE newsletter_issue_id = $1) AS "sent!",
+            (SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1 AND cancelled_at IS NULL) AS "pending!",
+            (SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+                    AND cancelled_at IS NULL
+                    AND attempt_count > 0) AS "retrying!",
+            (SELECT COUNT(*) FROM dead_letter_queue
+                WHERE newsletter_issue_id = $1) AS "failed!",
+            (SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1 AND cancelled_at IS NOT NULL) AS "cancelled!"
+        "#,
+        issue_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(counts)
+}
+
+/// The deliveries being retried, most attempted first.
+#[tracing::instrument(skip(pool))]
+async fn list_retrying_deliveries(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<RetryingDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        RetryingDelivery,
+        r#"
+        SELECT subscriber_email, attempt_count, error_message, last_attempted_at
+        FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND cancelled_at IS NULL AND attempt_count > 0
+        ORDER BY attempt_count DESC, last_attempted_at DESC
+        LIMIT $2
+        "#,
+        issue_id,
+        LISTED_FAILURES,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The deliveries that were dead-lettered, most recent first.
+#[tracing::instrument(skip(pool))]
+async fn list_failed_deliveries(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<FailedDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        FailedDelivery,
+        r#"
+        SELECT subscriber_email, attempt_count, last_error, failed_at
+        FROM dead_letter_queue
+        WHERE newsletter_issue_id = $1
+        ORDER BY failed_at DESC
+        LIMIT $2
+        "#,
+        issue_id,
+        LISTED_FAILURES,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Emails delivered per minute, in order, for the minutes anything went out.
+#[tracing::instrument(skip(pool))]
+async fn get_send_rate(pool: &PgPool, issue_id: Uuid) -> Result<Vec<SendRate>, sqlx::Error> {
+    sqlx::query_as!(
+        SendRate,
+        r#"
+        SELECT date_trunc('minute', delivered_at) AS "minute!", COUNT(*) AS "sent!"
+        FROM issue_delivery_log
+        WHERE newsletter_issue_id = $1
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        issue_id,
+    )
+    .fetch_all(pool)
+    .await
+}
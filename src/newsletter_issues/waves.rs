@@ -0,0 +1,179 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct DeliveryWave {
+    pub timezone: String,
+    pub send_at: DateTime<Utc>,
+    /// Set once the wave has been enqueued.
+    pub recipients: Option<i32>,
+    /// Recipients of the wave whose email has not gone out yet.
+    pub pending: i64,
+}
+
+impl DeliveryWave {
+    /// Recipients of the wave that have been handled by the delivery worker.
+    pub fn sent(&self) -> i64 {
+        (i64::from(self.recipients.unwrap_or_default()) - self.pending).max(0)
+    }
+}
+
+/// Plans one wave per timezone among confirmed subscribers, each going out at
+/// the issue's local send time in that timezone, and records which wave each
+/// subscriber belongs to.
+///
+/// Waves whose time has already passed are picked up by the scheduler right
+/// away.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn plan_delivery_waves(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_waves (newsletter_issue_id, timezone, send_at)
+        SELECT DISTINCT
+            i.newsletter_issue_id,
+            COALESCE(s.timezone, i.delivery_timezone, 'UTC'),
+            i.local_send_at AT TIME ZONE COALESCE(s.timezone, i.delivery_timezone, 'UTC')
+        FROM newsletter_issues i
+        JOIN subscriptions s ON s.status = 'confirmed' AND s.duplicate_of IS NULL
+        WHERE i.newsletter_issue_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_wave_recipients (newsletter_issue_id, subscriber_email, timezone)
+        SELECT i.newsletter_issue_id, s.email, COALESCE(s.timezone, i.delivery_timezone, 'UTC')
+        FROM newsletter_issues i
+        JOIN subscriptions s ON s.status = 'confirmed' AND s.duplicate_of IS NULL
+        WHERE i.newsletter_issue_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Queues the issue for the subscribers of a wave who are still confirmed and
+/// returns how many were added.
+///
+/// Subscribers keep the wave they were planned in even if they move to another
+/// timezone. Those who confirmed after the waves were planned join the wave of
+/// their timezone if it is still to come, and the next wave that goes out
+/// otherwise.
+#[tracing::instrument(skip(transaction))]
+pub async fn enqueue_delivery_wave(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    timezone: &str,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_wave_recipients (newsletter_issue_id, subscriber_email, timezone)
+        SELECT i.newsletter_issue_id, s.email, $2
+        FROM subscriptions s, newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+            AND s.status = 'confirmed'
+            AND s.duplicate_of IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_waves w
+                WHERE w.newsletter_issue_id = i.newsletter_issue_id
+                    AND w.timezone = COALESCE(s.timezone, i.delivery_timezone, 'UTC')
+                    AND w.timezone <> $2
+                    AND w.enqueued_at IS NULL
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+                    AND q.subscriber_email = s.email
+            )
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        timezone,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    // Deliveries of merged duplicates are moved to the address that was kept,
+    // which may have been planned in a later wave.
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT r.newsletter_issue_id, r.subscriber_email
+        FROM issue_wave_recipients r
+        JOIN subscriptions s ON s.email = r.subscriber_email
+        WHERE r.newsletter_issue_id = $1
+            AND r.timezone = $2
+            AND s.status = 'confirmed'
+            AND s.duplicate_of IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_log l
+                WHERE l.newsletter_issue_id = r.newsletter_issue_id
+                    AND l.subscriber_email = r.subscriber_email
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM dead_letter_queue d
+                WHERE d.newsletter_issue_id = r.newsletter_issue_id
+                    AND d.subscriber_email = r.subscriber_email
+            )
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        timezone,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_waves
+        SET enqueued_at = NOW(), recipients = $3
+        WHERE newsletter_issue_id = $1 AND timezone = $2
+        "#,
+        issue_id,
+        timezone,
+        result.rows_affected() as i32,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_delivery_waves(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<DeliveryWave>, anyhow::Error> {
+    let waves = sqlx::query_as!(
+        DeliveryWave,
+        r#"
+        SELECT w.timezone, w.send_at, w.recipients,
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                JOIN issue_wave_recipients r USING (newsletter_issue_id, subscriber_email)
+                WHERE q.newsletter_issue_id = w.newsletter_issue_id
+                    AND r.timezone = w.timezone
+            ) AS "pending!"
+        FROM issue_delivery_waves w
+        WHERE w.newsletter_issue_id = $1
+        ORDER BY w.send_at, w.timezone
+        "#,
+        issue_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list delivery waves")?;
+
+    Ok(waves)
+}
@@ -0,0 +1,265 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::configuration::{RateLimit, RateLimitSettings};
+
+/// What a request counts towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedAction {
+    SubscribeFromIp,
+    SubscribeEmail,
+    ConfirmFromIp,
+    LoginFromIp,
+    LoginAsUser,
+}
+
+impl LimitedAction {
+    fn bucket(self) -> &'static str {
+        match self {
+            LimitedAction::SubscribeFromIp => "subscribe_ip",
+            LimitedAction::SubscribeEmail => "subscribe_email",
+            LimitedAction::ConfirmFromIp => "confirm_ip",
+            LimitedAction::LoginFromIp => "login_ip",
+            LimitedAction::LoginAsUser => "login_username",
+        }
+    }
+}
+
+/// Refused for going over a rate limit. Clients can try again after
+/// `retry_after_seconds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub retry_after_seconds: u64,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Too many requests, please try again in {} seconds",
+            self.retry_after_seconds
+        )
+    }
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "error": "rate_limited",
+            "message": self.to_string(),
+        });
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, self.retry_after_seconds.to_string())],
+            Json(body),
+        )
+            .into_response()
+    }
+}
+
+/// Counts requests per client, email or username in fixed windows, in
+/// Postgres so that every instance of the application shares the counts.
+#[derive(Clone)]
+pub struct RateLimiter {
+    pool: PgPool,
+    settings: Arc<RateLimitSettings>,
+}
+
+impl RateLimiter {
+    pub fn new(pool: PgPool, settings: RateLimitSettings) -> Self {
+        Self {
+            pool,
+            settings: Arc::new(settings),
+        }
+    }
+
+    fn limit(&self, action: LimitedAction) -> RateLimit {
+        match action {
+            LimitedAction::SubscribeFromIp => self.settings.subscribe_per_ip,
+            LimitedAction::SubscribeEmail => self.settings.subscribe_per_email,
+            LimitedAction::ConfirmFromIp => self.settings.confirm_per_ip,
+            LimitedAction::LoginFromIp => self.settings.login_per_ip,
+            LimitedAction::LoginAsUser => self.settings.login_per_username,
+        }
+    }
+
+    /// The address of the client that sent a request `peer` delivered.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let forwarded_for = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        client_ip(peer, &forwarded_for, &self.settings.trusted_proxies)
+    }
+
+    /// Counts a request towards `action` for `key`, unless that goes over
+    /// the limit. Requests go through if they cannot be counted, as locking
+    /// everyone out would be worse.
+    pub async fn check(&self, action: LimitedAction, key: &str) -> Result<(), RateLimited> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+
+        let limit = self.limit(action);
+        let now = Utc::now();
+        let window = i64::from(limit.window_seconds.get());
+        let window_start = now.timestamp() - now.timestamp().rem_euclid(window);
+        let hits = record_hit(
+            &self.pool,
+            action.bucket(),
+            &hash_key(key),
+            DateTime::from_timestamp(window_start, 0).unwrap_or(now),
+        )
+        .await;
+
+        match hits {
+            Ok(hits) if hits > i64::from(limit.requests) => Err(RateLimited {
+                retry_after_seconds: (window_start + window - now.timestamp()).max(1) as u64,
+            }),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::error!(error.message = %e, "Failed to count a rate-limited request");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The client behind `peer`. Requests forwarded by a trusted proxy come from
+/// the last address in `forwarded_for` that is not another trusted proxy;
+/// anyone else could write whatever they want there.
+fn client_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    if !trusted_proxies.contains(&peer) {
+        return client;
+    }
+    for hop in forwarded_for.rsplit(',').map(str::trim) {
+        let Ok(ip) = hop.parse::<IpAddr>() else {
+            break;
+        };
+        client = ip;
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Counts a request in its window and returns how many the window has.
+#[tracing::instrument(skip(pool, key_hash))]
+async fn record_hit(
+    pool: &PgPool,
+    bucket: &str,
+    key_hash: &str,
+    window_start: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    let hits = sqlx::query_scalar!(
+        r#"
+        INSERT INTO rate_limit_hits (bucket, key_hash, window_start, hits)
+        VALUES ($1, $2, $3, 1)
+        ON CONFLICT (bucket, key_hash, window_start)
+        DO UPDATE SET hits = rate_limit_hits.hits + 1
+        RETURNING hits
+        "#,
+        bucket,
+        key_hash,
+        window_start,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(hits.into())
+}
+
+/// Forgets the windows that started before `cutoff`.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_expired_hits(pool: &PgPool, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM rate_limit_hits WHERE window_start < $1",
+        cutoff,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Answers `429 Too Many Requests` to clients that call the route more often
+/// than `action` allows.
+pub async fn limit_by_ip(
+    State((limiter, action)): State<(RateLimiter, LimitedAction)>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = limiter.client_ip(peer.ip(), request.headers());
+    if let Err(limited) = limiter.check(action, &ip.to_string()).await {
+        tracing::warn!(client_ip = %ip, ?action, "Rate limited a client");
+        return limited.into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_ip;
+    use std::net::IpAddr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        assert_eq!(
+            client_ip(ip("203.0.113.7"), "198.51.100.1", &[ip("10.0.0.1")]),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped() {
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+        assert_eq!(
+            client_ip(
+                ip("10.0.0.1"),
+                "198.51.100.9, 198.51.100.1, 10.0.0.2",
+                &proxies
+            ),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn garbage_stops_at_the_last_trusted_hop() {
+        let proxies = [ip("10.0.0.1")];
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), "not-an-ip", &proxies),
+            ip("10.0.0.1")
+        );
+        assert_eq!(client_ip(ip("10.0.0.1"), "", &proxies), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn ipv6_clients_are_supported() {
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), "2001:db8::1", &[ip("10.0.0.1")]),
+            ip("2001:db8::1")
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use std::borrow::Cow;
+
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{element, rewrite_str, text, ElementContentHandlers, RewriteStrSettings, Selector};
+use reqwest::Url;
+
+// Elements that mail clients either strip or refuse to render.
+const UNSUPPORTED_ELEMENTS: &[&str] = &[
+    "script", "noscript", "iframe", "frame", "frameset", "object", "embed", "applet", "form",
+    "input", "button", "select", "textarea", "link", "base", "video", "audio", "canvas",
+];
+
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "background", "poster"];
+
+// Holds the author's inline style while stylesheet rules are applied, so that
+// it can be re-appended last and keep taking precedence.
+const ORIGINAL_STYLE_ATTRIBUTE: &str = "data-original-style";
+
+/// Makes an HTML body safe to send by email.
+///
+/// Rules from `<style>` blocks are inlined into `style` attributes (rules that
+/// cannot be inlined, like media queries, are kept in a single `<style>` block),
+/// unsupported elements and event handlers are stripped, relative URLs are
+/// resolved against `base_url` and, if given, a hidden preheader is added at
+/// the top of the body.
+pub fn prepare_email_html(
+    html: &str,
+    base_url: &str,
+    preheader: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    let base_url = Url::parse(base_url)?;
+    let stylesheet = parse_stylesheet(&collect_stylesheets(html)?);
+    let preheader = preheader
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(preheader_html);
+
+    let mut has_head = false;
+    let mut has_body = false;
+
+    let mut handlers = Vec::new();
+    for name in UNSUPPORTED_ELEMENTS {
+        handlers.push(element!(*name, |el| {
+            el.remove();
+            Ok(())
+        }));
+    }
+    handlers.push(element!("*", |el| {
+        sanitize_attributes(el, &base_url)?;
+        if let Some(style) = el.get_attribute("style") {
+            el.remove_attribute("style");
+            el.set_attribute(ORIGINAL_STYLE_ATTRIBUTE, &style)?;
+        }
+        Ok(())
+    }));
+    for rule in &stylesheet.rules {
+        handlers.push((
+            Cow::Borrowed(&rule.selector),
+            ElementContentHandlers::default().element(|el: &mut Element| {
+                let mut style = el.get_attribute("style").unwrap_or_default();
+                append_declarations(&mut style, &rule.declarations);
+                el.set_attribute("style", &style)?;
+                Ok(())
+            }),
+        ));
+    }
+    handlers.push(element!("*", |el| {
+        if let Some(original) = el.get_attribute(ORIGINAL_STYLE_ATTRIBUTE) {
+            el.remove_attribute(ORIGINAL_STYLE_ATTRIBUTE);
+            let mut style = el.get_attribute("style").unwrap_or_default();
+            append_declarations(&mut style, &original);
+            el.set_attribute("style", &style)?;
+        }
+        Ok(())
+    }));
+    handlers.push(element!("style", |el| {
+        el.remove();
+        Ok(())
+    }));
+    handlers.push(element!("head", |el| {
+        has_head = true;
+        if !stylesheet.residual.is_empty() {
+            el.append(
+                &residual_style_html(&stylesheet.residual),
+                ContentType::Html,
+            );
+        }
+        Ok(())
+    }));
+    handlers.push(element!("body", |el| {
+        has_body = true;
+        if let Some(preheader) = &preheader {
+            el.prepend(preheader, ContentType::Html);
+        }
+        Ok(())
+    }));
+
+    let mut output = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: handlers,
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    // Fragments have neither a <head> nor a <body> to attach to.
+    if !has_body {
+        if let Some(preheader) = &preheader {
+            output.insert_str(0, preheader);
+        }
+    }
+    if !has_head && !stylesheet.residual.is_empty() {
+        output.insert_str(0, &residual_style_html(&stylesheet.residual));
+    }
+
+    Ok(output)
+}
+
+fn collect_stylesheets(html: &str) -> Result<String, anyhow::Error> {
+    let mut css = String::new();
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![text!("style", |chunk| {
+                css.push_str(chunk.as_str());
+                if chunk.last_in_text_node() {
+                    css.push('\n');
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok(css)
+}
+
+fn sanitize_attributes(el: &mut Element, base_url: &Url) -> Result<(), anyhow::Error> {
+    let event_handlers: Vec<String> = el
+        .attributes()
+        .iter()
+        .map(|a| a.name())
+        .filter(|name| name.starts_with("on"))
+        .collect();
+    for name in event_handlers {
+        el.remove_attribute(&name);
+    }
+
+    for name in URL_ATTRIBUTES {
+        if let Some(value) = el.get_attribute(name) {
+            match resolve_url(base_url, &value) {
+                Some(url) => el.set_attribute(name, &url)?,
+                None => el.remove_attribute(name),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `None` for URLs that must not survive in an email.
+fn resolve_url(base_url: &Url, value: &str) -> Option<String> {
+    let value = value.trim();
+    let scheme = value.split(':').next().unwrap_or_default().to_lowercase();
+    if scheme == "javascript" || scheme == "vbscript" {
+        return None;
+    }
+
+    if value.is_empty() || value.starts_with('#') || Url::parse(value).is_ok() {
+        return Some(value.to_string());
+    }
+
+    Some(
+        base_url
+            .join(value)
+            .map(String::from)
+            .unwrap_or_else(|_| value.to_string()),
+    )
+}
+
+fn append_declarations(style: &mut String, declarations: &str) {
+    let declarations = declarations.trim().trim_end_matches(';').trim();
+    if declarations.is_empty() {
+        return;
+    }
+
+    let existing = style.trim_end();
+    if !existing.is_empty() && !existing.ends_with(';') {
+        style.push(';');
+    }
+    if !style.is_empty() {
+        style.push(' ');
+    }
+    style.push_str(declarations);
+    style.push(';');
+}
+
+const PREHEADER_STYLE: &str = "display: none; max-height: 0; overflow: hidden; mso-hide: all;";
+
+fn preheader_html(preheader: &str) -> String {
+    format!(
+        r#"<div style="{}">{}</div>"#,
+        PREHEADER_STYLE,
+        htmlescape::encode_minimal(preheader)
+    )
+}
+
+/// The content of an email prepared by [`prepare_email_html`] as an HTML
+/// fragment for other readers, e.g. feed readers: without its document
+/// wrapper, head and hidden preheader.
+pub fn email_body_fragment(html: &str) -> Result<String, anyhow::Error> {
+    let preheader = format!(r#"div[style="{}"]"#, PREHEADER_STYLE);
+    let output = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!("head", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+                element!("html, body", |el| {
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                element!(preheader, |el| {
+                    el.remove();
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    Ok(output.trim().to_owned())
+}
+
+fn residual_style_html(css: &str) -> String {
+    format!("<style>\n{}</style>", css)
+}
+
+struct Stylesheet {
+    rules: Vec<CssRule>,
+    residual: String,
+}
+
+struct CssRule {
+    selector: Selector,
+    declarations: String,
+}
+
+/// Splits CSS into rules that can be inlined, ordered by specificity, and a
+/// residual stylesheet made of at-rules and selectors mail clients would need
+/// to evaluate themselves (e.g. `a:hover`).
+fn parse_stylesheet(css: &str) -> Stylesheet {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut residual = String::new();
+    let mut rest = css.as_str();
+
+    loop {
+        rest = rest.trim_start();
+        let Some(open) = rest.find('{') else {
+            break;
+        };
+        let prelude = rest[..open].trim();
+
+        if prelude.starts_with('@') {
+            // Statement at-rules such as `@import url(...);` are dropped.
+            if let Some(semicolon) = prelude.find(';') {
+                rest = &rest[semicolon + 1..];
+                continue;
+            }
+            let end = matching_brace(rest, open);
+            residual.push_str(rest[..end].trim());
+            residual.push('\n');
+            rest = &rest[end..];
+            continue;
+        }
+
+        let Some(close) = rest[open..].find('}').map(|i| open + i) else {
+            break;
+        };
+        let declarations = rest[open + 1..close].trim();
+        for selector in prelude.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match selector.parse::<Selector>() {
+                Ok(parsed) => rules.push((
+                    specificity(selector),
+                    CssRule {
+                        selector: parsed,
+                        declarations: declarations.to_string(),
+                    },
+                )),
+                Err(_) => residual.push_str(&format!("{} {{ {} }}\n", selector, declarations)),
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+
+    // Stable sort: rules with the same specificity keep their source order.
+    rules.sort_by_key(|(specificity, _)| *specificity);
+
+    Stylesheet {
+        rules: rules.into_iter().map(|(_, rule)| rule).collect(),
+        residual,
+    }
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Index just past the brace closing the block opened at `open`.
+fn matching_brace(css: &str, open: usize) -> usize {
+    let mut depth = 0;
+    for (index, c) in css[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return open + index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+/// Approximate CSS specificity as (ids, classes/attributes/pseudo-classes, elements).
+fn specificity(selector: &str) -> (usize, usize, usize) {
+    let mut specificity = (0, 0, 0);
+    for compound in selector.split(|c: char| c.is_whitespace() || matches!(c, '>' | '+' | '~')) {
+        specificity.0 += compound.matches('#').count();
+        specificity.1 += compound.matches('.').count()
+            + compound.matches('[').count()
+            + compound.matches(':').count();
+        if compound.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            specificity.2 += 1;
+        }
+    }
+    specificity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{email_body_fragment, prepare_email_html};
+
+    const BASE_URL: &str = "https://newsletter.example.com";
+
+    fn prepare(html: &str) -> String {
+        prepare_email_html(html, BASE_URL, None).unwrap()
+    }
+
+    #[test]
+    fn style_blocks_are_inlined() {
+        let html = prepare(
+            "<html><head><style>p { color: red; } .lead { font-size: 18px }</style></head>\
+             <body><p class=\"lead\">Hi</p></body></html>",
+        );
+
+        assert!(!html.contains("<style"));
+        assert!(html.contains(r#"<p class="lead" style="color: red; font-size: 18px;">Hi</p>"#));
+    }
+
+    #[test]
+    fn more_specific_rules_and_inline_styles_win() {
+        let html = prepare(
+            "<style>#intro { color: blue } p { color: red }</style>\
+             <p id=\"intro\" style=\"color: green\">Hi</p>",
+        );
+
+        assert!(html.contains(r#"style="color: red; color: blue; color: green;""#));
+    }
+
+    #[test]
+    fn media_queries_and_pseudo_classes_are_kept_in_a_style_block() {
+        let html = prepare(
+            "<html><head><style>a:hover { color: red } \
+             @media (max-width: 600px) { p { margin: 0 } }</style></head>\
+             <body><p>Hi</p></body></html>",
+        );
+
+        assert!(html.contains("a:hover { color: red }"));
+        assert!(html.contains("@media (max-width: 600px) { p { margin: 0 } }"));
+        assert_eq!(html.matches("<style>").count(), 1);
+    }
+
+    #[test]
+    fn unsupported_elements_and_event_handlers_are_stripped() {
+        let html = prepare(
+            "<p onclick=\"steal()\">Hi</p><script>alert(1)</script>\
+             <iframe src=\"https://example.com\"></iframe><a href=\"javascript:alert(1)\">x</a>",
+        );
+
+        assert!(!html.contains("onclick"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<iframe"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn relative_urls_are_resolved_against_the_base_url() {
+        let html = prepare(
+            "<a href=\"/archive\">Archive</a><img src=\"images/logo.png\">\
+             <a href=\"https://example.com\">Abs</a><a href=\"mailto:hi@example.com\">Mail</a>\
+             <a href=\"#top\">Top</a>",
+        );
+
+        assert!(html.contains(r#"href="https://newsletter.example.com/archive""#));
+        assert!(html.contains(r#"src="https://newsletter.example.com/images/logo.png""#));
+        assert!(html.contains(r#"href="https://example.com""#));
+        assert!(html.contains(r#"href="mailto:hi@example.com""#));
+        assert!(html.contains(r##"href="#top""##));
+    }
+
+    #[test]
+    fn preheader_is_added_at_the_top_of_the_body() {
+        let html = prepare_email_html(
+            "<html><body><p>Hi</p></body></html>",
+            BASE_URL,
+            Some("This week: <news>"),
+        )
+        .unwrap();
+
+        assert!(html.contains(
+            "<body><div style=\"display: none; max-height: 0; overflow: hidden; mso-hide: all;\">\
+             This week: &lt;news&gt;</div><p>Hi</p>"
+        ));
+    }
+
+    #[test]
+    fn preheader_is_prepended_to_fragments() {
+        let html = prepare_email_html("<p>Hi</p>", BASE_URL, Some("Preview")).unwrap();
+
+        assert!(html.starts_with("<div style=\"display: none;"));
+        assert!(html.ends_with("Preview</div><p>Hi</p>"));
+    }
+
+    #[test]
+    fn fragments_leave_out_the_document_and_the_preheader() {
+        let html = prepare_email_html(
+            "<html><head><title>Weekly</title></head><body><p>Hi</p></body></html>",
+            BASE_URL,
+            Some("In this issue"),
+        )
+        .unwrap();
+
+        assert_eq!(email_body_fragment(&html).unwrap(), "<p>Hi</p>");
+    }
+}
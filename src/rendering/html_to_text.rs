@@ -0,0 +1,165 @@
+use scraper::{ElementRef, Html};
+
+use super::plain_text::PlainTextWriter;
+
+/// Derives a readable plain-text alternative from the HTML body of an issue.
+///
+/// Links are turned into numbered footnotes, headings are underlined, lists
+/// keep their markers and images are replaced by their alt text.
+pub fn html_to_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let mut writer = PlainTextWriter::new();
+    walk_element(document.root_element(), &mut writer);
+    writer.finish()
+}
+
+fn walk_children(node: ElementRef<'_>, writer: &mut PlainTextWriter) {
+    for child in node.children() {
+        if let Some(text) = child.value().as_text() {
+            writer.collapsed_text(text);
+        } else if let Some(element) = ElementRef::wrap(child) {
+            walk_element(element, writer);
+        }
+    }
+}
+
+fn walk_element(node: ElementRef<'_>, writer: &mut PlainTextWriter) {
+    let element = node.value();
+    if is_hidden(element.attr("style")) {
+        return;
+    }
+
+    match element.name() {
+        "head" | "script" | "style" | "template" | "noscript" => {}
+        "br" => writer.line_break(),
+        "hr" => writer.horizontal_rule(),
+        "img" => {
+            if let Some(alt) = element.attr("alt") {
+                writer.collapsed_text(alt);
+            }
+        }
+        "a" => {
+            writer.start_link(element.attr("href").unwrap_or_default());
+            walk_children(node, writer);
+            writer.end_link();
+        }
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            writer.line_break();
+            walk_children(node, writer);
+            let level = element.name()[1..].parse().unwrap_or(1);
+            writer.end_heading(level);
+        }
+        "ul" | "ol" => {
+            let first_number = (element.name() == "ol").then(|| {
+                element
+                    .attr("start")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(1)
+            });
+            writer.start_list(first_number);
+            walk_children(node, writer);
+            writer.end_list();
+        }
+        "li" => {
+            writer.start_list_item();
+            walk_children(node, writer);
+            writer.end_list_item();
+        }
+        "blockquote" => {
+            writer.start_quote();
+            walk_children(node, writer);
+            writer.end_quote();
+        }
+        "pre" => {
+            let text: String = node.text().collect();
+            writer.preformatted(&text);
+        }
+        "tr" => {
+            walk_children(node, writer);
+            writer.line_break();
+        }
+        "td" | "th" => {
+            walk_children(node, writer);
+            writer.text(" ");
+        }
+        "p" | "div" | "section" | "article" | "header" | "footer" | "table" | "center" => {
+            writer.line_break();
+            walk_children(node, writer);
+            writer.end_block();
+        }
+        _ => walk_children(node, writer),
+    }
+}
+
+/// Hidden content, like an email preheader, has no place in the text version.
+fn is_hidden(style: Option<&str>) -> bool {
+    style
+        .map(|s| s.replace(' ', "").to_lowercase().contains("display:none"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_text;
+
+    #[test]
+    fn links_become_footnotes() {
+        let text = html_to_text(
+            r#"<p>See <a href="https://example.com">our site</a> or
+            <a href="https://example.com/docs">the docs</a>.</p>"#,
+        );
+
+        assert_eq!(
+            text,
+            "See our site [1] or the docs [2].\n\n[1] https://example.com\n[2] https://example.com/docs"
+        );
+    }
+
+    #[test]
+    fn links_whose_label_is_the_url_are_not_duplicated() {
+        let text = html_to_text(r#"<a href="https://example.com">https://example.com</a>"#);
+
+        assert_eq!(text, "https://example.com");
+    }
+
+    #[test]
+    fn headings_are_underlined() {
+        let text = html_to_text("<h1>Title</h1><p>Intro</p><h3>Details</h3>");
+
+        assert_eq!(text, "Title\n=====\n\nIntro\n\nDetails\n-------");
+    }
+
+    #[test]
+    fn lists_are_preserved() {
+        let text = html_to_text(
+            "<ul><li>one</li><li>two<ol start=\"3\"><li>three</li></ol></li></ul><p>After</p>",
+        );
+
+        assert_eq!(text, "- one\n- two\n  3. three\n\nAfter");
+    }
+
+    #[test]
+    fn images_are_replaced_by_their_alt_text() {
+        let text = html_to_text(r#"<p><img src="cat.png" alt="A sleeping cat"> Meow</p>"#);
+
+        assert_eq!(text, "A sleeping cat Meow");
+    }
+
+    #[test]
+    fn scripts_styles_and_whitespace_are_dropped() {
+        let text = html_to_text(
+            "<html><head><style>p { color: red; }</style></head>
+            <body><script>alert(1)</script><p>Hello
+                   <b>world</b></p></body></html>",
+        );
+
+        assert_eq!(text, "Hello world");
+    }
+
+    #[test]
+    fn hidden_elements_are_skipped() {
+        let text = html_to_text(r#"<div style="display: none">Preheader</div><p>Body</p>"#);
+
+        assert_eq!(text, "Body");
+    }
+}
@@ -0,0 +1,38 @@
+use super::{html_to_text, prepare_email_html, render_markdown};
+
+/// The bodies that actually go out to subscribers.
+#[derive(Debug)]
+pub struct IssueBody {
+    pub text: String,
+    pub html: String,
+}
+
+/// Renders what an author wrote into email-ready bodies.
+///
+/// Markdown takes precedence; otherwise the HTML body is required and the
+/// plain-text body is derived from it when left empty. The HTML is always made
+/// email-safe.
+pub fn render_issue_body(
+    markdown: &str,
+    text: &str,
+    html: &str,
+    preheader: &str,
+    base_url: &str,
+) -> Result<IssueBody, anyhow::Error> {
+    let (html, text) = if !markdown.trim().is_empty() {
+        let rendered = render_markdown(markdown);
+        (rendered.html, Some(rendered.text))
+    } else if html.trim().is_empty() {
+        anyhow::bail!("The issue needs either Markdown or HTML content");
+    } else {
+        (
+            html.to_string(),
+            Some(text.to_string()).filter(|t| !t.trim().is_empty()),
+        )
+    };
+
+    let html = prepare_email_html(&html, base_url, Some(preheader))?;
+    let text = text.unwrap_or_else(|| html_to_text(&html));
+
+    Ok(IssueBody { text, html })
+}
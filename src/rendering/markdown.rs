@@ -0,0 +1,142 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+use super::plain_text::PlainTextWriter;
+
+#[derive(Debug, serde::Serialize)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub text: String,
+}
+
+/// Renders an issue written in Markdown to sanitized HTML and to a readable
+/// plain-text alternative.
+pub fn render_markdown(source: &str) -> RenderedMarkdown {
+    RenderedMarkdown {
+        html: markdown_to_html(source),
+        text: markdown_to_text(source),
+    }
+}
+
+fn parser(source: &str) -> Parser<'_> {
+    Parser::new_ext(
+        source,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    )
+}
+
+fn markdown_to_html(source: &str) -> String {
+    let mut unsanitized_html = String::new();
+    html::push_html(&mut unsanitized_html, parser(source));
+
+    // Markdown allows raw HTML, so the output is run through a whitelist
+    // sanitizer before it can end up in anyone's inbox.
+    ammonia::clean(&unsanitized_html)
+}
+
+fn markdown_to_text(source: &str) -> String {
+    let mut writer = PlainTextWriter::new();
+    let mut code_block: Option<String> = None;
+    let mut first_table_cell = true;
+
+    for event in parser(source) {
+        if let Some(code) = code_block.as_mut() {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    writer.preformatted(code);
+                    code_block = None;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => code_block = Some(String::new()),
+            Event::Start(Tag::BlockQuote(_)) => writer.start_quote(),
+            Event::End(TagEnd::BlockQuote(_)) => writer.end_quote(),
+            Event::Start(Tag::List(first_number)) => writer.start_list(first_number),
+            Event::End(TagEnd::List(_)) => writer.end_list(),
+            Event::Start(Tag::Item) => writer.start_list_item(),
+            Event::End(TagEnd::Item) => writer.end_list_item(),
+            Event::Start(Tag::Link { dest_url, .. }) => writer.start_link(&dest_url),
+            Event::End(TagEnd::Link) => writer.end_link(),
+            Event::End(TagEnd::Heading(level)) => writer.end_heading(level as u8),
+            Event::End(TagEnd::Paragraph | TagEnd::Table) => writer.end_block(),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                writer.line_break();
+                first_table_cell = true;
+            }
+            Event::Start(Tag::TableCell) => {
+                if !first_table_cell {
+                    writer.text(" | ");
+                }
+                first_table_cell = false;
+            }
+            Event::Text(text) | Event::Code(text) => writer.text(&text),
+            Event::SoftBreak => writer.text(" "),
+            Event::HardBreak => writer.line_break(),
+            Event::Rule => writer.horizontal_rule(),
+            Event::TaskListMarker(checked) => writer.text(if checked { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        let rendered = render_markdown("# Hello\n\nSome *emphasis* here.");
+
+        assert!(rendered.html.contains("<h1>Hello</h1>"));
+        assert!(rendered.html.contains("<em>emphasis</em>"));
+    }
+
+    #[test]
+    fn raw_html_is_sanitized() {
+        let rendered = render_markdown(
+            "Hi <script>alert('x')</script> <a href=\"javascript:alert(1)\">there</a>",
+        );
+
+        assert!(!rendered.html.contains("<script"));
+        assert!(!rendered.html.contains("javascript:"));
+    }
+
+    #[test]
+    fn headings_are_underlined_in_plain_text() {
+        let rendered = render_markdown("# Title\n\n## Section\n\nBody");
+
+        assert_eq!(rendered.text, "Title\n=====\n\nSection\n-------\n\nBody");
+    }
+
+    #[test]
+    fn links_become_footnotes_in_plain_text() {
+        let rendered = render_markdown(
+            "Read [the post](https://example.com/post) and [again](https://example.com/post).",
+        );
+
+        assert_eq!(
+            rendered.text,
+            "Read the post [1] and again [1].\n\n[1] https://example.com/post"
+        );
+    }
+
+    #[test]
+    fn lists_are_preserved_in_plain_text() {
+        let rendered = render_markdown("- one\n- two\n  1. nested\n\nAfter");
+
+        assert_eq!(rendered.text, "- one\n- two\n  1. nested\n\nAfter");
+    }
+
+    #[test]
+    fn images_are_replaced_by_their_alt_text() {
+        let rendered = render_markdown("![A cat](https://example.com/cat.png)");
+
+        assert_eq!(rendered.text, "A cat");
+    }
+}
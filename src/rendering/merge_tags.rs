@@ -0,0 +1,131 @@
+/// What a merge tag can be replaced with for one recipient.
+///
+/// Authors write `{{ name }}` or `{{ email }}` anywhere in an issue, with an
+/// optional fallback for empty values: `{{ name | there }}`. Unknown tags are
+/// left untouched.
+#[derive(Debug, Clone, Copy)]
+pub struct MergeFields<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+}
+
+/// The body a merge tag appears in, which decides how values are escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeTarget {
+    Text,
+    Html,
+}
+
+impl MergeFields<'static> {
+    /// For copies of an issue nobody in particular reads, like the public
+    /// archive: every tag is left empty or falls back to its default, so no
+    /// subscriber's details end up there.
+    pub const ANONYMOUS: Self = Self {
+        name: "",
+        email: "",
+    };
+}
+
+impl MergeFields<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "name" => Some(self.name),
+            "email" => Some(self.email),
+            _ => None,
+        }
+    }
+}
+
+pub fn fill_merge_tags(template: &str, fields: &MergeFields, target: MergeTarget) -> String {
+    replace_tags(template, |key, fallback| {
+        let value = fields.get(key)?;
+        Some(if value.trim().is_empty() {
+            fallback.to_owned()
+        } else {
+            match target {
+                MergeTarget::Text => value.to_owned(),
+                MergeTarget::Html => htmlescape::encode_minimal(value),
+            }
+        })
+    })
+}
+
+/// Replaces every `{{ key }}` or `{{ key | fallback }}` tag of `template`
+/// with what `replacement` returns for its trimmed key and fallback. Tags it
+/// returns `None` for are left untouched.
+pub fn replace_tags(
+    template: &str,
+    mut replacement: impl FnMut(&str, &str) -> Option<String>,
+) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let tag = &rest[start + 2..start + 2 + length];
+        output.push_str(&rest[..start]);
+
+        let (key, fallback) = match tag.split_once('|') {
+            Some((key, fallback)) => (key.trim(), fallback.trim()),
+            None => (tag.trim(), ""),
+        };
+        match replacement(key, fallback) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..start + 4 + length]),
+        }
+        rest = &rest[start + 4 + length..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_merge_tags, MergeFields, MergeTarget};
+
+    const FIELDS: MergeFields = MergeFields {
+        name: "Ursula <3",
+        email: "ursula@example.com",
+    };
+
+    #[test]
+    fn tags_are_replaced_with_the_recipient_fields() {
+        let filled = fill_merge_tags("Hi {{name}}, {{ email }}", &FIELDS, MergeTarget::Text);
+        assert_eq!(filled, "Hi Ursula <3, ursula@example.com");
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        let filled = fill_merge_tags("<p>Hi {{ name }}</p>", &FIELDS, MergeTarget::Html);
+        assert_eq!(filled, "<p>Hi Ursula &lt;3</p>");
+    }
+
+    #[test]
+    fn empty_values_use_the_fallback() {
+        let fields = MergeFields { name: "", ..FIELDS };
+        let filled = fill_merge_tags("Hi {{ name | there }}!", &fields, MergeTarget::Text);
+        assert_eq!(filled, "Hi there!");
+    }
+
+    #[test]
+    fn anonymous_copies_use_the_fallbacks() {
+        let filled = fill_merge_tags(
+            "Hi {{ name | there }}, {{ email }}!",
+            &MergeFields::ANONYMOUS,
+            MergeTarget::Text,
+        );
+        assert_eq!(filled, "Hi there, !");
+    }
+
+    #[test]
+    fn unknown_and_unterminated_tags_are_left_alone() {
+        let template = "{{ unsubscribe }} and {{ name";
+        assert_eq!(
+            fill_merge_tags(template, &FIELDS, MergeTarget::Text),
+            template
+        );
+    }
+}
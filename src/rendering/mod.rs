@@ -0,0 +1,12 @@
+mod email_html;
+mod html_to_text;
+mod issue;
+mod markdown;
+mod merge_tags;
+mod plain_text;
+
+pub use email_html::*;
+pub use html_to_text::*;
+pub use issue::*;
+pub use markdown::*;
+pub use merge_tags::*;
@@ -0,0 +1,203 @@
+/// Incrementally builds the plain-text alternative of an issue.
+///
+/// Renderers drive the writer with block and inline events; the writer takes
+/// care of blank lines between blocks, list markers, quote prefixes, heading
+/// underlines and collecting links as numbered footnotes.
+#[derive(Default)]
+pub struct PlainTextWriter {
+    output: String,
+    line: String,
+    containers: Vec<Container>,
+    lists: Vec<Option<u64>>,
+    links: Vec<String>,
+    open_links: Vec<(String, usize)>,
+}
+
+enum Container {
+    Quote,
+    ListItem { marker: String, used: bool },
+}
+
+impl PlainTextWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.line.push_str(text);
+    }
+
+    /// Appends HTML text, collapsing whitespace runs the way a browser would.
+    pub fn collapsed_text(&mut self, text: &str) {
+        let mut last_was_space = self.line.is_empty() || self.line.ends_with(' ');
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !last_was_space {
+                    self.line.push(' ');
+                }
+                last_was_space = true;
+            } else {
+                self.line.push(c);
+                last_was_space = false;
+            }
+        }
+    }
+
+    pub fn line_break(&mut self) {
+        self.flush_line();
+    }
+
+    pub fn end_block(&mut self) {
+        self.flush_line();
+        self.blank_line();
+    }
+
+    pub fn end_heading(&mut self, level: u8) {
+        let heading = std::mem::take(&mut self.line);
+        let heading = heading.trim();
+        if heading.is_empty() {
+            return;
+        }
+
+        let underline = if level == 1 { "=" } else { "-" };
+        self.line = heading.to_string();
+        self.flush_line();
+        self.line = underline.repeat(heading.chars().count());
+        self.end_block();
+    }
+
+    pub fn horizontal_rule(&mut self) {
+        self.flush_line();
+        self.line = "-".repeat(20);
+        self.end_block();
+    }
+
+    pub fn preformatted(&mut self, text: &str) {
+        self.flush_line();
+        for line in text.trim_end_matches('\n').lines() {
+            self.write_line(format!("    {}", line).trim_end());
+        }
+        self.blank_line();
+    }
+
+    pub fn start_quote(&mut self) {
+        self.flush_line();
+        self.containers.push(Container::Quote);
+    }
+
+    pub fn end_quote(&mut self) {
+        self.flush_line();
+        self.containers.pop();
+        self.blank_line();
+    }
+
+    pub fn start_list(&mut self, first_number: Option<u64>) {
+        self.flush_line();
+        self.lists.push(first_number);
+    }
+
+    pub fn end_list(&mut self) {
+        self.flush_line();
+        self.lists.pop();
+        if self.lists.is_empty() {
+            self.blank_line();
+        }
+    }
+
+    pub fn start_list_item(&mut self) {
+        self.flush_line();
+        let marker = match self.lists.last_mut() {
+            Some(Some(number)) => {
+                let marker = format!("{}. ", number);
+                *number += 1;
+                marker
+            }
+            _ => "- ".to_string(),
+        };
+        self.containers.push(Container::ListItem {
+            marker,
+            used: false,
+        });
+    }
+
+    pub fn end_list_item(&mut self) {
+        self.flush_line();
+        self.containers.pop();
+    }
+
+    pub fn start_link(&mut self, url: &str) {
+        self.open_links.push((url.to_string(), self.line.len()));
+    }
+
+    pub fn end_link(&mut self) {
+        let Some((url, start)) = self.open_links.pop() else {
+            return;
+        };
+
+        let label = self.line.get(start..).unwrap_or_default().trim();
+        if url.is_empty() || url.starts_with('#') || label == url {
+            return;
+        }
+        if label.is_empty() {
+            self.line.push_str(&url);
+            return;
+        }
+
+        let number = match self.links.iter().position(|l| *l == url) {
+            Some(index) => index + 1,
+            None => {
+                self.links.push(url);
+                self.links.len()
+            }
+        };
+        self.line.push_str(&format!(" [{}]", number));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.flush_line();
+
+        let mut output = self.output.trim_end().to_string();
+        if !self.links.is_empty() {
+            output.push_str("\n\n");
+            for (index, url) in self.links.iter().enumerate() {
+                output.push_str(&format!("[{}] {}\n", index + 1, url));
+            }
+        }
+
+        output.trim_end().to_string()
+    }
+
+    fn flush_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        let line = line.trim();
+        if !line.is_empty() {
+            self.write_line(line);
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let mut prefix = String::new();
+        for container in self.containers.iter_mut() {
+            match container {
+                Container::Quote => prefix.push_str("> "),
+                Container::ListItem { marker, used } if !*used => {
+                    prefix.push_str(marker);
+                    *used = true;
+                }
+                Container::ListItem { marker, .. } => {
+                    prefix.push_str(&" ".repeat(marker.len()));
+                }
+            }
+        }
+
+        self.output.push_str(&prefix);
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn blank_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{Html, IntoResponse, Response};
+use sqlx::PgPool;
+
+use crate::analytics::{
+    engagement_by_week, get_subscriber_growth, list_issue_engagement, list_top_links, CsvWriter,
+    EngagementWeek, GrowthWeek, IssueEngagement, TopLink, GROWTH_WEEKS,
+};
+use crate::utils::{e500, AppError};
+use crate::web_templates::AnalyticsTemplate;
+
+/// The tables of the analytics page that can be downloaded.
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsReport {
+    Issues,
+    Links,
+    Engagement,
+    Growth,
+}
+
+impl AnalyticsReport {
+    fn file_name(self) -> &'static str {
+        match self {
+            AnalyticsReport::Issues => "issues.csv",
+            AnalyticsReport::Links => "links.csv",
+            AnalyticsReport::Engagement => "engagement.csv",
+            AnalyticsReport::Growth => "growth.csv",
+        }
+    }
+}
+
+fn rate(rate: Option<f64>) -> String {
+    rate.map(|r| format!("{:.1}", r)).unwrap_or_default()
+}
+
+pub async fn analytics(State(pool): State<PgPool>) -> Result<Html<String>, AppError> {
+    let issues = get_issues(&pool).await?;
+    let template = AnalyticsTemplate {
+        engagement: engagement_by_week(&issues),
+        issues,
+        top_links: get_top_links(&pool).await?,
+        growth: get_growth(&pool).await?,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// The numbers behind one table of the analytics page, as CSV.
+#[tracing::instrument(name = "Export analytics", skip(pool))]
+pub async fn export_analytics(
+    State(pool): State<PgPool>,
+    Path(report): Path<AnalyticsReport>,
+) -> Result<Response, AppError> {
+    let csv = match report {
+        AnalyticsReport::Issues => issues_csv(&get_issues(&pool).await?),
+        AnalyticsReport::Engagement => {
+            engagement_csv(&engagement_by_week(&get_issues(&pool).await?))
+        }
+        AnalyticsReport::Links => links_csv(&get_top_links(&pool).await?),
+        AnalyticsReport::Growth => growth_csv(&get_growth(&pool).await?),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", report.file_name()),
+            ),
+        ],
+        csv.finish(),
+    )
+        .into_response())
+}
+
+async fn get_issues(pool: &PgPool) -> Result<Vec<IssueEngagement>, AppError> {
+    list_issue_engagement(pool)
+        .await
+        .context("Failed to compute issue engagement")
+        .map_err(e500)
+}
+
+async fn get_top_links(pool: &PgPool) -> Result<Vec<TopLink>, AppError> {
+    list_top_links(pool)
+        .await
+        .context("Failed to list the top links")
+        .map_err(e500)
+}
+
+async fn get_growth(pool: &PgPool) -> Result<Vec<GrowthWeek>, AppError> {
+    get_subscriber_growth(pool, GROWTH_WEEKS)
+        .await
+        .context("Failed to compute subscriber growth")
+        .map_err(e500)
+}
+
+fn issues_csv(issues: &[IssueEngagement]) -> CsvWriter {
+    let mut csv = CsvWriter::new(&[
+        "issue_id",
+        "title",
+        "sent_at",
+        "delivered",
+        "opens",
+        "unique_opens",
+        "machine_opens",
+        "open_rate",
+        "clicks",
+        "unique_clicks",
+        "click_rate",
+    ]);
+    for issue in issues {
+        csv.row(&[
+            issue.issue_id.to_string(),
+            issue.title.clone(),
+            issue.sent_at.to_rfc3339(),
+            issue.delivered.to_string(),
+            issue.opens.to_string(),
+            issue.unique_opens.to_string(),
+            issue.machine_opens.to_string(),
+            rate(issue.open_rate()),
+            issue.clicks.to_string(),
+            issue.unique_clicks.to_string(),
+            rate(Some(issue.click_rate())),
+        ]);
+    }
+    csv
+}
+
+fn engagement_csv(weeks: &[EngagementWeek]) -> CsvWriter {
+    let mut csv = CsvWriter::new(&[
+        "week",
+        "issues",
+        "delivered",
+        "unique_opens",
+        "open_rate",
+        "unique_clicks",
+        "click_rate",
+    ]);
+    for week in weeks {
+        csv.row(&[
+            week.week.to_string(),
+            week.issues.to_string(),
+            week.delivered.to_string(),
+            week.unique_opens.to_string(),
+            rate(week.open_rate()),
+            week.unique_clicks.to_string(),
+            rate(Some(week.click_rate())),
+        ]);
+    }
+    csv
+}
+
+fn links_csv(links: &[TopLink]) -> CsvWriter {
+    let mut csv = CsvWriter::new(&["issue_id", "title", "url", "clicks", "unique_clicks"]);
+    for link in links {
+        csv.row(&[
+            link.issue_id.to_string(),
+            link.title.clone(),
+            link.url.clone(),
+            link.clicks.to_string(),
+            link.unique_clicks.to_string(),
+        ]);
+    }
+    csv
+}
+
+fn growth_csv(weeks: &[GrowthWeek]) -> CsvWriter {
+    let mut csv = CsvWriter::new(&["week", "subscribed", "unsubscribed", "total", "churn_rate"]);
+    for week in weeks {
+        csv.row(&[
+            week.week.to_string(),
+            week.subscribed.to_string(),
+            week.unsubscribed.to_string(),
+            week.total.to_string(),
+            rate(Some(week.churn_rate())),
+        ]);
+    }
+    csv
+}
@@ -0,0 +1,98 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum_extra::extract::Form;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::newsletter_issues::{
+    discard_dead_letters, list_dead_letters, requeue_dead_letters, DeadLetterFilter,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other, AppError};
+use crate::web_templates::DeadLettersTemplate;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterAction {
+    RequeueSelected,
+    RequeueAll,
+    DiscardSelected,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeadLettersFormData {
+    issue_id: Uuid,
+    /// The checked deliveries of the issue.
+    #[serde(default)]
+    subscriber_email: Vec<String>,
+    action: DeadLetterAction,
+}
+
+pub async fn dead_letters(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let groups = list_dead_letters(&pool).await.map_err(e500)?;
+
+    let template = DeadLettersTemplate {
+        flash_messages,
+        groups,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Manage dead-lettered deliveries",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%form.issue_id)
+)]
+pub async fn manage_dead_letters(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Form(form): Form<DeadLettersFormData>,
+) -> Result<Response, AppError> {
+    let selected = match form.action {
+        DeadLetterAction::RequeueAll => None,
+        DeadLetterAction::RequeueSelected | DeadLetterAction::DiscardSelected => {
+            if form.subscriber_email.is_empty() {
+                session.flash_error("Select at least one delivery").await;
+                return Ok(see_other("/admin/dead-letters"));
+            }
+            Some(form.subscriber_email)
+        }
+    };
+    let filter = DeadLetterFilter {
+        issue_id: Some(form.issue_id),
+        subscriber_emails: selected,
+        error_contains: None,
+    };
+
+    match form.action {
+        DeadLetterAction::RequeueSelected | DeadLetterAction::RequeueAll => {
+            let requeued = requeue_dead_letters(&pool, &filter)
+                .await
+                .context("Failed to requeue dead-lettered deliveries")
+                .map_err(e500)?;
+            session
+                .flash_info(format!("Requeued {} deliveries", requeued))
+                .await;
+        }
+        DeadLetterAction::DiscardSelected => {
+            let discarded = discard_dead_letters(&pool, &filter)
+                .await
+                .context("Failed to discard dead-lettered deliveries")
+                .map_err(e500)?;
+            session
+                .flash_info(format!("Discarded {} deliveries", discarded))
+                .await;
+        }
+    }
+
+    Ok(see_other("/admin/dead-letters"))
+}
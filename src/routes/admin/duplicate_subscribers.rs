@@ -0,0 +1,76 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum_extra::extract::Form;
+use sqlx::PgPool;
+
+use crate::authentication::AuthenticatedUser;
+use crate::duplicate_subscribers::{find_duplicate_subscribers, merge_duplicate_subscribers};
+use crate::email_policy::EmailPolicyLoader;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other, AppError};
+use crate::web_templates::DuplicateSubscribersTemplate;
+
+#[derive(serde::Deserialize)]
+pub struct MergeFormData {
+    normalized_email: String,
+}
+
+pub async fn duplicate_subscribers(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(loader): State<EmailPolicyLoader>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let policy = loader.load(&pool).await.map_err(e500)?;
+    let groups = find_duplicate_subscribers(&pool, &policy)
+        .await
+        .map_err(e500)?;
+
+    let template = DuplicateSubscribersTemplate {
+        flash_messages,
+        groups,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Merge duplicate subscribers",
+    skip_all,
+    fields(user_id=%&*user_id, normalized_email=%form.normalized_email)
+)]
+pub async fn merge_duplicates(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(loader): State<EmailPolicyLoader>,
+    Form(form): Form<MergeFormData>,
+) -> Result<Response, AppError> {
+    let policy = loader.load(&pool).await.map_err(e500)?;
+    let merged = merge_duplicate_subscribers(&pool, &policy, &form.normalized_email)
+        .await
+        .context("Failed to merge duplicate subscribers")
+        .map_err(e500)?;
+
+    match merged {
+        Some((kept, count)) => {
+            let merged = if count == 1 {
+                "1 subscriber".to_owned()
+            } else {
+                format!("{} subscribers", count)
+            };
+            session
+                .flash_info(format!("Merged {} into {}", merged, kept.email))
+                .await;
+        }
+        None => {
+            session
+                .flash_error(format!("{} has no duplicates left", form.normalized_email))
+                .await;
+        }
+    }
+
+    Ok(see_other("/admin/subscribers/duplicates"))
+}
@@ -0,0 +1,107 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum_extra::extract::Form;
+use sqlx::PgPool;
+
+use crate::authentication::AuthenticatedUser;
+use crate::email_policy::{
+    delete_blocked_domain, insert_blocked_domain, list_blocked_domains, list_flagged_subscribers,
+    parse_blocked_domain, EmailPolicyLoader,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other, AppError};
+use crate::web_templates::EmailPolicyTemplate;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockedDomainAction {
+    Add,
+    Remove,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BlockedDomainFormData {
+    domain: String,
+    action: BlockedDomainAction,
+}
+
+pub async fn email_policy(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(loader): State<EmailPolicyLoader>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let blocked_domains = list_blocked_domains(&pool).await.map_err(e500)?;
+    let policy = loader.load(&pool).await.map_err(e500)?;
+    let flagged_subscribers = list_flagged_subscribers(&pool, &policy)
+        .await
+        .map_err(e500)?;
+
+    let template = EmailPolicyTemplate {
+        flash_messages,
+        blocked_domains,
+        flagged_subscribers,
+        block_disposable: loader.settings().block_disposable,
+        disposable_domain_count: loader.disposable_domain_count(),
+        block_role_accounts: loader.settings().block_role_accounts,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Manage the blocked email domains",
+    skip_all,
+    fields(user_id=%&*user_id, domain=%form.domain)
+)]
+pub async fn manage_blocked_domains(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Form(form): Form<BlockedDomainFormData>,
+) -> Result<Response, AppError> {
+    let domain = match parse_blocked_domain(&form.domain) {
+        Ok(domain) => domain,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other("/admin/email-policy"));
+        }
+    };
+
+    match form.action {
+        BlockedDomainAction::Add => {
+            let added = insert_blocked_domain(&pool, &domain)
+                .await
+                .context("Failed to block a domain")
+                .map_err(e500)?;
+            if added {
+                session
+                    .flash_info(format!("Addresses at {} can no longer subscribe", domain))
+                    .await;
+            } else {
+                session
+                    .flash_info(format!("{} was already blocked", domain))
+                    .await;
+            }
+        }
+        BlockedDomainAction::Remove => {
+            let removed = delete_blocked_domain(&pool, &domain)
+                .await
+                .context("Failed to unblock a domain")
+                .map_err(e500)?;
+            if removed {
+                session
+                    .flash_info(format!("Addresses at {} can subscribe again", domain))
+                    .await;
+            } else {
+                session
+                    .flash_error(format!("{} was not blocked", domain))
+                    .await;
+            }
+        }
+    }
+
+    Ok(see_other("/admin/email-policy"))
+}
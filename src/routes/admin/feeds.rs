@@ -0,0 +1,185 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::{Form, Path, State};
+use axum::response::{Html, Response};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::feed_sources::{
+    create_feed_source, delete_feed_source, get_feed_source, is_duplicate_feed_url,
+    list_feed_sources, update_feed_source, FeedSourceSettings,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e404, e500, see_other, AppError};
+use crate::web_templates::{FeedSourceTemplate, FeedSourcesTemplate};
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishMode {
+    Draft,
+    Publish,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FeedSourceFormData {
+    name: String,
+    url: String,
+    publish_mode: PublishMode,
+    /// Empty for an issue per post.
+    #[serde(default)]
+    digest_size: String,
+    /// Left out when adding a source, which starts with the default templates.
+    title_template: Option<String>,
+    body_template: Option<String>,
+    post_template: Option<String>,
+}
+
+impl TryFrom<FeedSourceFormData> for FeedSourceSettings {
+    type Error = String;
+
+    fn try_from(form: FeedSourceFormData) -> Result<Self, Self::Error> {
+        let defaults = FeedSourceSettings::new(form.name.trim().into(), form.url.trim().into());
+        let digest_size = match form.digest_size.trim() {
+            "" => None,
+            size => Some(
+                size.parse()
+                    .map_err(|_| "The digest size must be a number".to_owned())?,
+            ),
+        };
+        let settings = FeedSourceSettings {
+            auto_publish: matches!(form.publish_mode, PublishMode::Publish),
+            digest_size,
+            title_template: form
+                .title_template
+                .unwrap_or(defaults.title_template.clone()),
+            body_template: form.body_template.unwrap_or(defaults.body_template.clone()),
+            post_template: form.post_template.unwrap_or(defaults.post_template.clone()),
+            ..defaults
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+pub async fn feed_sources(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let sources = list_feed_sources(&pool).await.map_err(e500)?;
+
+    let template = FeedSourcesTemplate {
+        flash_messages,
+        sources,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(name = "Add a feed source", skip_all, fields(user_id=%&*user_id))]
+pub async fn add_feed_source(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Form(form): Form<FeedSourceFormData>,
+) -> Result<Response, AppError> {
+    let settings = match FeedSourceSettings::try_from(form) {
+        Ok(settings) => settings,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other("/admin/feeds"));
+        }
+    };
+
+    match create_feed_source(&pool, &settings).await {
+        Ok(feed_source_id) => {
+            session
+                .flash_info("Feed added - posts published from now on will be turned into issues")
+                .await;
+            Ok(see_other(&format!("/admin/feeds/{}", feed_source_id)))
+        }
+        Err(e) if is_duplicate_feed_url(&e) => {
+            session.flash_error("This feed was already added").await;
+            Ok(see_other("/admin/feeds"))
+        }
+        Err(e) => Err(e500(e)),
+    }
+}
+
+pub async fn feed_source(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(feed_source_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let source = get_feed_source(&pool, feed_source_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow::anyhow!("The feed source does not exist")))?;
+
+    let template = FeedSourceTemplate {
+        flash_messages,
+        source,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Save a feed source",
+    skip_all,
+    fields(user_id=%&*user_id, feed_source_id=%feed_source_id)
+)]
+pub async fn save_feed_source(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(feed_source_id): Path<Uuid>,
+    Form(form): Form<FeedSourceFormData>,
+) -> Result<Response, AppError> {
+    let location = format!("/admin/feeds/{}", feed_source_id);
+    let settings = match FeedSourceSettings::try_from(form) {
+        Ok(settings) => settings,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other(&location));
+        }
+    };
+
+    match update_feed_source(&pool, feed_source_id, &settings).await {
+        Ok(true) => {
+            session.flash_info("Feed saved").await;
+            Ok(see_other(&location))
+        }
+        Ok(false) => Err(e404(anyhow::anyhow!("The feed source does not exist"))),
+        Err(e) if is_duplicate_feed_url(&e) => {
+            session.flash_error("Another feed has this URL").await;
+            Ok(see_other(&location))
+        }
+        Err(e) => Err(e500(e)),
+    }
+}
+
+#[tracing::instrument(
+    name = "Remove a feed source",
+    skip_all,
+    fields(user_id=%&*user_id, feed_source_id=%feed_source_id)
+)]
+pub async fn remove_feed_source(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(feed_source_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let found = delete_feed_source(&pool, feed_source_id)
+        .await
+        .context("Failed to remove the feed source")
+        .map_err(e500)?;
+    if !found {
+        return Err(e404(anyhow::anyhow!("The feed source does not exist")));
+    }
+
+    session.flash_info("Feed removed").await;
+    Ok(see_other("/admin/feeds"))
+}
@@ -0,0 +1,44 @@
+use anyhow::Context;
+use axum::extract::{Form, Path, State};
+use axum::response::Response;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::newsletter_issues::set_hidden_from_archive;
+use crate::session_state::TypedSession;
+use crate::utils::{e404, e500, see_other, AppError};
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveFormData {
+    hidden: bool,
+}
+
+#[tracing::instrument(
+    name = "Change whether an issue is in the archive",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn set_archive_visibility(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<ArchiveFormData>,
+) -> Result<Response, AppError> {
+    let found = set_hidden_from_archive(&pool, issue_id, form.hidden)
+        .await
+        .context("Failed to update the issue")
+        .map_err(e500)?;
+    if !found {
+        return Err(e404(anyhow::anyhow!("The issue does not exist")));
+    }
+
+    if form.hidden {
+        session.flash_info("Hidden from the public archive").await;
+    } else {
+        session.flash_info("Listed in the public archive").await;
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
@@ -0,0 +1,107 @@
+use anyhow::Context;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::newsletter_issues::{
+    cancel_sending, get_delivery_counts, pause_sending, resume_sending, DeliveryCounts,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other, AppError};
+
+fn progress(counts: &DeliveryCounts) -> String {
+    format!("{} of {} emails sent", counts.sent, counts.total())
+}
+
+#[tracing::instrument(
+    name = "Pause sending an issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn pause_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let paused = pause_sending(&pool, issue_id)
+        .await
+        .context("Failed to pause the issue")
+        .map_err(e500)?;
+    if !paused {
+        session
+            .flash_error("Only an issue that is being sent can be paused")
+            .await;
+    } else {
+        let counts = get_delivery_counts(&pool, issue_id).await.map_err(e500)?;
+        session
+            .flash_info(format!("Sending paused - {} so far", progress(&counts)))
+            .await;
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
+
+#[tracing::instrument(
+    name = "Resume sending an issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn resume_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let resumed = resume_sending(&pool, issue_id)
+        .await
+        .context("Failed to resume the issue")
+        .map_err(e500)?;
+    if !resumed {
+        session
+            .flash_error("Only a paused issue can be resumed")
+            .await;
+    } else {
+        session.flash_info("Sending resumed").await;
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
+
+#[tracing::instrument(
+    name = "Cancel sending an issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn cancel_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let cancelled = cancel_sending(&pool, issue_id)
+        .await
+        .context("Failed to cancel the issue")
+        .map_err(e500)?;
+    match cancelled {
+        None => {
+            session
+                .flash_error("Only an issue that is being sent or paused can be cancelled")
+                .await
+        }
+        Some(cancelled) => {
+            let counts = get_delivery_counts(&pool, issue_id).await.map_err(e500)?;
+            session
+                .flash_info(format!(
+                    "Sending cancelled - {}, {} will not go out",
+                    progress(&counts),
+                    cancelled
+                ))
+                .await;
+        }
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
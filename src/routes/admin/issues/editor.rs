@@ -0,0 +1,187 @@
+use anyhow::anyhow;
+use askama::Template;
+use axum::extract::{Form, Path, State};
+use axum::response::{Html, Response};
+use axum::Json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::domain::DeliveryMode;
+use crate::newsletter_issues::{
+    get_delivery_stats, get_issue, list_delivery_waves, save_draft, DraftContent, DraftOptions,
+    SaveDraftError,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e404, e409, e500, see_other, AppError};
+use crate::web_templates::IssueEditorTemplate;
+
+/// Bounds of the subject line test settings; out of range values are brought
+/// back within them.
+const MIN_AB_TEST_PERCENT: i16 = 2;
+const MAX_AB_TEST_PERCENT: i16 = 50;
+const MAX_AB_TEST_HOURS: i16 = 72;
+
+#[derive(serde::Deserialize)]
+pub struct IssueFormData {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    preheader: String,
+    #[serde(default)]
+    markdown: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    html: String,
+    /// Checkbox: count opens with a tracking pixel.
+    #[serde(default)]
+    track_opens: Option<String>,
+    /// Subjects to test against the title, left empty when unused.
+    #[serde(default)]
+    subject_b: String,
+    #[serde(default)]
+    subject_c: String,
+    #[serde(default)]
+    subject_d: String,
+    #[serde(default)]
+    ab_test_percent: String,
+    #[serde(default)]
+    ab_test_hours: String,
+}
+
+impl IssueFormData {
+    pub fn into_draft(self) -> (DraftContent, DraftOptions) {
+        let defaults = DraftOptions::default();
+        let options = DraftOptions {
+            track_opens: self.track_opens.is_some(),
+            subject_variants: [self.subject_b, self.subject_c, self.subject_d]
+                .into_iter()
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            ab_test_percent: self
+                .ab_test_percent
+                .trim()
+                .parse()
+                .unwrap_or(defaults.ab_test_percent)
+                .clamp(MIN_AB_TEST_PERCENT, MAX_AB_TEST_PERCENT),
+            ab_test_hours: self
+                .ab_test_hours
+                .trim()
+                .parse()
+                .unwrap_or(defaults.ab_test_hours)
+                .clamp(1, MAX_AB_TEST_HOURS),
+        };
+        let content = DraftContent {
+            title: self.title,
+            preheader: self.preheader,
+            markdown: Some(self.markdown).filter(|m| !m.trim().is_empty()),
+            text: self.text,
+            html: self.html,
+        };
+        (content, options)
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct AutosaveResponse {
+    /// RFC 3339 timestamp of the save.
+    saved_at: String,
+}
+
+pub(super) fn save_draft_error(e: SaveDraftError) -> AppError {
+    match e {
+        SaveDraftError::NotFound => e404(e),
+        SaveDraftError::NotEditable(_) => e409(e),
+        SaveDraftError::UnexpectedError(_) => e500(e),
+    }
+}
+
+pub async fn issue_editor(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let issue = get_issue(&pool, issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("The issue does not exist")))?;
+    let waves = list_delivery_waves(&pool, issue_id).await.map_err(e500)?;
+    let deliveries = if issue.status.is_editable() {
+        None
+    } else {
+        Some(get_delivery_stats(&pool, issue_id).await.map_err(e500)?)
+    };
+
+    let template = IssueEditorTemplate {
+        flash_messages,
+        issue_id,
+        status: issue.status,
+        editable: issue.status.is_editable(),
+        title: issue.content.title,
+        preheader: issue.content.preheader,
+        markdown: issue.content.markdown.unwrap_or_default(),
+        text: issue.content.text,
+        html: issue.content.html,
+        track_opens: issue.options.track_opens,
+        subject_variants: issue.options.subject_variants,
+        ab_test_percent: issue.options.ab_test_percent,
+        ab_test_hours: issue.options.ab_test_hours,
+        scheduled_at: issue.scheduled_at,
+        local_send_at: issue
+            .local_send_at
+            .filter(|_| issue.delivery_mode == DeliveryMode::LocalTime),
+        waves,
+        deliveries,
+        slug: issue.slug,
+        hide_from_archive: issue.hide_from_archive,
+        updated_at: issue.updated_at,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Save a draft issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn save_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<IssueFormData>,
+) -> Result<Response, AppError> {
+    let (content, options) = form.into_draft();
+    match save_draft(&pool, issue_id, *user_id, &content, Some(&options), false).await {
+        Ok(_) => session.flash_info("Draft saved").await,
+        Err(e @ SaveDraftError::NotEditable(_)) => session.flash_error(e.to_string()).await,
+        Err(e) => return Err(save_draft_error(e)),
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
+
+#[tracing::instrument(
+    name = "Autosave a draft issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn autosave_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<IssueFormData>,
+) -> Result<Json<AutosaveResponse>, AppError> {
+    let (content, options) = form.into_draft();
+    let saved_at = save_draft(&pool, issue_id, *user_id, &content, Some(&options), true)
+        .await
+        .map_err(save_draft_error)?;
+
+    Ok(Json(AutosaveResponse {
+        saved_at: saved_at.to_rfc3339(),
+    }))
+}
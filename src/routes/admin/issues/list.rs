@@ -0,0 +1,35 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Response};
+use sqlx::PgPool;
+
+use crate::newsletter_issues::{create_draft, list_issues};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use crate::web_templates::IssuesListTemplate;
+
+pub async fn issues_list(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+) -> Result<Html<String>, crate::utils::AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let issues = list_issues(&pool).await.map_err(e500)?;
+
+    let template = IssuesListTemplate {
+        flash_messages,
+        issues,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(name = "Create a draft issue", skip_all)]
+pub async fn new_issue(State(pool): State<PgPool>) -> Result<Response, crate::utils::AppError> {
+    let issue_id = create_draft(&pool)
+        .await
+        .context("Failed to create a draft issue")
+        .map_err(e500)?;
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
@@ -0,0 +1,17 @@
+mod archive;
+mod controls;
+mod editor;
+mod list;
+mod revisions;
+mod schedule;
+mod send;
+mod test_send;
+
+pub use archive::*;
+pub use controls::*;
+pub use editor::*;
+pub use list::*;
+pub use revisions::*;
+pub use schedule::*;
+pub use send::*;
+pub use test_send::*;
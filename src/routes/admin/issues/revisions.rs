@@ -0,0 +1,127 @@
+use anyhow::anyhow;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{Html, Response};
+use similar::{ChangeTag, TextDiff};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::editor::save_draft_error;
+use crate::authentication::AuthenticatedUser;
+use crate::newsletter_issues::{
+    get_issue, get_revision, list_revisions, save_draft, DraftContent, SaveDraftError,
+};
+use crate::session_state::TypedSession;
+use crate::utils::{e404, e500, see_other, AppError};
+use crate::web_templates::{DiffLine, FieldDiff, IssueRevisionTemplate, IssueRevisionsTemplate};
+
+pub async fn issue_revisions(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let issue = get_issue(&pool, issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("The issue does not exist")))?;
+    let revisions = list_revisions(&pool, issue_id).await.map_err(e500)?;
+
+    let template = IssueRevisionsTemplate {
+        flash_messages,
+        issue_id,
+        title: issue.content.title,
+        revisions,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Shows what changed between a revision and the current draft.
+pub async fn issue_revision(
+    State(pool): State<PgPool>,
+    Path((issue_id, revision_id)): Path<(Uuid, Uuid)>,
+) -> Result<Html<String>, AppError> {
+    let issue = get_issue(&pool, issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("The issue does not exist")))?;
+    let revision = get_revision(&pool, issue_id, revision_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("The revision does not exist")))?;
+
+    let template = IssueRevisionTemplate {
+        issue_id,
+        editable: issue.status.is_editable(),
+        diffs: diff_content(&revision.content, &issue.content),
+        revision,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Restore a revision",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id, revision_id=%revision_id)
+)]
+pub async fn restore_revision(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path((issue_id, revision_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    let revision = get_revision(&pool, issue_id, revision_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("The revision does not exist")))?;
+
+    match save_draft(&pool, issue_id, *user_id, &revision.content, None, false).await {
+        Ok(_) => {
+            let message = format!(
+                "Restored the revision from {}",
+                revision.created_at.format("%Y-%m-%d %H:%M")
+            );
+            session.flash_info(message).await;
+        }
+        Err(e @ SaveDraftError::NotEditable(_)) => session.flash_error(e.to_string()).await,
+        Err(e) => return Err(save_draft_error(e)),
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
+
+/// Line diffs of every field that differs, going from `old` to `new`.
+fn diff_content(old: &DraftContent, new: &DraftContent) -> Vec<FieldDiff> {
+    let fields = [
+        ("Title", old.title.as_str(), new.title.as_str()),
+        ("Preheader", old.preheader.as_str(), new.preheader.as_str()),
+        (
+            "Markdown",
+            old.markdown.as_deref().unwrap_or_default(),
+            new.markdown.as_deref().unwrap_or_default(),
+        ),
+        ("Text", old.text.as_str(), new.text.as_str()),
+        ("HTML", old.html.as_str(), new.html.as_str()),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldDiff {
+            field,
+            lines: TextDiff::from_lines(old, new)
+                .iter_all_changes()
+                .map(|change| DiffLine {
+                    kind: match change.tag() {
+                        ChangeTag::Delete => "delete",
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Equal => "equal",
+                    },
+                    text: change.to_string_lossy().trim_end_matches('\n').to_string(),
+                })
+                .collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,129 @@
+use anyhow::Context;
+use axum::extract::{Form, Path, State};
+use axum::response::Response;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::editor::{save_draft_error, IssueFormData};
+use crate::authentication::AuthenticatedUser;
+use crate::domain::{DeliveryMode, ScheduledTime};
+use crate::newsletter_issues::{
+    save_draft, schedule_issue, unschedule_issue, SaveDraftError, ScheduleIssueError,
+};
+use crate::session_state::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other, AppError};
+
+#[derive(serde::Deserialize)]
+pub struct ScheduleFormData {
+    #[serde(flatten)]
+    issue: IssueFormData,
+    scheduled_at: String,
+    #[serde(default)]
+    timezone: String,
+    /// Checkbox: send at `scheduled_at` in each subscriber's own timezone.
+    #[serde(default)]
+    local_time: Option<String>,
+}
+
+/// Saves the draft as it is in the editor and schedules, or reschedules, it.
+#[tracing::instrument(
+    name = "Schedule an issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn schedule_send(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<ScheduleFormData>,
+) -> Result<Response, AppError> {
+    let editor = format!("/admin/issues/{}", issue_id);
+    let (content, options) = form.issue.into_draft();
+
+    match save_draft(&pool, issue_id, *user_id, &content, Some(&options), false).await {
+        Ok(_) => {}
+        Err(e @ SaveDraftError::NotEditable(_)) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+        Err(e) => return Err(save_draft_error(e)),
+    }
+
+    let timezone = if form.timezone.trim().is_empty() {
+        "UTC"
+    } else {
+        &form.timezone
+    };
+    let scheduled_at = match ScheduledTime::parse(&form.scheduled_at, timezone, Utc::now()) {
+        Ok(scheduled_at) => scheduled_at,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other(&editor));
+        }
+    };
+
+    // Catch content problems now rather than when nobody is watching.
+    if let Err(e) = content.render(&base_url.0) {
+        session.flash_error(e.to_string()).await;
+        return Ok(see_other(&editor));
+    }
+
+    let mode = if form.local_time.is_some() {
+        DeliveryMode::LocalTime
+    } else {
+        DeliveryMode::AtOnce
+    };
+    if mode == DeliveryMode::LocalTime && !options.subject_variants.is_empty() {
+        session
+            .flash_error("Subject lines can only be tested on issues sent at once")
+            .await;
+        return Ok(see_other(&editor));
+    }
+    match schedule_issue(&pool, issue_id, scheduled_at, mode).await {
+        Ok(()) => {}
+        Err(ScheduleIssueError::UnexpectedError(e)) => return Err(e500(e)),
+        Err(e) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+    }
+
+    let message = match mode {
+        DeliveryMode::AtOnce => format!(
+            "The issue is scheduled for {} UTC",
+            scheduled_at.as_ref().format("%Y-%m-%d %H:%M")
+        ),
+        DeliveryMode::LocalTime => format!(
+            "The issue is scheduled for {} in each subscriber's timezone",
+            scheduled_at.local().format("%Y-%m-%d %H:%M")
+        ),
+    };
+    session.flash_info(message).await;
+    Ok(see_other("/admin/issues"))
+}
+
+#[tracing::instrument(name = "Cancel a scheduled issue", skip_all, fields(newsletter_issue_id=%issue_id))]
+pub async fn cancel_scheduled_send(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let unscheduled = unschedule_issue(&pool, issue_id)
+        .await
+        .context("Failed to unschedule the issue")
+        .map_err(e500)?;
+
+    if unscheduled {
+        session
+            .flash_info("The scheduled send has been cancelled - the issue is a draft again")
+            .await;
+    } else {
+        session.flash_error("The issue is not scheduled").await;
+    }
+
+    Ok(see_other(&format!("/admin/issues/{}", issue_id)))
+}
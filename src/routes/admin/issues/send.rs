@@ -0,0 +1,80 @@
+use anyhow::Context;
+use axum::extract::{Form, Path, State};
+use axum::response::Response;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::editor::{save_draft_error, IssueFormData};
+use crate::authentication::AuthenticatedUser;
+use crate::domain::DeliveryMode;
+use crate::newsletter_issues::{begin_delivery, save_draft, SaveDraftError};
+use crate::session_state::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other, AppError};
+
+/// Saves the draft as it is in the editor, then sends it to every confirmed
+/// subscriber. The issue page then follows the delivery.
+#[tracing::instrument(
+    name = "Send a draft issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn send_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<IssueFormData>,
+) -> Result<Response, AppError> {
+    let editor = format!("/admin/issues/{}", issue_id);
+    let (content, options) = form.into_draft();
+
+    match save_draft(&pool, issue_id, *user_id, &content, Some(&options), false).await {
+        Ok(_) => {}
+        Err(e @ SaveDraftError::NotEditable(_)) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+        Err(e) => return Err(save_draft_error(e)),
+    }
+
+    let body = match content.render(&base_url.0) {
+        Ok(body) => body,
+        Err(e) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let started = begin_delivery(
+        &mut transaction,
+        issue_id,
+        &body,
+        Some(DeliveryMode::AtOnce),
+    )
+    .await
+    .context("Failed to start delivering the issue")
+    .map_err(e500)?;
+    if !started {
+        session.flash_error("The issue has already been sent").await;
+        return Ok(see_other(&editor));
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the issue")
+        .map_err(e500)?;
+
+    session
+        .flash_info("The newsletter issue has been accepted - emails will go out shortly")
+        .await;
+    Ok(see_other(&editor))
+}
@@ -0,0 +1,153 @@
+use anyhow::Context;
+use axum::extract::{Form, Path, State};
+use axum::response::Response;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::editor::{save_draft_error, IssueFormData};
+use crate::authentication::AuthenticatedUser;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::newsletter_issues::{get_recipient, personalize_issue, save_draft, SaveDraftError};
+use crate::rendering::MergeFields;
+use crate::session_state::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other, AppError};
+
+/// A test send is for a handful of inboxes, not a way around the queue.
+const MAX_TEST_RECIPIENTS: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct TestSendFormData {
+    #[serde(flatten)]
+    issue: IssueFormData,
+    /// Addresses separated by commas, semicolons or whitespace.
+    #[serde(default)]
+    test_recipients: String,
+}
+
+/// Saves the draft as it is in the editor and emails it to the given
+/// addresses, rendered exactly as the delivery worker would. The delivery
+/// queue and the issue status are left alone.
+#[tracing::instrument(
+    name = "Send a test of an issue",
+    skip_all,
+    fields(user_id=%&*user_id, newsletter_issue_id=%issue_id)
+)]
+pub async fn send_test_issue(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(email_client): State<EmailClient>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(issue_id): Path<Uuid>,
+    Form(form): Form<TestSendFormData>,
+) -> Result<Response, AppError> {
+    let editor = format!("/admin/issues/{}", issue_id);
+    let recipients = match parse_test_recipients(&form.test_recipients) {
+        Ok(recipients) => recipients,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other(&editor));
+        }
+    };
+    let (content, options) = form.issue.into_draft();
+
+    match save_draft(&pool, issue_id, *user_id, &content, Some(&options), false).await {
+        Ok(_) => {}
+        Err(e @ SaveDraftError::NotEditable(_)) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+        Err(e) => return Err(save_draft_error(e)),
+    }
+
+    let body = match content.render(&base_url.0) {
+        Ok(body) => body,
+        Err(e) => {
+            session.flash_error(e.to_string()).await;
+            return Ok(see_other(&editor));
+        }
+    };
+
+    let mut failed = Vec::new();
+    for recipient in &recipients {
+        let name = get_recipient(&pool, recipient.as_ref())
+            .await
+            .context("Failed to look up the test recipient")
+            .map_err(e500)?
+            .name;
+        let message = personalize_issue(
+            issue_id,
+            &content.title,
+            &body,
+            &MergeFields {
+                name: &name,
+                email: recipient.as_ref(),
+            },
+        );
+        if let Err(e) = email_client
+            .send_email_with_headers(
+                recipient,
+                &message.subject,
+                &message.html,
+                &message.text,
+                &message.headers,
+            )
+            .await
+        {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to send a test email to {}",
+                recipient
+            );
+            failed.push(recipient.to_string());
+        }
+    }
+
+    if failed.is_empty() {
+        session
+            .flash_info(format!("Test email sent to {}", join(&recipients)))
+            .await;
+    } else {
+        session
+            .flash_error(format!(
+                "The test email could not be sent to {}",
+                failed.join(", ")
+            ))
+            .await;
+    }
+    Ok(see_other(&editor))
+}
+
+fn parse_test_recipients(input: &str) -> Result<Vec<SubscriberEmail>, String> {
+    let mut recipients: Vec<SubscriberEmail> = Vec::new();
+    for address in input
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|a| !a.is_empty())
+    {
+        let address = SubscriberEmail::parse(address.to_string())?;
+        if !recipients.iter().any(|r| r.as_ref() == address.as_ref()) {
+            recipients.push(address);
+        }
+    }
+
+    if recipients.is_empty() {
+        return Err("Enter at least one address to send the test to".into());
+    }
+    if recipients.len() > MAX_TEST_RECIPIENTS {
+        return Err(format!(
+            "A test can go to at most {} addresses",
+            MAX_TEST_RECIPIENTS
+        ));
+    }
+    Ok(recipients)
+}
+
+fn join(recipients: &[SubscriberEmail]) -> String {
+    recipients
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,9 +1,31 @@
+mod analytics;
 mod dashboard;
+mod dead_letters;
+mod duplicate_subscribers;
+mod email_policy;
+mod feeds;
+mod issues;
 mod logout;
 mod newsletters;
 mod password;
+mod signup_form;
+mod site_theme;
+mod subscribers;
 
+pub use analytics::{analytics, export_analytics};
 pub use dashboard::{admin_dashboard, get_username};
+pub use dead_letters::{dead_letters, manage_dead_letters};
+pub use duplicate_subscribers::{duplicate_subscribers, merge_duplicates};
+pub use email_policy::{email_policy, manage_blocked_domains};
+pub use feeds::{add_feed_source, feed_source, feed_sources, remove_feed_source, save_feed_source};
+pub use issues::{
+    autosave_issue, cancel_issue, cancel_scheduled_send, issue_editor, issue_revision,
+    issue_revisions, issues_list, new_issue, pause_issue, restore_revision, resume_issue,
+    save_issue, schedule_send, send_issue, send_test_issue, set_archive_visibility,
+};
 pub use logout::log_out;
-pub use newsletters::{newsletters_form, publish_newsletter};
+pub use newsletters::{newsletters_form, preview_newsletter, publish_newsletter};
 pub use password::{change_password, change_password_form};
+pub use signup_form::{manage_allowed_origins, signup_form};
+pub use site_theme::{save_theme, site_theme};
+pub use subscribers::subscribers;
@@ -1,5 +1,7 @@
 mod get;
 mod post;
+mod preview;
 
 pub use get::*;
 pub use post::*;
+pub use preview::*;
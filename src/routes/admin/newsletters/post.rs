@@ -1,20 +1,27 @@
-use anyhow::Context;
 use axum::extract::{Form, State};
 use axum::response::Response;
-use sqlx::{PgPool, Postgres, Transaction};
-use uuid::Uuid;
+use sqlx::PgPool;
 
 use crate::{
     authentication::AuthenticatedUser,
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    newsletter_issues::publish_issue,
+    rendering::render_issue_body,
     session_state::TypedSession,
-    utils::{e400, e500, see_other},
+    startup::ApplicationBaseUrl,
+    utils::{e400, e422, e500, see_other},
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
+    #[serde(default)]
+    preheader: String,
+    #[serde(default)]
+    markdown: String,
+    #[serde(default)]
     text: String,
+    #[serde(default)]
     html: String,
     idempotency_key: String,
 }
@@ -27,16 +34,21 @@ pub struct FormData {
 pub async fn publish_newsletter(
     AuthenticatedUser(user_id): AuthenticatedUser,
     State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
     session: TypedSession,
     Form(form): Form<FormData>,
 ) -> Result<Response, crate::utils::AppError> {
     let FormData {
         title,
+        preheader,
+        markdown,
         text,
         html,
         idempotency_key,
     } = form;
 
+    let body = render_issue_body(&markdown, &text, &html, &preheader, &base_url.0).map_err(e422)?;
+    let markdown = Some(markdown).filter(|m| !m.trim().is_empty());
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
 
     let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
@@ -52,15 +64,15 @@ pub async fn publish_newsletter(
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text, &html)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-
-    enequeue_delivery_tasks(&mut transaction, issue_id)
-        .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+    publish_issue(
+        &mut transaction,
+        &title,
+        &preheader,
+        markdown.as_deref(),
+        &body,
+    )
+    .await
+    .map_err(e500)?;
 
     session
         .flash_info("The newsletter issue has been accepted - emails will go out shortly")
@@ -72,56 +84,3 @@ pub async fn publish_newsletter(
 
     Ok(response)
 }
-
-#[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
-    transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text: &str,
-    html: &str,
-) -> Result<Uuid, sqlx::Error> {
-    let newsletter_issue_id = Uuid::new_v4();
-    sqlx::query!(
-        r#"
-        INSERT INTO newsletter_issues (
-            newsletter_issue_id,
-            title,
-            text_content,
-            html_content,
-            published_at
-        )
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        newsletter_issue_id,
-        title,
-        text,
-        html,
-    )
-    .execute(transaction.as_mut())
-    .await?;
-
-    Ok(newsletter_issue_id)
-}
-
-#[tracing::instrument(skip_all)]
-async fn enequeue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email 
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-        newsletter_issue_id,
-    )
-    .execute(transaction.as_mut())
-    .await?;
-
-    Ok(())
-}
@@ -0,0 +1,13 @@
+use axum::extract::Form;
+use axum::Json;
+
+use crate::rendering::{render_markdown, RenderedMarkdown};
+
+#[derive(serde::Deserialize)]
+pub struct PreviewFormData {
+    markdown: String,
+}
+
+pub async fn preview_newsletter(Form(form): Form<PreviewFormData>) -> Json<RenderedMarkdown> {
+    Json(render_markdown(&form.markdown))
+}
@@ -0,0 +1,100 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum_extra::extract::Form;
+use sqlx::PgPool;
+
+use crate::allowed_origins::{
+    delete_allowed_origin, insert_allowed_origin, list_allowed_origins, parse_origin,
+};
+use crate::authentication::AuthenticatedUser;
+use crate::session_state::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other, AppError};
+use crate::web_templates::SignupFormTemplate;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllowedOriginAction {
+    Add,
+    Remove,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AllowedOriginFormData {
+    origin: String,
+    action: AllowedOriginAction,
+}
+
+pub async fn signup_form(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let origins = list_allowed_origins(&pool).await.map_err(e500)?;
+
+    let template = SignupFormTemplate {
+        flash_messages,
+        origins,
+        base_url: base_url.0,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(
+    name = "Manage the sites allowed to embed the signup form",
+    skip_all,
+    fields(user_id=%&*user_id, origin=%form.origin)
+)]
+pub async fn manage_allowed_origins(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Form(form): Form<AllowedOriginFormData>,
+) -> Result<Response, AppError> {
+    let origin = match parse_origin(&form.origin) {
+        Ok(origin) => origin,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other("/admin/signup-form"));
+        }
+    };
+
+    match form.action {
+        AllowedOriginAction::Add => {
+            let added = insert_allowed_origin(&pool, &origin)
+                .await
+                .context("Failed to allow a site")
+                .map_err(e500)?;
+            if added {
+                session
+                    .flash_info(format!("{} can now embed the signup form", origin))
+                    .await;
+            } else {
+                session
+                    .flash_info(format!("{} was already allowed", origin))
+                    .await;
+            }
+        }
+        AllowedOriginAction::Remove => {
+            let removed = delete_allowed_origin(&pool, &origin)
+                .await
+                .context("Failed to remove an allowed site")
+                .map_err(e500)?;
+            if removed {
+                session
+                    .flash_info(format!("{} can no longer embed the signup form", origin))
+                    .await;
+            } else {
+                session
+                    .flash_error(format!("{} was not allowed", origin))
+                    .await;
+            }
+        }
+    }
+
+    Ok(see_other("/admin/signup-form"))
+}
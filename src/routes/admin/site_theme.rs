@@ -0,0 +1,89 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::{Form, State};
+use axum::response::{Html, Response};
+use sqlx::PgPool;
+
+use crate::authentication::AuthenticatedUser;
+use crate::session_state::TypedSession;
+use crate::site_theme::{get_site_theme, save_site_theme, SiteTheme};
+use crate::utils::{e500, see_other, AppError};
+use crate::web_templates::SiteThemeTemplate;
+
+#[derive(serde::Deserialize)]
+pub struct SiteThemeFormData {
+    newsletter_name: String,
+    /// Empty for no logo.
+    #[serde(default)]
+    logo_url: String,
+    background_color: String,
+    text_color: String,
+    accent_color: String,
+    signup_copy: String,
+    check_inbox_copy: String,
+    confirmed_copy: String,
+    unsubscribed_copy: String,
+}
+
+impl TryFrom<SiteThemeFormData> for SiteTheme {
+    type Error = String;
+
+    fn try_from(form: SiteThemeFormData) -> Result<Self, Self::Error> {
+        let logo_url = form.logo_url.trim();
+        let theme = SiteTheme {
+            newsletter_name: form.newsletter_name.trim().into(),
+            logo_url: (!logo_url.is_empty()).then(|| logo_url.into()),
+            background_color: form.background_color.trim().to_lowercase(),
+            text_color: form.text_color.trim().to_lowercase(),
+            accent_color: form.accent_color.trim().to_lowercase(),
+            signup_copy: form.signup_copy.trim().into(),
+            check_inbox_copy: form.check_inbox_copy.trim().into(),
+            confirmed_copy: form.confirmed_copy.trim().into(),
+            unsubscribed_copy: form.unsubscribed_copy.trim().into(),
+        };
+        theme.validate()?;
+        Ok(theme)
+    }
+}
+
+pub async fn site_theme(
+    session: TypedSession,
+    State(pool): State<PgPool>,
+) -> Result<Html<String>, AppError> {
+    let flash_messages = session.get_flash_messages().await;
+    let theme = get_site_theme(&pool)
+        .await
+        .map_err(e500)?
+        .unwrap_or_default();
+
+    let template = SiteThemeTemplate {
+        flash_messages,
+        theme,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(name = "Save the site theme", skip_all, fields(user_id=%&*user_id))]
+pub async fn save_theme(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    session: TypedSession,
+    State(pool): State<PgPool>,
+    Form(form): Form<SiteThemeFormData>,
+) -> Result<Response, AppError> {
+    let theme = match SiteTheme::try_from(form) {
+        Ok(theme) => theme,
+        Err(e) => {
+            session.flash_error(e).await;
+            return Ok(see_other("/admin/public-pages"));
+        }
+    };
+
+    save_site_theme(&pool, &theme)
+        .await
+        .context("Failed to save the site theme")
+        .map_err(e500)?;
+    session.flash_info("Public pages updated").await;
+
+    Ok(see_other("/admin/public-pages"))
+}
@@ -0,0 +1,33 @@
+use askama::Template;
+use axum::extract::{Query, State};
+use axum::response::Html;
+use sqlx::PgPool;
+
+use crate::analytics::{list_subscriber_engagement, SCORED_ISSUES, SUBSCRIBERS_PER_PAGE};
+use crate::utils::{e500, AppError};
+use crate::web_templates::SubscribersTemplate;
+
+#[derive(serde::Deserialize)]
+pub struct Pagination {
+    /// Starts at 0.
+    #[serde(default)]
+    page: u32,
+}
+
+pub async fn subscribers(
+    State(pool): State<PgPool>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Html<String>, AppError> {
+    let page = i64::from(pagination.page);
+    let subscribers = list_subscriber_engagement(&pool, page)
+        .await
+        .map_err(e500)?;
+    let template = SubscribersTemplate {
+        has_next_page: subscribers.len() as i64 == SUBSCRIBERS_PER_PAGE,
+        subscribers,
+        page,
+        scored_issues: SCORED_ISSUES,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
@@ -0,0 +1,53 @@
+use anyhow::anyhow;
+use askama::Template;
+use axum::extract::{Path, Query, State};
+use axum::response::Html;
+use sqlx::PgPool;
+
+use crate::newsletter_issues::{get_archived_issue, list_archived_issues};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e404, e500, AppError};
+use crate::web_templates::{ArchiveIssueTemplate, ArchiveTemplate};
+
+#[derive(serde::Deserialize)]
+pub struct Pagination {
+    /// Starts at 0.
+    #[serde(default)]
+    page: u32,
+}
+
+/// The public list of past issues.
+pub async fn archive(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Html<String>, AppError> {
+    let page = i64::from(pagination.page);
+    let archive = list_archived_issues(&pool, page).await.map_err(e500)?;
+    let template = ArchiveTemplate {
+        entries: archive.entries,
+        page,
+        has_next_page: archive.has_next_page,
+        url: format!("{}/archive", base_url.0),
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// The public copy of a past issue.
+pub async fn archived_issue(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(slug): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let issue = get_archived_issue(&pool, &slug)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404(anyhow!("No such issue in the archive")))?;
+    let template = ArchiveIssueTemplate {
+        url: format!("{}/archive/{}", base_url.0, issue.slug),
+        issue,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
@@ -0,0 +1,141 @@
+use anyhow::Context;
+use askama::Template;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::newsletter_issues::list_feed_issues;
+use crate::rendering::email_body_fragment;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, AppError};
+use crate::web_templates::{AtomFeedTemplate, FeedEntry, RssFeedTemplate};
+
+/// The latest issues as feed entries, with their full content.
+async fn get_feed_entries(pool: &PgPool, base_url: &str) -> Result<Vec<FeedEntry>, AppError> {
+    list_feed_issues(pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|issue| {
+            Ok(FeedEntry {
+                content: email_body_fragment(&issue.html)
+                    .context("Failed to render an issue for the feed")
+                    .map_err(e500)?,
+                id: issue.issue_id,
+                title: issue.title,
+                url: format!("{}/archive/{}", base_url, issue.slug),
+                published_at: issue.published_at,
+                summary: issue.description,
+            })
+        })
+        .collect()
+}
+
+/// A strong validator for `body`: the start of its SHA-256 hash.
+fn entity_tag(body: &str) -> String {
+    format!(
+        "\"{}\"",
+        hex::encode(&Sha256::digest(body.as_bytes())[..16])
+    )
+}
+
+/// Whether an `If-None-Match` header value lists `etag`. Weak comparison, as
+/// a GET needs no more.
+fn if_none_match(header_value: &str, etag: &str) -> bool {
+    header_value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Serves a feed, or just `304 Not Modified` to readers that already have
+/// this version of it.
+fn conditional_response(headers: &HeaderMap, content_type: &'static str, body: String) -> Response {
+    let etag = entity_tag(&body);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| if_none_match(v, &etag));
+    // Readers may keep the feed, as long as they check it is still current.
+    let cache_control = (header::CACHE_CONTROL, "no-cache".to_owned());
+
+    if not_modified {
+        (
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), cache_control],
+        )
+            .into_response()
+    } else {
+        (
+            [
+                (header::CONTENT_TYPE, content_type.to_owned()),
+                (header::ETAG, etag),
+                cache_control,
+            ],
+            body,
+        )
+            .into_response()
+    }
+}
+
+/// The latest issues as an RSS 2.0 feed.
+#[tracing::instrument(name = "Serve the RSS feed", skip_all)]
+pub async fn rss_feed(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let template = RssFeedTemplate {
+        entries: get_feed_entries(&pool, &base_url.0).await?,
+        base_url: base_url.0,
+    };
+
+    Ok(conditional_response(
+        &headers,
+        "application/rss+xml; charset=utf-8",
+        template.render().unwrap(),
+    ))
+}
+
+/// The latest issues as an Atom feed.
+#[tracing::instrument(name = "Serve the Atom feed", skip_all)]
+pub async fn atom_feed(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let template = AtomFeedTemplate {
+        entries: get_feed_entries(&pool, &base_url.0).await?,
+        base_url: base_url.0,
+    };
+
+    Ok(conditional_response(
+        &headers,
+        "application/atom+xml; charset=utf-8",
+        template.render().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entity_tag, if_none_match};
+
+    #[test]
+    fn entity_tags_are_quoted_and_follow_the_content() {
+        let etag = entity_tag("<rss/>");
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(etag.len(), 34);
+        assert_ne!(etag, entity_tag("<feed/>"));
+    }
+
+    #[test]
+    fn if_none_match_accepts_lists_weak_tags_and_wildcards() {
+        let etag = entity_tag("<rss/>");
+        assert!(if_none_match(&etag, &etag));
+        assert!(if_none_match(&format!("\"other\", W/{}", etag), &etag));
+        assert!(if_none_match("*", &etag));
+        assert!(!if_none_match("\"other\"", &etag));
+    }
+}
@@ -1,5 +1,114 @@
-use axum::response::Html;
+use askama::Template;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum_extra::extract::Query;
+use sqlx::PgPool;
 
-pub async fn home() -> Html<&'static str> {
-    Html(include_str!("home.html"))
+use crate::signup_protection::SignupGuard;
+use crate::site_theme::load_site_theme;
+use crate::startup::ApplicationBaseUrl;
+use crate::web_templates::{PublicPage, PublicPageTemplate};
+
+/// What the signup form sends back to the signup page when a signup fails.
+#[derive(serde::Deserialize)]
+pub struct SignupPageQuery {
+    #[serde(default)]
+    error: Option<String>,
+    /// The fields that were not valid.
+    #[serde(default)]
+    field: Vec<String>,
+}
+
+impl SignupPageQuery {
+    fn error_message(&self) -> Option<String> {
+        match self.error.as_deref()? {
+            "validation_failed" => {
+                let fields: Vec<_> = self
+                    .field
+                    .iter()
+                    .filter_map(|field| match field.as_str() {
+                        "name" => Some("name"),
+                        "email" => Some("email address"),
+                        "timezone" => Some("time zone"),
+                        _ => None,
+                    })
+                    .collect();
+                if fields.is_empty() {
+                    Some("Please check your details".into())
+                } else {
+                    Some(format!("Please check your {}", fields.join(" and ")))
+                }
+            }
+            "bot_check_failed" => {
+                Some("We could not tell that you are not a bot, please try again".into())
+            }
+            "rate_limited" => Some("Too many signups, please try again later".into()),
+            _ => Some("Something went wrong, please try again later".into()),
+        }
+    }
+}
+
+/// Renders a public page with the theme the admin picked.
+pub(crate) async fn public_page(
+    pool: &PgPool,
+    base_url: &str,
+    status: StatusCode,
+    page: PublicPage,
+) -> Response {
+    let template = PublicPageTemplate {
+        theme: load_site_theme(pool).await,
+        base_url: base_url.to_owned(),
+        page,
+    };
+
+    (status, Html(template.render().unwrap())).into_response()
+}
+
+/// The signup page.
+pub async fn home(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+    State(guard): State<SignupGuard>,
+    Query(query): Query<SignupPageQuery>,
+) -> Response {
+    let page = PublicPage::Signup {
+        error: query.error_message(),
+        challenge: guard.challenge(),
+    };
+    public_page(&pool, &base_url.0, StatusCode::OK, page).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignupPageQuery;
+    use claim::{assert_none, assert_some_eq};
+
+    fn query(error: Option<&str>, field: &[&str]) -> SignupPageQuery {
+        SignupPageQuery {
+            error: error.map(Into::into),
+            field: field.iter().map(|&f| f.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn no_error_no_message() {
+        assert_none!(query(None, &["email"]).error_message());
+    }
+
+    #[test]
+    fn invalid_fields_are_named() {
+        assert_some_eq!(
+            query(Some("validation_failed"), &["email", "name"]).error_message(),
+            "Please check your email address and name"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_not_echoed() {
+        assert_some_eq!(
+            query(Some("validation_failed"), &["<script>"]).error_message(),
+            "Please check your details"
+        );
+    }
 }
@@ -1,9 +1,10 @@
 use axum::extract::{Form, State};
-use axum::response::Redirect;
+use axum::response::{IntoResponse, Redirect, Response};
 use secrecy::Secret;
 use sqlx::PgPool;
 
 use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::rate_limit::{LimitedAction, RateLimiter};
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
 use crate::utils::e500;
@@ -30,7 +31,7 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(form, pool, rate_limiter, session),
     fields(
         username=tracing::field::Empty,
         user_id=tracing::field::Empty,
@@ -38,24 +39,38 @@ pub struct FormData {
 )]
 pub async fn login(
     State(pool): State<PgPool>,
+    State(rate_limiter): State<RateLimiter>,
     session: TypedSession,
     Form(form): Form<FormData>,
-) -> Result<Redirect, crate::utils::AppError> {
+) -> Result<Response, crate::utils::AppError> {
     let credentials = Credentials {
         username: form.username,
         password: form.password,
     };
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+
+    // Guessing the password of one account from many addresses is still
+    // guessing.
+    if let Err(limited) = rate_limiter
+        .check(
+            LimitedAction::LoginAsUser,
+            &credentials.username.to_lowercase(),
+        )
+        .await
+    {
+        tracing::warn!("Rate limited logins for a username");
+        return Ok(limited.into_response());
+    }
 
     match validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
 
             session.renew().await.map_err(e500)?;
             session.insert_user_id(user_id).await.map_err(e500)?;
 
-            Ok(Redirect::to("/admin/dashboard"))
+            Ok(Redirect::to("/admin/dashboard").into_response())
         }
         Err(e) => {
             let e = match e {
@@ -65,7 +80,7 @@ pub async fn login(
 
             session.flash_error(e.to_string()).await;
 
-            Ok(Redirect::to("/login"))
+            Ok(Redirect::to("/login").into_response())
         }
     }
 }
@@ -1,16 +1,36 @@
 mod admin;
+mod archive;
+mod feeds;
 mod health_check;
 mod home;
 mod login;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_embed;
+mod subscriptions_keep;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod tracking;
 
 pub use admin::{
-    admin_dashboard, change_password, change_password_form, get_username, log_out,
-    newsletters_form, publish_newsletter,
+    add_feed_source, admin_dashboard, analytics, autosave_issue, cancel_issue,
+    cancel_scheduled_send, change_password, change_password_form, dead_letters,
+    duplicate_subscribers, email_policy, export_analytics, feed_source, feed_sources, get_username,
+    issue_editor, issue_revision, issue_revisions, issues_list, log_out, manage_allowed_origins,
+    manage_blocked_domains, manage_dead_letters, merge_duplicates, new_issue, newsletters_form,
+    pause_issue, preview_newsletter, publish_newsletter, remove_feed_source, restore_revision,
+    resume_issue, save_feed_source, save_issue, save_theme, schedule_send, send_issue,
+    send_test_issue, set_archive_visibility, signup_form, site_theme, subscribers,
 };
+pub use archive::{archive, archived_issue};
+pub use feeds::{atom_feed, rss_feed};
 pub use health_check::health_check;
 pub use home::home;
 pub use login::{login, login_form};
 pub use subscriptions::{error_chain_fmt, subscribe};
-pub use subscriptions_confirm::confirm;
+pub use subscriptions_confirm::{check_inbox, confirm};
+pub use subscriptions_embed::{signup_challenge, signup_embed, signup_script};
+pub use subscriptions_keep::keep_subscription;
+pub use subscriptions_preferences::{preferences, save_preferences};
+pub use subscriptions_unsubscribe::unsubscribe_subscriber;
+pub use tracking::{track_click, track_open};
@@ -1,40 +1,247 @@
+use std::collections::BTreeMap;
+
 use anyhow::Context;
 use askama::Template;
-use axum::extract::{Form, State};
-use axum::http::StatusCode;
+use axum::extract::{Form, FromRequest, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::Url;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    allowed_origins::allowed_redirect,
+    domain::{EmailPolicy, NewSubscriber, SubscriberEmail, SubscriberName, SubscriberTimezone},
     email_client::EmailClient,
+    email_policy::EmailPolicyLoader,
     email_templates::{
         AlreadySubscribedEmailHtml, AlreadySubscribedEmailText, ConfirmationEmailHtml,
         ConfirmationEmailText,
     },
+    rate_limit::{LimitedAction, RateLimited, RateLimiter},
+    rendering::prepare_email_html,
+    signup_protection::{SignupCheckError, SignupGuard, SignupProof},
     startup::ApplicationBaseUrl,
+    utils::see_other,
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     email: String,
     name: String,
+    #[serde(default)]
+    timezone: Option<String>,
+    /// Where plain HTML forms on allowed sites send subscribers after
+    /// signing up, instead of answering them here.
+    #[serde(default)]
+    success_url: Option<String>,
+    /// Where they send subscribers when the signup fails, with the reason in
+    /// the `error` query parameter.
+    #[serde(default)]
+    error_url: Option<String>,
+    /// The honeypot: hidden from people, so only bots fill it in.
+    #[serde(default)]
+    website: String,
+    #[serde(default)]
+    form_token: Option<String>,
+    #[serde(default)]
+    proof_of_work: Option<String>,
+    /// CAPTCHA widgets send it under their own names.
+    #[serde(default, alias = "h-captcha-response", alias = "cf-turnstile-response")]
+    captcha_response: Option<String>,
+}
+
+impl FormData {
+    fn take_proof(&mut self) -> SignupProof {
+        SignupProof {
+            form_token: self.form_token.take(),
+            proof_of_work: self.proof_of_work.take(),
+            captcha_response: self.captcha_response.take(),
+        }
+    }
+}
+
+/// The same fields as [`FormData`], all optional so that JSON clients are told
+/// about missing fields along with the invalid ones.
+#[derive(serde::Deserialize)]
+struct JsonData {
+    email: Option<String>,
+    name: Option<String>,
+    timezone: Option<String>,
+    website: Option<String>,
+    form_token: Option<String>,
+    proof_of_work: Option<String>,
+    captcha_response: Option<String>,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+impl From<JsonData> for FormData {
+    fn from(data: JsonData) -> Self {
+        Self {
+            email: data.email.unwrap_or_default(),
+            name: data.name.unwrap_or_default(),
+            timezone: data.timezone,
+            success_url: None,
+            error_url: None,
+            website: data.website.unwrap_or_default(),
+            form_token: data.form_token,
+            proof_of_work: data.proof_of_work,
+            captcha_response: data.captcha_response,
+        }
+    }
+}
 
-    fn try_from(form: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(form.name)?;
-        let email = SubscriberEmail::parse(form.email)?;
+/// What is wrong with each field of a signup.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct FieldErrors(BTreeMap<&'static str, String>);
+
+impl FieldErrors {
+    /// Parses the value of `field`, keeping what is wrong with it if anything.
+    fn check<T>(
+        &mut self,
+        field: &'static str,
+        value: String,
+        parse: impl FnOnce(String) -> Result<T, String>,
+    ) -> Option<T> {
+        if value.trim().is_empty() {
+            self.0.insert(field, format!("The {} is required", field));
+            return None;
+        }
+        match parse(value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.0.insert(field, e);
+                None
+            }
+        }
+    }
+}
 
-        Ok(NewSubscriber { email, name })
+impl std::fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages = self.0.values().map(String::as_str).collect::<Vec<_>>();
+        write!(f, "{}", messages.join("\n"))
     }
 }
 
+impl NewSubscriber {
+    /// Validates a signup. The email must also pass `policy`.
+    fn from_form(form: FormData, policy: &EmailPolicy) -> Result<Self, FieldErrors> {
+        let mut errors = FieldErrors::default();
+        let email = errors.check("email", form.email, |email| {
+            SubscriberEmail::parse_with_policy(email, policy)
+        });
+        let name = errors.check("name", form.name, SubscriberName::parse);
+        // The timezone is filled in by the signup form's script, so an
+        // unknown one is dropped rather than failing the signup.
+        let timezone = form
+            .timezone
+            .and_then(|timezone| SubscriberTimezone::parse(&timezone).ok());
+
+        match (email, name) {
+            (Some(email), Some(name)) => Ok(NewSubscriber {
+                email,
+                name,
+                timezone,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// How the client wants to be answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Text,
+    Json,
+}
+
+impl ResponseFormat {
+    /// JSON for clients that send JSON or accept it, plain text otherwise.
+    fn negotiate(headers: &HeaderMap) -> Self {
+        let mentions_json = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.contains("application/json") || v.contains("+json"))
+        };
+        if mentions_json(header::CONTENT_TYPE) || mentions_json(header::ACCEPT) {
+            Self::Json
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// A signup, posted by a form or as JSON.
+pub struct SubscribeRequest {
+    form: FormData,
+    format: ResponseFormat,
+}
+
+impl<S: Send + Sync> FromRequest<S> for SubscribeRequest {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = ResponseFormat::negotiate(request.headers());
+        let is_json = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+
+        let form = if is_json {
+            Json::<JsonData>::from_request(request, state)
+                .await
+                .map_err(|rejection| {
+                    json_error(
+                        rejection.status(),
+                        "invalid_body",
+                        &rejection.body_text(),
+                        None,
+                    )
+                })?
+                .0
+                .into()
+        } else {
+            Form::<FormData>::from_request(request, state)
+                .await
+                .map_err(|rejection| match format {
+                    ResponseFormat::Json => json_error(
+                        rejection.status(),
+                        "invalid_body",
+                        &rejection.body_text(),
+                        None,
+                    ),
+                    ResponseFormat::Text => rejection.into_response(),
+                })?
+                .0
+        };
+
+        Ok(Self { form, format })
+    }
+}
+
+/// An error for JSON clients: a stable code to branch on, a message for
+/// people and, for invalid signups, what is wrong with each field.
+fn json_error(
+    status: StatusCode,
+    code: &str,
+    message: &str,
+    fields: Option<&FieldErrors>,
+) -> Response {
+    let mut body = serde_json::json!({
+        "error": code,
+        "message": message,
+    });
+    if let Some(fields) = fields {
+        body["fields"] = serde_json::json!(fields);
+    }
+    (status, Json(body)).into_response()
+}
+
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter,
@@ -53,7 +260,13 @@ pub fn error_chain_fmt(
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
-    ValidationError(String),
+    ValidationError(FieldErrors),
+
+    #[error("{0}")]
+    BotCheckFailed(&'static str),
+
+    #[error("{0}")]
+    RateLimited(RateLimited),
 
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
@@ -68,7 +281,10 @@ impl std::fmt::Debug for SubscribeError {
 impl IntoResponse for SubscribeError {
     fn into_response(self) -> Response {
         let status = match self {
-            SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::ValidationError(_) | SubscribeError::BotCheckFailed(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscribeError::RateLimited(limited) => return limited.into_response(),
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -76,21 +292,190 @@ impl IntoResponse for SubscribeError {
     }
 }
 
+impl SubscribeError {
+    /// A stable code for clients to branch on.
+    fn code(&self) -> &'static str {
+        match self {
+            SubscribeError::ValidationError(_) => "validation_failed",
+            SubscribeError::BotCheckFailed(_) => "bot_check_failed",
+            SubscribeError::RateLimited(_) => "rate_limited",
+            SubscribeError::UnexpectedError(_) => "internal_error",
+        }
+    }
+
+    fn into_json_response(self) -> Response {
+        match &self {
+            SubscribeError::ValidationError(fields) => json_error(
+                StatusCode::BAD_REQUEST,
+                self.code(),
+                "Some fields are not valid",
+                Some(fields),
+            ),
+            SubscribeError::BotCheckFailed(message) => {
+                json_error(StatusCode::BAD_REQUEST, self.code(), message, None)
+            }
+            SubscribeError::RateLimited(limited) => limited.into_response(),
+            SubscribeError::UnexpectedError(_) => {
+                tracing::error!("{:?}", self);
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    self.code(),
+                    "Something went wrong, please try again later",
+                    None,
+                )
+            }
+        }
+    }
+}
+
+/// Where to send subscribers who signed up from a plain HTML form on another
+/// site.
+#[derive(Debug, Default)]
+struct SignupRedirects {
+    success_url: Option<Url>,
+    error_url: Option<Url>,
+}
+
+impl SignupRedirects {
+    /// The redirects the form asked for. They must lead to this site or an
+    /// allowed one.
+    async fn from_form(
+        pool: &PgPool,
+        base_url: &str,
+        form: &FormData,
+    ) -> Result<Self, SubscribeError> {
+        let mut redirects = Self::default();
+        let mut errors = FieldErrors::default();
+        for (field, url, redirect) in [
+            ("success_url", &form.success_url, &mut redirects.success_url),
+            ("error_url", &form.error_url, &mut redirects.error_url),
+        ] {
+            let Some(url) = url.as_deref().filter(|url| !url.trim().is_empty()) else {
+                continue;
+            };
+            *redirect = allowed_redirect(pool, base_url, url)
+                .await
+                .context("Failed to check a redirect URL")?;
+            if redirect.is_none() {
+                errors
+                    .0
+                    .insert(field, format!("{} is not on an allowed site", url));
+            }
+        }
+
+        if errors.0.is_empty() {
+            Ok(redirects)
+        } else {
+            Err(SubscribeError::ValidationError(errors))
+        }
+    }
+
+    /// Redirects to the page for `outcome`, if the form gave one.
+    fn respond(&self, outcome: &Result<(), SubscribeError>) -> Option<Response> {
+        match outcome {
+            Ok(()) => self.success_url.as_ref().map(|url| see_other(url.as_str())),
+            Err(e) => self.error_url.as_ref().map(|url| {
+                let mut url = url.clone();
+                url.query_pairs_mut().append_pair("error", e.code());
+                if let SubscribeError::ValidationError(fields) = e {
+                    for field in fields.0.keys() {
+                        url.query_pairs_mut().append_pair("field", field);
+                    }
+                }
+                see_other(url.as_str())
+            }),
+        }
+    }
+}
+
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(request, pool, email_client, base_url, rate_limiter, guard, email_policy),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_email = %request.form.email,
+        subscriber_name = %request.form.name
     )
 )]
 pub async fn subscribe(
     State(pool): State<PgPool>,
     State(email_client): State<EmailClient>,
     State(base_url): State<ApplicationBaseUrl>,
-    Form(form): Form<FormData>,
-) -> Result<Response, SubscribeError> {
-    let new_subscriber: NewSubscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    State(rate_limiter): State<RateLimiter>,
+    State(guard): State<SignupGuard>,
+    State(email_policy): State<EmailPolicyLoader>,
+    request: SubscribeRequest,
+) -> Response {
+    let SubscribeRequest { form, format } = request;
+    let outcome = match SignupRedirects::from_form(&pool, &base_url.0, &form).await {
+        Ok(redirects) => {
+            let outcome = add_subscriber(
+                &pool,
+                &email_client,
+                &rate_limiter,
+                &guard,
+                &email_policy,
+                &base_url.0,
+                form,
+            )
+            .await;
+            if let Some(response) = redirects.respond(&outcome) {
+                if let Err(e) = &outcome {
+                    tracing::warn!(error.message = %e, "Failed to add a subscriber");
+                }
+                return response;
+            }
+            outcome
+        }
+        Err(e) => Err(e),
+    };
+
+    match (outcome, format) {
+        (Ok(()), ResponseFormat::Text) => StatusCode::OK.into_response(),
+        (Ok(()), ResponseFormat::Json) => Json(serde_json::json!({
+            "message": "Check your inbox to confirm your subscription",
+        }))
+        .into_response(),
+        (Err(e), ResponseFormat::Text) => e.into_response(),
+        (Err(e), ResponseFormat::Json) => e.into_json_response(),
+    }
+}
+
+/// Stores the signup and emails the subscriber the link to confirm it, or
+/// tells them they are already on the list.
+async fn add_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    rate_limiter: &RateLimiter,
+    guard: &SignupGuard,
+    email_policy: &EmailPolicyLoader,
+    base_url: &str,
+    mut form: FormData,
+) -> Result<(), SubscribeError> {
+    if !form.website.is_empty() {
+        // Bots are told they made it, so that they do not try harder.
+        tracing::info!("Dropped a signup that filled in the honeypot");
+        return Ok(());
+    }
+    let proof = form.take_proof();
+    let policy = email_policy
+        .load(pool)
+        .await
+        .context("Failed to load the email policy")?;
+    let new_subscriber =
+        NewSubscriber::from_form(form, &policy).map_err(SubscribeError::ValidationError)?;
+    guard.check(&proof).await.map_err(|e| match e {
+        SignupCheckError::Rejected(message) => SubscribeError::BotCheckFailed(message),
+        SignupCheckError::UnexpectedError(e) => SubscribeError::UnexpectedError(e),
+    })?;
+    // Every signup sends an email, which must not become a way to flood an
+    // inbox.
+    rate_limiter
+        .check(
+            LimitedAction::SubscribeEmail,
+            new_subscriber.email.normalized(),
+        )
+        .await
+        .map_err(SubscribeError::RateLimited)?;
 
     let mut transaction = pool
         .begin()
@@ -98,10 +483,50 @@ pub async fn subscribe(
         .context("Failed to acquire a Postgres connection from the pool")?;
 
     // Check if subscriber already exists
-    let existing_subscriber = get_subscriber_by_email(&mut transaction, &new_subscriber.email)
+    let mut existing_subscriber = get_subscriber_by_email(&mut transaction, &new_subscriber.email)
         .await
         .context("Failed to check for existing subscriber")?;
 
+    if existing_subscriber.is_none() {
+        // New subscriber - proceed with insertion
+        let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+            .await
+            .context("Failed to insert new subcriber in the database")?;
+
+        match subscriber_id {
+            Some(subscriber_id) => {
+                let subscription_token = generate_subscription_token();
+
+                store_token(&mut transaction, subscriber_id, &subscription_token)
+                    .await
+                    .context("Failed to store the confirmation token for a new subscriber")?;
+
+                transaction
+                    .commit()
+                    .await
+                    .context("Failed to commit SQL transaction to store a new subscriber")?;
+
+                send_confirmation_email(
+                    email_client,
+                    new_subscriber,
+                    base_url,
+                    &subscription_token,
+                )
+                .await
+                .context("Failed to send a confirmation email")?;
+
+                return Ok(());
+            }
+            // A concurrent signup for the same address got there first.
+            None => {
+                existing_subscriber =
+                    get_subscriber_by_email(&mut transaction, &new_subscriber.email)
+                        .await
+                        .context("Failed to check for existing subscriber")?;
+            }
+        }
+    }
+
     match existing_subscriber {
         Some((_subscriber_id, status)) if status == "confirmed" => {
             // Already subscribed - send a friendly email
@@ -110,11 +535,11 @@ pub async fn subscribe(
                 .await
                 .context("Failed to commit SQL transaction")?;
 
-            send_already_subscribed_email(&email_client, &new_subscriber)
+            send_already_subscribed_email(email_client, &new_subscriber, base_url)
                 .await
                 .context("Failed to send already-subscribed email")?;
 
-            return Ok(StatusCode::OK.into_response());
+            Ok(())
         }
         Some((subscriber_id, _)) => {
             // Pending confirmation - generate new token and resend
@@ -129,45 +554,16 @@ pub async fn subscribe(
                 .await
                 .context("Failed to commit SQL transaction to store token")?;
 
-            send_confirmation_email(
-                &email_client,
-                new_subscriber,
-                &base_url.0,
-                &subscription_token,
-            )
-            .await
-            .context("Failed to send a confirmation email")?;
-
-            return Ok(StatusCode::OK.into_response());
-        }
-        None => {
-            // New subscriber - proceed with insertion
-            let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+            send_confirmation_email(email_client, new_subscriber, base_url, &subscription_token)
                 .await
-                .context("Failed to insert new subcriber in the database")?;
+                .context("Failed to send a confirmation email")?;
 
-            let subscription_token = generate_subscription_token();
-
-            store_token(&mut transaction, subscriber_id, &subscription_token)
-                .await
-                .context("Failed to store the confirmation token for a new subscriber")?;
-
-            transaction
-                .commit()
-                .await
-                .context("Failed to commit SQL transaction to store a new subscriber")?;
-
-            send_confirmation_email(
-                &email_client,
-                new_subscriber,
-                &base_url.0,
-                &subscription_token,
-            )
-            .await
-            .context("Failed to send a confirmation email")?;
-
-            Ok(StatusCode::OK.into_response())
+            Ok(())
         }
+        None => Err(anyhow::anyhow!(
+            "The subscriber that conflicted with a new signup no longer exists"
+        )
+        .into()),
     }
 }
 
@@ -180,9 +576,9 @@ pub async fn get_subscriber_by_email(
         r#"
         SELECT id, status
         FROM subscriptions
-        WHERE email = $1
+        WHERE normalized_email = $1 AND duplicate_of IS NULL
         "#,
-        email.as_ref(),
+        email.normalized(),
     )
     .fetch_optional(transaction.as_mut())
     .await
@@ -194,6 +590,8 @@ pub async fn get_subscriber_by_email(
     Ok(result.map(|row| (row.id, row.status)))
 }
 
+/// Returns `None` if another subscriber already has the same normalized
+/// address, e.g. one that signed up at the same time.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
@@ -201,27 +599,29 @@ pub async fn get_subscriber_by_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-
-    sqlx::query!(
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (
+            id, email, normalized_email, name, subscribed_at, status, timezone
+        )
+        VALUES ($1, $2, $3, $4, $5, 'pending_confirmation', $6)
+        ON CONFLICT (normalized_email) WHERE duplicate_of IS NULL DO NOTHING
+        RETURNING id
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
         new_subscriber.email.as_ref(),
+        new_subscriber.email.normalized(),
         new_subscriber.name.as_ref(),
         Utc::now(),
+        new_subscriber.timezone.as_ref().map(AsRef::as_ref),
     )
-    .execute(transaction.as_mut())
+    .fetch_optional(transaction.as_mut())
     .await
     .map_err(|e| {
         tracing::info!("Failed to execute query: {:?}", e);
         e
-    })?;
-
-    Ok(subscriber_id)
+    })
 }
 
 #[tracing::instrument(
@@ -260,7 +660,7 @@ pub async fn send_confirmation_email(
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token,
@@ -279,6 +679,12 @@ pub async fn send_confirmation_email(
     let html_body = html_template
         .render()
         .expect("Failed to render HTML email template");
+    let html_body = prepare_email_html(
+        &html_body,
+        base_url,
+        Some("One click to confirm your subscription"),
+    )
+    .context("Failed to prepare the confirmation email")?;
     let plain_body = text_template
         .render()
         .expect("Failed to render text email template");
@@ -290,14 +696,19 @@ pub async fn send_confirmation_email(
             &html_body,
             &plain_body,
         )
-        .await
+        .await?;
+    Ok(())
 }
 
-#[tracing::instrument(name = "Send already-subscribed email", skip(email_client, subscriber))]
+#[tracing::instrument(
+    name = "Send already-subscribed email",
+    skip(email_client, subscriber, base_url)
+)]
 pub async fn send_already_subscribed_email(
     email_client: &EmailClient,
     subscriber: &NewSubscriber,
-) -> Result<(), reqwest::Error> {
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
     let html_template = AlreadySubscribedEmailHtml {
         subscriber_name: subscriber.name.as_ref().to_string(),
     };
@@ -309,6 +720,8 @@ pub async fn send_already_subscribed_email(
     let html_body = html_template
         .render()
         .expect("Failed to render HTML email template");
+    let html_body = prepare_email_html(&html_body, base_url, Some("You're already on the list"))
+        .context("Failed to prepare the already-subscribed email")?;
     let plain_body = text_template
         .render()
         .expect("Failed to render text email template");
@@ -320,7 +733,8 @@ pub async fn send_already_subscribed_email(
             &html_body,
             &plain_body,
         )
-        .await
+        .await?;
+    Ok(())
 }
 
 fn generate_subscription_token() -> String {
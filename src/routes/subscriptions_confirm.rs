@@ -1,64 +1,75 @@
 use axum::extract::{Query, State};
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::Response;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::domain::SubscriptionToken;
+use crate::routes::home::public_page;
+use crate::startup::ApplicationBaseUrl;
+use crate::web_templates::PublicPage;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     pub subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
+/// Where the confirmation email sends new subscribers, when the signup form
+/// redirected them there.
+pub async fn check_inbox(
+    State(pool): State<PgPool>,
+    State(base_url): State<ApplicationBaseUrl>,
+) -> Response {
+    public_page(&pool, &base_url.0, StatusCode::OK, PublicPage::CheckInbox).await
+}
+
+const CONFIRMATION_FAILED: PublicPage = PublicPage::Notice {
+    title: "Something went wrong",
+    message: "We could not confirm your subscription. Please try again later.",
+};
+
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, base_url)
+)]
 pub async fn confirm(
     Query(parameters): Query<Parameters>,
     State(pool): State<PgPool>,
-) -> impl IntoResponse {
+    State(base_url): State<ApplicationBaseUrl>,
+) -> Response {
+    let (status, page) = confirm_token(&pool, &parameters.subscription_token).await;
+    public_page(&pool, &base_url.0, status, page).await
+}
+
+async fn confirm_token(pool: &PgPool, subscription_token: &str) -> (StatusCode, PublicPage) {
     // Validate token format before querying database
-    let token = match SubscriptionToken::parse(parameters.subscription_token.clone()) {
+    let token = match SubscriptionToken::parse(subscription_token.to_owned()) {
         Ok(token) => token,
         Err(e) => {
             tracing::warn!("Invalid token format: {}", e);
-            return (
-                StatusCode::BAD_REQUEST,
-                "Invalid confirmation token format. The token must be 25 alphanumeric characters.",
-            );
+            return (StatusCode::BAD_REQUEST, PublicPage::InvalidToken);
         }
     };
 
-    let id = match get_subscriber_id_from_token(&pool, token.as_ref()).await {
+    let id = match get_subscriber_id_from_token(pool, token.as_ref()).await {
         Ok(id) => id,
         Err(e) => {
             tracing::error!("Failed to get subscriber ID from token: {:?}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to confirm subscription. Please try again later.",
-            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, CONFIRMATION_FAILED);
         }
     };
 
     match id {
         None => {
             // Token doesn't exist or is invalid
-            tracing::warn!(
-                "Non-existent confirmation token: {}",
-                parameters.subscription_token
-            );
-            return (
-                StatusCode::BAD_REQUEST,
-                "Invalid confirmation token. The token may have expired or does not exist.",
-            );
+            tracing::warn!("Non-existent confirmation token: {}", subscription_token);
+            (StatusCode::BAD_REQUEST, PublicPage::InvalidToken)
         }
-        Some(subscriber_id) => match confirm_subscriber(&pool, subscriber_id).await {
-            Ok(_) => (StatusCode::OK, "Your subscription has been confirmed!"),
+        Some(subscriber_id) => match confirm_subscriber(pool, subscriber_id).await {
+            Ok(_) => (StatusCode::OK, PublicPage::Confirmed),
             Err(e) => {
                 tracing::error!("Failed to confirm subscriber {}: {:?}", subscriber_id, e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to confirm subscription. Please try again later.",
-                )
+                (StatusCode::INTERNAL_SERVER_ERROR, CONFIRMATION_FAILED)
             }
         },
     }
@@ -99,21 +110,35 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
             Ok(())
         }
         Some(_) => {
-            // Pending confirmation - update to confirmed
+            // Pending confirmation - update to confirmed, and retire the
+            // subscriber's links so that none of them can sign them back up
+            // after they leave the list.
+            let mut transaction = pool.begin().await?;
             sqlx::query!(
                 r#"
                 UPDATE subscriptions
-                SET status = 'confirmed'
+                SET status = 'confirmed', unsubscribed_at = NULL
                 WHERE id = $1
                 "#,
                 subscriber_id,
             )
-            .execute(pool)
+            .execute(transaction.as_mut())
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query {:?}", e);
+                e
+            })?;
+            sqlx::query!(
+                "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+                subscriber_id,
+            )
+            .execute(transaction.as_mut())
             .await
             .map_err(|e| {
                 tracing::error!("Failed to execute query {:?}", e);
                 e
             })?;
+            transaction.commit().await?;
 
             Ok(())
         }
@@ -0,0 +1,68 @@
+use askama::Template;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+
+use crate::signup_protection::SignupGuard;
+use crate::startup::ApplicationBaseUrl;
+use crate::web_templates::{SignupEmbedTemplate, SignupScriptTemplate};
+
+/// A bare signup form, for other sites to show in an iframe.
+pub async fn signup_embed(State(guard): State<SignupGuard>) -> Html<String> {
+    let template = SignupEmbedTemplate {
+        challenge: guard.challenge(),
+    };
+
+    Html(template.render().unwrap())
+}
+
+/// What a JSON client needs to sign someone up when signup protection is on:
+/// a form token, the proof of work to find for it and the CAPTCHA to show.
+pub async fn signup_challenge(State(guard): State<SignupGuard>) -> Response {
+    let challenge = guard.challenge();
+    let body = serde_json::json!({
+        "form_token": challenge.form_token,
+        "proof_of_work_bits": challenge.proof_of_work_bits,
+        "captcha": challenge.captcha.map(|captcha| serde_json::json!({
+            "script_url": captcha.provider.script_url(),
+            "widget_class": captcha.provider.widget_class(),
+            "response_field": captcha.provider.response_field(),
+            "site_key": captcha.site_key,
+        })),
+    });
+
+    ([(header::CACHE_CONTROL, "no-store")], Json(body)).into_response()
+}
+
+/// The script that puts a signup form on another site.
+pub async fn signup_script(
+    State(base_url): State<ApplicationBaseUrl>,
+    State(guard): State<SignupGuard>,
+) -> Response {
+    let challenge = guard.challenge();
+    let config = serde_json::json!({
+        "endpoint": format!("{}/subscriptions", base_url.0),
+        "formToken": challenge.form_token,
+        "proofOfWorkBits": challenge.proof_of_work_bits,
+        "captcha": challenge.captcha.map(|captcha| serde_json::json!({
+            "scriptUrl": captcha.provider.script_url(),
+            "widgetClass": captcha.provider.widget_class(),
+            "responseField": captcha.provider.response_field(),
+            "siteKey": captcha.site_key,
+        })),
+    });
+    let template = SignupScriptTemplate {
+        config_json: config.to_string(),
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            // Every copy carries a fresh form token.
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        template.render().unwrap(),
+    )
+        .into_response()
+}
@@ -0,0 +1,55 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use sqlx::PgPool;
+
+use crate::routes::home::public_page;
+use crate::startup::ApplicationBaseUrl;
+use crate::sunset::keep_subscribed;
+use crate::tracking::Tracker;
+use crate::web_templates::PublicPage;
+
+/// Where the link of a re-engagement email leads: keeps the subscriber on the
+/// list, or puts them back on it if the sunset policy already took them off.
+#[tracing::instrument(name = "Keep a subscriber on the list", skip_all)]
+pub async fn keep_subscription(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(token): Path<String>,
+) -> Response {
+    let (status, page) = match tracker.parse_keep_token(&token) {
+        None => {
+            tracing::warn!("Invalid keep-subscribed token");
+            (StatusCode::BAD_REQUEST, PublicPage::InvalidToken)
+        }
+        Some(subscriber_id) => match keep_subscribed(&pool, subscriber_id).await {
+            Ok(true) => (
+                StatusCode::OK,
+                PublicPage::Notice {
+                    title: "Thanks for staying!",
+                    message: "You'll keep receiving our newsletter.",
+                },
+            ),
+            Ok(false) => (
+                StatusCode::BAD_REQUEST,
+                PublicPage::Notice {
+                    title: "This subscription no longer exists",
+                    message: "You're welcome to sign up again.",
+                },
+            ),
+            Err(e) => {
+                tracing::error!("Failed to keep subscriber {}: {:?}", subscriber_id, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    PublicPage::Notice {
+                        title: "Something went wrong",
+                        message: "Failed to update your subscription. Please try again later.",
+                    },
+                )
+            }
+        },
+    };
+
+    public_page(&pool, &base_url.0, status, page).await
+}
@@ -0,0 +1,107 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum_extra::extract::Form;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriberTimezone;
+use crate::routes::home::public_page;
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_preferences::{get_timezone, set_timezone};
+use crate::tracking::Tracker;
+use crate::web_templates::PublicPage;
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesFormData {
+    timezone: String,
+}
+
+const NO_SUBSCRIPTION: PublicPage = PublicPage::Notice {
+    title: "This subscription no longer exists",
+    message: "You're welcome to sign up again.",
+};
+
+const UPDATE_FAILED: PublicPage = PublicPage::Notice {
+    title: "Something went wrong",
+    message: "Failed to update your subscription. Please try again later.",
+};
+
+/// Where the preferences link at the end of every issue leads.
+#[tracing::instrument(name = "Show subscriber preferences", skip_all)]
+pub async fn preferences(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(token): Path<String>,
+) -> Response {
+    let (status, page) = match tracker.parse_preferences_token(&token) {
+        None => {
+            tracing::warn!("Invalid preferences token");
+            (StatusCode::BAD_REQUEST, PublicPage::InvalidToken)
+        }
+        Some(subscriber_id) => preferences_page(&pool, subscriber_id, StatusCode::OK, None).await,
+    };
+
+    public_page(&pool, &base_url.0, status, page).await
+}
+
+#[tracing::instrument(name = "Save subscriber preferences", skip_all)]
+pub async fn save_preferences(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(token): Path<String>,
+    Form(form): Form<PreferencesFormData>,
+) -> Response {
+    let (status, page) = match tracker.parse_preferences_token(&token) {
+        None => {
+            tracing::warn!("Invalid preferences token");
+            (StatusCode::BAD_REQUEST, PublicPage::InvalidToken)
+        }
+        Some(subscriber_id) => match SubscriberTimezone::parse(&form.timezone) {
+            Err(e) => {
+                tracing::warn!("Invalid timezone: {}", e);
+                let notice = Some("Please pick a timezone from the list.");
+                preferences_page(&pool, subscriber_id, StatusCode::BAD_REQUEST, notice).await
+            }
+            Ok(timezone) => match set_timezone(&pool, subscriber_id, timezone).await {
+                Ok(true) => {
+                    let notice = Some("Your timezone was saved.");
+                    preferences_page(&pool, subscriber_id, StatusCode::OK, notice).await
+                }
+                Ok(false) => (StatusCode::BAD_REQUEST, NO_SUBSCRIPTION),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to save the timezone of subscriber {}: {:?}",
+                        subscriber_id,
+                        e
+                    );
+                    (StatusCode::INTERNAL_SERVER_ERROR, UPDATE_FAILED)
+                }
+            },
+        },
+    };
+
+    public_page(&pool, &base_url.0, status, page).await
+}
+
+async fn preferences_page(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    status: StatusCode,
+    notice: Option<&'static str>,
+) -> (StatusCode, PublicPage) {
+    match get_timezone(pool, subscriber_id).await {
+        Ok(Some(timezone)) => (status, PublicPage::preferences(timezone.as_deref(), notice)),
+        Ok(None) => (StatusCode::BAD_REQUEST, NO_SUBSCRIPTION),
+        Err(e) => {
+            tracing::error!(
+                "Failed to load the timezone of subscriber {}: {:?}",
+                subscriber_id,
+                e
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, UPDATE_FAILED)
+        }
+    }
+}
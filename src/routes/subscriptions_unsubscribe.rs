@@ -0,0 +1,53 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use sqlx::PgPool;
+
+use crate::routes::home::public_page;
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_preferences::unsubscribe;
+use crate::tracking::Tracker;
+use crate::web_templates::PublicPage;
+
+/// Where the unsubscribe link at the end of every issue leads. Mail clients
+/// POST to it for one-click unsubscribes.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip_all)]
+pub async fn unsubscribe_subscriber(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Path(token): Path<String>,
+) -> Response {
+    let (status, page) = match tracker.parse_unsubscribe_token(&token) {
+        None => {
+            tracing::warn!("Invalid unsubscribe token");
+            (StatusCode::BAD_REQUEST, PublicPage::InvalidToken)
+        }
+        Some(subscriber_id) => match unsubscribe(&pool, subscriber_id).await {
+            Ok(true) => (StatusCode::OK, PublicPage::Unsubscribed),
+            Ok(false) => (
+                StatusCode::BAD_REQUEST,
+                PublicPage::Notice {
+                    title: "This subscription no longer exists",
+                    message: "You're welcome to sign up again.",
+                },
+            ),
+            Err(e) => {
+                tracing::error!(
+                    "Failed to unsubscribe subscriber {}: {:?}",
+                    subscriber_id,
+                    e
+                );
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    PublicPage::Notice {
+                        title: "Something went wrong",
+                        message: "Failed to update your subscription. Please try again later.",
+                    },
+                )
+            }
+        },
+    };
+
+    public_page(&pool, &base_url.0, status, page).await
+}
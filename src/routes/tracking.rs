@@ -0,0 +1,92 @@
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use sqlx::PgPool;
+
+use crate::rate_limit::RateLimiter;
+use crate::tracking::{record_click, record_open, Tracker};
+use crate::utils::{e400, AppError};
+
+/// A transparent 1x1 GIF.
+const PIXEL: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+}
+
+/// Serves the open tracking pixel of an issue and records the open.
+///
+/// The pixel is always served, so that a broken or forged token never shows up
+/// as a broken image in someone's inbox; only genuine tokens are recorded.
+#[tracing::instrument(name = "Track an open", skip_all)]
+pub async fn track_open(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(rate_limiter): State<RateLimiter>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match tracker.parse_open_token(&token) {
+        Some(token) => {
+            let ip = rate_limiter.client_ip(peer.ip(), &headers).to_string();
+            if let Err(e) =
+                record_open(&pool, &tracker, token, user_agent(&headers), Some(&ip)).await
+            {
+                tracing::error!(error.cause_chain = ?e, "Failed to record an open");
+            }
+        }
+        None => tracing::warn!("Invalid open tracking token"),
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (header::CACHE_CONTROL, "no-store, max-age=0"),
+        ],
+        PIXEL,
+    )
+}
+
+/// Records a click on a tracked link and sends the reader on to its
+/// destination.
+///
+/// Only links we signed are followed, so the route cannot be used to redirect
+/// people to arbitrary sites.
+#[tracing::instrument(name = "Track a click", skip_all)]
+pub async fn track_click(
+    State(pool): State<PgPool>,
+    State(tracker): State<Tracker>,
+    State(rate_limiter): State<RateLimiter>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token = tracker
+        .parse_click_token(&token)
+        .ok_or_else(|| e400(anyhow!("Invalid link")))?;
+
+    // Losing a click is better than losing the reader.
+    let ip = rate_limiter.client_ip(peer.ip(), &headers).to_string();
+    if let Err(e) = record_click(&pool, &tracker, &token, user_agent(&headers), Some(&ip)).await {
+        tracing::error!(error.cause_chain = ?e, "Failed to record a click");
+    }
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, token.url),
+            (header::CACHE_CONTROL, "no-store".to_owned()),
+        ],
+    )
+        .into_response())
+}
@@ -0,0 +1,368 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::configuration::{CaptchaProvider, CaptchaSettings, SignupProtectionSettings};
+use crate::tracking::Tracker;
+
+const FORM_TOKEN_PURPOSE: &str = "signup-form";
+
+/// How long a served form can be sent, in minutes. Used tokens are
+/// remembered as long.
+const FORM_TOKEN_VALIDITY_MINUTES: i64 = 60;
+
+impl Tracker {
+    /// Records when a signup form was served. Random bytes tell apart the
+    /// forms served in the same millisecond, as each can only be sent once.
+    pub fn signup_form_token(&self, served_at: DateTime<Utc>) -> String {
+        let mut payload = served_at.timestamp_millis().to_be_bytes().to_vec();
+        payload.extend(rand::random::<[u8; 16]>());
+        self.sign(FORM_TOKEN_PURPOSE, &payload)
+    }
+
+    pub fn parse_signup_form_token(&self, token: &str) -> Option<DateTime<Utc>> {
+        let payload = self.verify(FORM_TOKEN_PURPOSE, token)?;
+        if payload.len() != 24 {
+            return None;
+        }
+        DateTime::from_timestamp_millis(i64::from_be_bytes(payload[..8].try_into().ok()?))
+    }
+}
+
+impl CaptchaProvider {
+    fn default_verify_url(self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+            CaptchaProvider::Turnstile => {
+                "https://challenges.cloudflare.com/turnstile/v0/siteverify"
+            }
+        }
+    }
+
+    pub fn script_url(self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "https://js.hcaptcha.com/1/api.js",
+            CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/api.js",
+        }
+    }
+
+    /// The class of the element the widget of the provider renders in.
+    pub fn widget_class(self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "h-captcha",
+            CaptchaProvider::Turnstile => "cf-turnstile",
+        }
+    }
+
+    /// The field the widget adds to the form with its answer.
+    pub fn response_field(self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "h-captcha-response",
+            CaptchaProvider::Turnstile => "cf-turnstile-response",
+        }
+    }
+}
+
+/// What a signup form served here needs to carry.
+#[derive(Debug)]
+pub struct SignupChallenge {
+    pub form_token: String,
+    /// 0 if no proof of work is needed.
+    pub proof_of_work_bits: u8,
+    pub captcha: Option<CaptchaWidget>,
+}
+
+#[derive(Debug)]
+pub struct CaptchaWidget {
+    pub provider: CaptchaProvider,
+    pub site_key: String,
+}
+
+/// What a signup sent to prove it comes from a person.
+#[derive(Debug, Default)]
+pub struct SignupProof {
+    pub form_token: Option<String>,
+    pub proof_of_work: Option<String>,
+    pub captcha_response: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SignupCheckError {
+    /// Looks like a bot. Tells people what to do about it.
+    #[error("{0}")]
+    Rejected(&'static str),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+const RELOAD: SignupCheckError = SignupCheckError::Rejected("Please reload the page and try again");
+
+/// Checks signups against the protections the configuration turns on.
+#[derive(Clone)]
+pub struct SignupGuard {
+    pool: PgPool,
+    settings: Arc<SignupProtectionSettings>,
+    tracker: Tracker,
+    http_client: reqwest::Client,
+}
+
+impl SignupGuard {
+    pub fn new(pool: PgPool, settings: SignupProtectionSettings, tracker: Tracker) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        Self {
+            pool,
+            settings: Arc::new(settings),
+            tracker,
+            http_client,
+        }
+    }
+
+    /// What a form served now needs to carry.
+    pub fn challenge(&self) -> SignupChallenge {
+        SignupChallenge {
+            form_token: self.tracker.signup_form_token(Utc::now()),
+            proof_of_work_bits: self.settings.proof_of_work_bits,
+            captcha: self.settings.captcha.as_ref().map(|captcha| CaptchaWidget {
+                provider: captcha.provider,
+                site_key: captcha.site_key.clone(),
+            }),
+        }
+    }
+
+    /// Every signup needs the token of a form served here, which it uses up
+    /// once the other checks pass. The proof of work is bound to that token,
+    /// so it cannot be sent again either.
+    #[tracing::instrument(skip_all)]
+    pub async fn check(&self, proof: &SignupProof) -> Result<(), SignupCheckError> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+
+        let token = proof
+            .form_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+            .ok_or(RELOAD)?;
+        let served_at = self.tracker.parse_signup_form_token(token).ok_or(RELOAD)?;
+        check_fill_time(served_at, Utc::now(), self.settings.min_fill_seconds)?;
+
+        let bits = self.settings.proof_of_work_bits;
+        if bits > 0 {
+            let nonce = proof
+                .proof_of_work
+                .as_deref()
+                .filter(|nonce| !nonce.is_empty())
+                .ok_or(SignupCheckError::Rejected(
+                    "Please turn on JavaScript to sign up",
+                ))?;
+            if !is_proof_of_work(token, nonce, bits) {
+                return Err(RELOAD);
+            }
+        }
+
+        if let Some(captcha) = &self.settings.captcha {
+            let response = proof
+                .captcha_response
+                .as_deref()
+                .filter(|response| !response.is_empty())
+                .ok_or(SignupCheckError::Rejected("Please complete the CAPTCHA"))?;
+            if !self.verify_captcha(captcha, response).await? {
+                return Err(SignupCheckError::Rejected("Please complete the CAPTCHA"));
+            }
+        }
+
+        // Last, so that people who failed another check can send the same
+        // form again.
+        if !use_form_token(&self.pool, token, served_at)
+            .await
+            .context("Failed to record a used form token")?
+        {
+            return Err(RELOAD);
+        }
+
+        Ok(())
+    }
+
+    /// hCaptcha and Turnstile check answers the same way.
+    async fn verify_captcha(
+        &self,
+        captcha: &CaptchaSettings,
+        response: &str,
+    ) -> Result<bool, anyhow::Error> {
+        #[derive(serde::Deserialize)]
+        struct Verification {
+            success: bool,
+        }
+
+        let url = captcha
+            .verify_url
+            .as_deref()
+            .unwrap_or(captcha.provider.default_verify_url());
+        let verification: Verification = self
+            .http_client
+            .post(url)
+            .form(&[
+                ("secret", captcha.secret_key.expose_secret().as_str()),
+                ("response", response),
+                ("sitekey", captcha.site_key.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("Failed to reach the CAPTCHA provider")?
+            .json()
+            .await
+            .context("Failed to read the answer of the CAPTCHA provider")?;
+
+        Ok(verification.success)
+    }
+}
+
+fn check_fill_time(
+    served_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    min_fill_seconds: u64,
+) -> Result<(), SignupCheckError> {
+    let elapsed = now - served_at;
+    if elapsed > chrono::Duration::minutes(FORM_TOKEN_VALIDITY_MINUTES) {
+        return Err(SignupCheckError::Rejected(
+            "The form expired, please reload the page",
+        ));
+    }
+    if elapsed < chrono::Duration::seconds(min_fill_seconds.try_into().unwrap_or(i64::MAX)) {
+        return Err(SignupCheckError::Rejected(
+            "Please take a moment to fill in the form",
+        ));
+    }
+    Ok(())
+}
+
+/// Records that `token` was used, unless it already was. Returns whether it
+/// was not.
+#[tracing::instrument(skip(pool, token))]
+async fn use_form_token(
+    pool: &PgPool,
+    token: &str,
+    served_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO used_signup_form_tokens (token_hash, served_at)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        hex::encode(Sha256::digest(token.as_bytes())),
+        served_at,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Forgets the used tokens that expired, which cannot be sent again anyway.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_expired_form_tokens(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - chrono::Duration::minutes(FORM_TOKEN_VALIDITY_MINUTES);
+    let result = sqlx::query!(
+        "DELETE FROM used_signup_form_tokens WHERE served_at < $1",
+        cutoff,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Whether the SHA-256 hash of `<form_token>:<nonce>` starts with `bits` zero
+/// bits.
+fn is_proof_of_work(form_token: &str, nonce: &str, bits: u8) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", form_token, nonce).as_bytes());
+    leading_zero_bits(&hash) >= u32::from(bits)
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_none, assert_ok, assert_some_eq};
+    use secrecy::Secret;
+
+    fn tracker() -> Tracker {
+        Tracker::new("http://localhost".into(), Secret::new("secret".into()))
+    }
+
+    #[test]
+    fn form_tokens_round_trip() {
+        let served_at = DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+        let token = tracker().signup_form_token(served_at);
+
+        assert_some_eq!(tracker().parse_signup_form_token(&token), served_at);
+    }
+
+    #[test]
+    fn forms_served_at_once_get_different_tokens() {
+        let served_at = Utc::now();
+
+        assert_ne!(
+            tracker().signup_form_token(served_at),
+            tracker().signup_form_token(served_at)
+        );
+    }
+
+    #[test]
+    fn keep_tokens_cannot_stand_in_for_form_tokens() {
+        let token = tracker().keep_subscribed_url(uuid::Uuid::new_v4());
+        let token = token.rsplit('/').next().unwrap();
+
+        assert_none!(tracker().parse_signup_form_token(token));
+    }
+
+    #[test]
+    fn forms_must_be_filled_in_between_the_minimum_and_an_hour() {
+        let now = Utc::now();
+        let ago = |seconds| now - chrono::Duration::seconds(seconds);
+
+        assert_err!(check_fill_time(ago(1), now, 3));
+        assert_ok!(check_fill_time(ago(3), now, 3));
+        assert_ok!(check_fill_time(ago(3600), now, 3));
+        assert_err!(check_fill_time(ago(3601), now, 3));
+    }
+
+    #[test]
+    fn leading_zero_bits_span_bytes() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn proofs_of_work_are_checked_against_their_token() {
+        let nonce = (0u32..)
+            .map(|n| n.to_string())
+            .find(|nonce| is_proof_of_work("token", nonce, 8))
+            .unwrap();
+
+        assert!(is_proof_of_work("token", &nonce, 8));
+        assert!(!is_proof_of_work("other-token", &nonce, 8));
+    }
+}
@@ -0,0 +1,219 @@
+use sqlx::PgPool;
+
+/// How long the newsletter name can be, in characters.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// How long each piece of copy can be, in characters.
+const MAX_COPY_LENGTH: usize = 2000;
+
+/// How the public pages look: the signup page and the pages subscribers land
+/// on from their emails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteTheme {
+    pub newsletter_name: String,
+    pub logo_url: Option<String>,
+    /// Colors are `#rrggbb`, which is what color inputs send and what keeps
+    /// them safe to put in a style sheet.
+    pub background_color: String,
+    pub text_color: String,
+    pub accent_color: String,
+    pub signup_copy: String,
+    pub check_inbox_copy: String,
+    pub confirmed_copy: String,
+    pub unsubscribed_copy: String,
+}
+
+impl Default for SiteTheme {
+    fn default() -> Self {
+        Self {
+            newsletter_name: "Our newsletter".into(),
+            logo_url: None,
+            background_color: "#ffffff".into(),
+            text_color: "#000000".into(),
+            accent_color: "#000000".into(),
+            signup_copy: "Get every new issue in your inbox.".into(),
+            check_inbox_copy: "We sent you an email. Follow the link in it to confirm your \
+                subscription."
+                .into(),
+            confirmed_copy: "Your subscription is confirmed. The next issue will be in your \
+                inbox."
+                .into(),
+            unsubscribed_copy: "You will not get any more emails from us. Sorry to see you go!"
+                .into(),
+        }
+    }
+}
+
+fn is_hex_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl SiteTheme {
+    /// Black or white, whichever reads better on the accent color.
+    pub fn accent_text_color(&self) -> &'static str {
+        let channel = |i: usize| {
+            u32::from_str_radix(self.accent_color.get(i..i + 2).unwrap_or("00"), 16).unwrap_or(0)
+        };
+        // Perceived brightness, from the W3C accessibility guidelines.
+        let brightness = (channel(1) * 299 + channel(3) * 587 + channel(5) * 114) / 1000;
+        if brightness > 150 {
+            "#000000"
+        } else {
+            "#ffffff"
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let name_length = self.newsletter_name.trim().chars().count();
+        if name_length == 0 || name_length > MAX_NAME_LENGTH {
+            return Err(format!(
+                "The newsletter name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            ));
+        }
+        if let Some(logo_url) = &self.logo_url {
+            match reqwest::Url::parse(logo_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => return Err("The logo URL must be an http or https URL".into()),
+            }
+        }
+        for (label, color) in [
+            ("background", &self.background_color),
+            ("text", &self.text_color),
+            ("accent", &self.accent_color),
+        ] {
+            if !is_hex_color(color) {
+                return Err(format!("The {} color must look like #1a2b3c", label));
+            }
+        }
+        for copy in [
+            &self.signup_copy,
+            &self.check_inbox_copy,
+            &self.confirmed_copy,
+            &self.unsubscribed_copy,
+        ] {
+            if copy.chars().count() > MAX_COPY_LENGTH {
+                return Err(format!(
+                    "Page copy can be at most {} characters",
+                    MAX_COPY_LENGTH
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The theme the admin saved, if they ever did.
+#[tracing::instrument(skip(pool))]
+pub async fn get_site_theme(pool: &PgPool) -> Result<Option<SiteTheme>, sqlx::Error> {
+    sqlx::query_as!(
+        SiteTheme,
+        r#"
+        SELECT newsletter_name, logo_url, background_color, text_color, accent_color,
+            signup_copy, check_inbox_copy, confirmed_copy, unsubscribed_copy
+        FROM site_theme
+        "#
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The theme to render public pages with. They still show, with the default
+/// theme, if it cannot be loaded.
+pub async fn load_site_theme(pool: &PgPool) -> SiteTheme {
+    match get_site_theme(pool).await {
+        Ok(theme) => theme.unwrap_or_default(),
+        Err(e) => {
+            tracing::error!(error.message = %e, "Failed to load the site theme");
+            SiteTheme::default()
+        }
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn save_site_theme(pool: &PgPool, theme: &SiteTheme) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO site_theme (
+            newsletter_name, logo_url, background_color, text_color, accent_color,
+            signup_copy, check_inbox_copy, confirmed_copy, unsubscribed_copy
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (id) DO UPDATE
+        SET newsletter_name = EXCLUDED.newsletter_name,
+            logo_url = EXCLUDED.logo_url,
+            background_color = EXCLUDED.background_color,
+            text_color = EXCLUDED.text_color,
+            accent_color = EXCLUDED.accent_color,
+            signup_copy = EXCLUDED.signup_copy,
+            check_inbox_copy = EXCLUDED.check_inbox_copy,
+            confirmed_copy = EXCLUDED.confirmed_copy,
+            unsubscribed_copy = EXCLUDED.unsubscribed_copy,
+            updated_at = NOW()
+        "#,
+        theme.newsletter_name,
+        theme.logo_url,
+        theme.background_color,
+        theme.text_color,
+        theme.accent_color,
+        theme.signup_copy,
+        theme.check_inbox_copy,
+        theme.confirmed_copy,
+        theme.unsubscribed_copy,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SiteTheme;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn the_default_theme_is_valid() {
+        assert_ok!(SiteTheme::default().validate());
+    }
+
+    #[test]
+    fn colors_must_be_hex_triplets() {
+        for color in ["#fff", "red", "#12345g", "#123456;}", "123456"] {
+            let theme = SiteTheme {
+                accent_color: color.into(),
+                ..Default::default()
+            };
+            assert_err!(theme.validate());
+        }
+    }
+
+    #[test]
+    fn buttons_stay_readable_on_any_accent_color() {
+        let theme = |accent_color: &str| SiteTheme {
+            accent_color: accent_color.into(),
+            ..Default::default()
+        };
+        assert_eq!(theme("#000000").accent_text_color(), "#ffffff");
+        assert_eq!(theme("#1d4ed8").accent_text_color(), "#ffffff");
+        assert_eq!(theme("#fde047").accent_text_color(), "#000000");
+    }
+
+    #[test]
+    fn logos_must_be_on_the_web() {
+        let theme = SiteTheme {
+            logo_url: Some("javascript:alert(1)".into()),
+            ..Default::default()
+        };
+        assert_err!(theme.validate());
+    }
+
+    #[test]
+    fn the_newsletter_needs_a_name() {
+        let theme = SiteTheme {
+            newsletter_name: "  ".into(),
+            ..Default::default()
+        };
+        assert_err!(theme.validate());
+    }
+}
@@ -1,3 +1,9 @@
+use std::net::SocketAddr;
+
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::extract::ConnectInfo;
+use axum::handler::Handler;
+use axum::middleware::AddExtension;
 use axum::routing::{get, post};
 use axum::{middleware, serve::Serve, Router};
 use secrecy::ExposeSecret;
@@ -17,17 +23,37 @@ use tower_sessions_redis_store::{
     RedisStore,
 };
 
+use crate::allowed_origins::signup_cors_layer;
 use crate::authentication::AuthenticatedUser;
 use crate::configuration::{DatabaseSettings, Settings};
 use crate::email_client::EmailClient;
+use crate::email_policy::EmailPolicyLoader;
+use crate::rate_limit::{self, LimitedAction, RateLimiter};
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, confirm, health_check, home, log_out,
-    login, login_form, newsletters_form, publish_newsletter, subscribe,
+    add_feed_source, admin_dashboard, analytics, archive, archived_issue, atom_feed,
+    autosave_issue, cancel_issue, cancel_scheduled_send, change_password, change_password_form,
+    check_inbox, confirm, dead_letters, duplicate_subscribers, email_policy, export_analytics,
+    feed_source, feed_sources, health_check, home, issue_editor, issue_revision, issue_revisions,
+    issues_list, keep_subscription, log_out, login, login_form, manage_allowed_origins,
+    manage_blocked_domains, manage_dead_letters, merge_duplicates, new_issue, newsletters_form,
+    pause_issue, preferences, preview_newsletter, publish_newsletter, remove_feed_source,
+    restore_revision, resume_issue, rss_feed, save_feed_source, save_issue, save_preferences,
+    save_theme, schedule_send, send_issue, send_test_issue, set_archive_visibility,
+    signup_challenge, signup_embed, signup_form, signup_script, site_theme, subscribe, subscribers,
+    track_click, track_open, unsubscribe_subscriber,
 };
+use crate::signup_protection::SignupGuard;
+use crate::tracking::Tracker;
+
+type Server = Serve<
+    TcpListener,
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
 
 pub struct Application {
     port: u16,
-    server: Serve<TcpListener, Router, Router>,
+    server: Server,
 }
 
 impl Application {
@@ -74,6 +100,14 @@ impl Application {
             db_pool: connection_pool.clone(),
             email_client: email_client.clone(),
             base_url: ApplicationBaseUrl(configuration.application.base_url.clone()),
+            tracker: configuration.application.tracker(),
+            rate_limiter: RateLimiter::new(connection_pool.clone(), configuration.rate_limit),
+            signup_guard: SignupGuard::new(
+                connection_pool.clone(),
+                configuration.signup_protection,
+                configuration.application.tracker(),
+            ),
+            email_policy: EmailPolicyLoader::new(configuration.email_policy)?,
         };
 
         let server = run(listener, state, session_layer)?;
@@ -86,9 +120,7 @@ impl Application {
     }
 
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server
-            .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        self.server.await.map_err(std::io::Error::other)
     }
 
     pub async fn run_with_graceful_shutdown(
@@ -98,7 +130,7 @@ impl Application {
         self.server
             .with_graceful_shutdown(shutdown_signal)
             .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .map_err(std::io::Error::other)
     }
 }
 
@@ -110,6 +142,10 @@ pub struct AppState {
     pub db_pool: PgPool,
     pub email_client: EmailClient,
     pub base_url: ApplicationBaseUrl,
+    pub tracker: Tracker,
+    pub rate_limiter: RateLimiter,
+    pub signup_guard: SignupGuard,
+    pub email_policy: EmailPolicyLoader,
 }
 
 impl axum::extract::FromRef<AppState> for PgPool {
@@ -130,15 +166,91 @@ impl axum::extract::FromRef<AppState> for ApplicationBaseUrl {
     }
 }
 
+impl axum::extract::FromRef<AppState> for Tracker {
+    fn from_ref(state: &AppState) -> Self {
+        state.tracker.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for RateLimiter {
+    fn from_ref(state: &AppState) -> Self {
+        state.rate_limiter.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for SignupGuard {
+    fn from_ref(state: &AppState) -> Self {
+        state.signup_guard.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for EmailPolicyLoader {
+    fn from_ref(state: &AppState) -> Self {
+        state.email_policy.clone()
+    }
+}
+
 fn build_router(
+    state: &AppState,
     session_layer: SessionManagerLayer<RedisStore<Pool>, PrivateCookie>,
 ) -> Router<AppState> {
+    let limit_by_ip = |action| {
+        middleware::from_fn_with_state(
+            (state.rate_limiter.clone(), action),
+            rate_limit::limit_by_ip,
+        )
+    };
+
     let admin_routes = Router::<AppState>::new()
         .route("/dashboard", get(admin_dashboard))
         .route(
             "/newsletters",
             get(newsletters_form).post(publish_newsletter),
         )
+        .route("/newsletters/preview", post(preview_newsletter))
+        .route("/issues", get(issues_list).post(new_issue))
+        .route("/issues/{issue_id}", get(issue_editor).post(save_issue))
+        .route("/issues/{issue_id}/autosave", post(autosave_issue))
+        .route("/issues/{issue_id}/send", post(send_issue))
+        .route("/issues/{issue_id}/test", post(send_test_issue))
+        .route("/issues/{issue_id}/pause", post(pause_issue))
+        .route("/issues/{issue_id}/resume", post(resume_issue))
+        .route("/issues/{issue_id}/cancel", post(cancel_issue))
+        .route("/issues/{issue_id}/schedule", post(schedule_send))
+        .route("/issues/{issue_id}/unschedule", post(cancel_scheduled_send))
+        .route("/issues/{issue_id}/archive", post(set_archive_visibility))
+        .route("/issues/{issue_id}/revisions", get(issue_revisions))
+        .route(
+            "/issues/{issue_id}/revisions/{revision_id}",
+            get(issue_revision),
+        )
+        .route(
+            "/issues/{issue_id}/revisions/{revision_id}/restore",
+            post(restore_revision),
+        )
+        .route("/dead-letters", get(dead_letters).post(manage_dead_letters))
+        .route("/analytics", get(analytics))
+        .route("/analytics/export/{report}", get(export_analytics))
+        .route("/subscribers", get(subscribers))
+        .route(
+            "/subscribers/duplicates",
+            get(duplicate_subscribers).post(merge_duplicates),
+        )
+        .route("/feeds", get(feed_sources).post(add_feed_source))
+        .route(
+            "/feeds/{feed_source_id}",
+            get(feed_source).post(save_feed_source),
+        )
+        .route("/feeds/{feed_source_id}/delete", post(remove_feed_source))
+        .route(
+            "/signup-form",
+            get(signup_form).post(manage_allowed_origins),
+        )
+        .route("/public-pages", get(site_theme).post(save_theme))
+        .route(
+            "/email-policy",
+            get(email_policy).post(manage_blocked_domains),
+        )
         .route("/password", get(change_password_form).post(change_password))
         .route("/logout", post(log_out))
         .route_layer(middleware::from_extractor::<AuthenticatedUser>());
@@ -146,9 +258,41 @@ fn build_router(
     Router::<AppState>::new()
         .route("/", get(home))
         .route("/health_check", get(health_check))
-        .route("/subscriptions", post(subscribe))
-        .route("/subscriptions/confirm", get(confirm))
-        .route("/login", get(login_form).post(login))
+        .route(
+            "/subscriptions",
+            post(subscribe.layer(limit_by_ip(LimitedAction::SubscribeFromIp)))
+                .layer(signup_cors_layer(state.db_pool.clone())),
+        )
+        .route(
+            "/subscriptions/challenge",
+            get(signup_challenge).layer(signup_cors_layer(state.db_pool.clone())),
+        )
+        .route("/subscriptions/embed", get(signup_embed))
+        .route("/subscriptions/embed.js", get(signup_script))
+        .route("/subscriptions/check-inbox", get(check_inbox))
+        .route(
+            "/subscriptions/confirm",
+            get(confirm.layer(limit_by_ip(LimitedAction::ConfirmFromIp))),
+        )
+        .route("/subscriptions/keep/{token}", get(keep_subscription))
+        .route(
+            "/subscriptions/preferences/{token}",
+            get(preferences).post(save_preferences),
+        )
+        .route(
+            "/subscriptions/unsubscribe/{token}",
+            get(unsubscribe_subscriber).post(unsubscribe_subscriber),
+        )
+        .route("/archive", get(archive))
+        .route("/archive/{slug}", get(archived_issue))
+        .route("/feed.xml", get(rss_feed))
+        .route("/atom.xml", get(atom_feed))
+        .route("/t/o/{token}", get(track_open))
+        .route("/t/c/{token}", get(track_click))
+        .route(
+            "/login",
+            get(login_form).post(login.layer(limit_by_ip(LimitedAction::LoginFromIp))),
+        )
         .nest("/admin", admin_routes)
         .layer(session_layer)
         .layer(TraceLayer::new_for_http())
@@ -158,9 +302,13 @@ fn run(
     listener: TcpListener,
     state: AppState,
     session_layer: SessionManagerLayer<RedisStore<Pool>, PrivateCookie>,
-) -> Result<axum::serve::Serve<TcpListener, Router, Router>, anyhow::Error> {
-    let app: Router = build_router(session_layer).with_state::<()>(state);
-    let server = axum::serve(listener, app);
+) -> Result<Server, anyhow::Error> {
+    let app: Router = build_router(&state, session_layer).with_state::<()>(state);
+    // The rate limits need the address of each client.
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
     Ok(server)
 }
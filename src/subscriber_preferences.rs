@@ -0,0 +1,217 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriberTimezone;
+use crate::email_client::EmailHeader;
+use crate::newsletter_issues::IssueEmail;
+use crate::tracking::Tracker;
+
+const PREFERENCES_PURPOSE: &str = "preferences";
+const UNSUBSCRIBE_PURPOSE: &str = "unsubscribe";
+
+impl Tracker {
+    /// The link a subscriber follows to change when issues reach them.
+    pub fn preferences_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/preferences/{}",
+            self.base_url(),
+            self.sign(PREFERENCES_PURPOSE, subscriber_id.as_bytes())
+        )
+    }
+
+    pub fn parse_preferences_token(&self, token: &str) -> Option<Uuid> {
+        Uuid::from_slice(&self.verify(PREFERENCES_PURPOSE, token)?).ok()
+    }
+
+    /// The link that takes a subscriber off the list, from the footer of an
+    /// issue or their mail client's unsubscribe button.
+    pub fn unsubscribe_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe/{}",
+            self.base_url(),
+            self.sign(UNSUBSCRIBE_PURPOSE, subscriber_id.as_bytes())
+        )
+    }
+
+    pub fn parse_unsubscribe_token(&self, token: &str) -> Option<Uuid> {
+        Uuid::from_slice(&self.verify(UNSUBSCRIBE_PURPOSE, token)?).ok()
+    }
+}
+
+/// Ends a subscriber's copy of an issue with the links to their preferences
+/// and to unsubscribe, and lets mail clients unsubscribe them in one click
+/// (RFC 8058).
+pub fn add_subscription_links(
+    message: &mut IssueEmail,
+    preferences_url: &str,
+    unsubscribe_url: &str,
+) {
+    let footer = format!(
+        r#"<p style="font-size:12px;color:#6b7280"><a href="{}" style="color:#6b7280">Manage your preferences</a> &middot; <a href="{}" style="color:#6b7280">Unsubscribe</a></p>"#,
+        htmlescape::encode_minimal(preferences_url),
+        htmlescape::encode_minimal(unsubscribe_url)
+    );
+    message.html = match message.html.to_ascii_lowercase().rfind("</body>") {
+        Some(end) => format!("{}{}{}", &message.html[..end], footer, &message.html[end..]),
+        None => format!("{}{}", message.html, footer),
+    };
+    message.text = format!(
+        "{}\n\nManage your preferences: {}\nUnsubscribe: {}\n",
+        message.text.trim_end(),
+        preferences_url,
+        unsubscribe_url
+    );
+    message.headers.extend([
+        EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: format!("<{}>", unsubscribe_url),
+        },
+        EmailHeader {
+            name: "List-Unsubscribe-Post".into(),
+            value: "List-Unsubscribe=One-Click".into(),
+        },
+    ]);
+}
+
+/// The timezone a subscriber set, or `None` if there is no such subscriber.
+#[tracing::instrument(skip(pool))]
+pub async fn get_timezone(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Option<String>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT timezone FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns `false` if there is no such subscriber.
+#[tracing::instrument(skip(pool))]
+pub async fn set_timezone(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    timezone: SubscriberTimezone,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE subscriptions SET timezone = $2 WHERE id = $1",
+        subscriber_id,
+        timezone.as_ref(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Takes a subscriber off the list, cancels their pending deliveries and
+/// deletes their confirmation tokens.
+///
+/// Returns `false` if there is no such subscriber, or they never confirmed.
+#[tracing::instrument(skip(pool))]
+pub async fn unsubscribe(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let Some(email) = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed', unsubscribed_at = COALESCE(unsubscribed_at, NOW())
+        WHERE id = $1 AND status IN ('confirmed', 'unsubscribed')
+        RETURNING email
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(transaction.as_mut())
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET cancelled_at = NOW()
+        WHERE subscriber_email = $1 AND cancelled_at IS NULL
+        "#,
+        email,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+
+    fn tracker() -> Tracker {
+        Tracker::new("http://localhost".into(), Secret::new("secret".into()))
+    }
+
+    #[test]
+    fn preferences_tokens_round_trip() {
+        let subscriber_id = Uuid::new_v4();
+        let url = tracker().preferences_url(subscriber_id);
+        let token = url
+            .strip_prefix("http://localhost/subscriptions/preferences/")
+            .unwrap();
+
+        assert_some_eq!(tracker().parse_preferences_token(token), subscriber_id);
+    }
+
+    #[test]
+    fn keep_tokens_cannot_stand_in_for_preferences_tokens() {
+        let url = tracker().keep_subscribed_url(Uuid::new_v4());
+        let token = url.rsplit('/').next().unwrap();
+
+        assert_none!(tracker().parse_preferences_token(token));
+    }
+
+    #[test]
+    fn unsubscribe_tokens_round_trip() {
+        let subscriber_id = Uuid::new_v4();
+        let url = tracker().unsubscribe_url(subscriber_id);
+        let token = url
+            .strip_prefix("http://localhost/subscriptions/unsubscribe/")
+            .unwrap();
+
+        assert_some_eq!(tracker().parse_unsubscribe_token(token), subscriber_id);
+        assert_none!(tracker().parse_preferences_token(token));
+    }
+
+    #[test]
+    fn the_links_go_at_the_end_of_both_bodies() {
+        let mut message = IssueEmail {
+            subject: "Weekly".into(),
+            html: "<html><body><p>Hi</p></body></html>".into(),
+            text: "Hi\n".into(),
+            headers: vec![],
+        };
+
+        add_subscription_links(
+            &mut message,
+            "http://localhost/p?a=1&b=2",
+            "http://localhost/u",
+        );
+
+        assert!(message.html.ends_with(
+            r#"<a href="http://localhost/p?a=1&amp;b=2" style="color:#6b7280">Manage your preferences</a> &middot; <a href="http://localhost/u" style="color:#6b7280">Unsubscribe</a></p></body></html>"#
+        ));
+        assert!(message.text.starts_with("Hi\n\n"));
+        assert!(message
+            .text
+            .ends_with("http://localhost/p?a=1&b=2\nUnsubscribe: http://localhost/u\n"));
+        assert_eq!(message.headers[0].value, "<http://localhost/u>");
+        assert_eq!(message.headers[1].value, "List-Unsubscribe=One-Click");
+    }
+}
@@ -0,0 +1,333 @@
+use std::time::Duration;
+
+use askama::Template;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::{Settings, SunsetSettings},
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    email_templates::{ReengagementEmailHtml, ReengagementEmailText},
+    rendering::prepare_email_html,
+    startup::get_connection_pool,
+    tracking::Tracker,
+};
+
+// How often to apply the sunset policy (24 hours)
+const SUNSET_INTERVAL_HOURS: u64 = 24;
+
+const KEEP_PURPOSE: &str = "keep";
+
+impl Tracker {
+    /// The link a subscriber follows to stay on the list.
+    pub fn keep_subscribed_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/keep/{}",
+            self.base_url(),
+            self.sign(KEEP_PURPOSE, subscriber_id.as_bytes())
+        )
+    }
+
+    pub fn parse_keep_token(&self, token: &str) -> Option<Uuid> {
+        Uuid::from_slice(&self.verify(KEEP_PURPOSE, token)?).ok()
+    }
+}
+
+/// What one run of the sunset policy did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SunsetOutcome {
+    pub reminded: u64,
+    pub unsubscribed: u64,
+}
+
+#[derive(Debug)]
+struct InactiveSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+}
+
+pub async fn run_sunset_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    let tracker = configuration.application.tracker();
+    sunset_loop(
+        &connection_pool,
+        &email_client,
+        &tracker,
+        &configuration.sunset,
+    )
+    .await
+}
+
+async fn sunset_loop(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    tracker: &Tracker,
+    policy: &SunsetSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        if policy.enabled {
+            match apply_sunset_policy(pool, email_client, tracker, policy).await {
+                Ok(outcome) => {
+                    tracing::info!(
+                        "Sent {} re-engagement emails, unsubscribed {} inactive subscribers",
+                        outcome.reminded,
+                        outcome.unsubscribed
+                    );
+                }
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to apply the sunset policy"
+                    );
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(SUNSET_INTERVAL_HOURS * 3600)).await;
+    }
+}
+
+/// Unsubscribes the subscribers who did not answer their re-engagement email
+/// in time, then sends one to the subscribers who left the last
+/// `policy.inactive_issues` issues unread.
+///
+/// Only issues that tracked opens count towards inactivity, as there is no
+/// telling whether the others were read. Reading an issue or asking to stay
+/// answers the re-engagement email.
+#[tracing::instrument(skip(pool, email_client, tracker))]
+pub async fn apply_sunset_policy(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    tracker: &Tracker,
+    policy: &SunsetSettings,
+) -> Result<SunsetOutcome, anyhow::Error> {
+    let mut outcome = SunsetOutcome {
+        unsubscribed: unsubscribe_unresponsive(pool, policy.grace_days).await?,
+        ..Default::default()
+    };
+
+    for subscriber in list_inactive_subscribers(pool, policy.inactive_issues).await? {
+        let email = match SubscriberEmail::parse(subscriber.email.clone()) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(
+                    error.message = %e,
+                    "Skipping an inactive subscriber. Their stored contact details are invalid",
+                );
+                continue;
+            }
+        };
+
+        // Whoever we could not reach is tried again on the next run.
+        if let Err(e) =
+            send_reengagement_email(email_client, tracker, &subscriber, &email, policy).await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a re-engagement email"
+            );
+            continue;
+        }
+        mark_reengagement_sent(pool, subscriber.id).await?;
+        outcome.reminded += 1;
+    }
+
+    Ok(outcome)
+}
+
+/// Confirmed subscribers who received `inactive_issues` issues with open
+/// tracking since they last engaged, and were not asked about it since.
+#[tracing::instrument(skip(pool))]
+async fn list_inactive_subscribers(
+    pool: &PgPool,
+    inactive_issues: u32,
+) -> Result<Vec<InactiveSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        InactiveSubscriber,
+        r#"
+        SELECT s.id, s.email, s.name
+        FROM subscriptions s
+        JOIN subscriber_last_engagement e ON e.subscriber_id = s.id
+        WHERE s.status = 'confirmed'
+            AND s.duplicate_of IS NULL
+            AND (s.reengagement_sent_at IS NULL OR e.last_engaged_at > s.reengagement_sent_at)
+            AND (
+                SELECT COUNT(*)
+                FROM issue_delivery_log l
+                JOIN newsletter_issues i USING (newsletter_issue_id)
+                WHERE l.subscriber_email = s.email
+                    AND i.track_opens
+                    AND (e.last_engaged_at IS NULL OR l.delivered_at > e.last_engaged_at)
+            ) >= $1
+        ORDER BY s.subscribed_at
+        "#,
+        i64::from(inactive_issues),
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(skip(email_client, tracker, subscriber, email, policy))]
+async fn send_reengagement_email(
+    email_client: &EmailClient,
+    tracker: &Tracker,
+    subscriber: &InactiveSubscriber,
+    email: &SubscriberEmail,
+    policy: &SunsetSettings,
+) -> Result<(), anyhow::Error> {
+    let keep_link = tracker.keep_subscribed_url(subscriber.id);
+
+    let html_body = ReengagementEmailHtml {
+        subscriber_name: subscriber.name.clone(),
+        keep_link: keep_link.clone(),
+        grace_days: policy.grace_days,
+    }
+    .render()?;
+    let html_body = prepare_email_html(
+        &html_body,
+        tracker.base_url(),
+        Some("One click to keep getting our newsletter"),
+    )?;
+    let plain_body = ReengagementEmailText {
+        subscriber_name: subscriber.name.clone(),
+        keep_link,
+        grace_days: policy.grace_days,
+    }
+    .render()?;
+
+    email_client
+        .send_email(
+            email,
+            "Do You Still Want Our Newsletter?",
+            &html_body,
+            &plain_body,
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn mark_reengagement_sent(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET reengagement_sent_at = NOW()
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Unsubscribes whoever was sent a re-engagement email more than
+/// `grace_days` ago and has not engaged since, cancels their pending
+/// deliveries and deletes their confirmation tokens. Returns how many
+/// subscribers left the list.
+#[tracing::instrument(skip(pool))]
+async fn unsubscribe_unresponsive(pool: &PgPool, grace_days: u32) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - chrono::Duration::days(grace_days.into());
+
+    let mut transaction = pool.begin().await?;
+    let (ids, emails): (Vec<Uuid>, Vec<String>) = sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET status = 'unsubscribed', unsubscribed_at = NOW()
+        FROM subscriber_last_engagement e
+        WHERE e.subscriber_id = s.id
+            AND s.status = 'confirmed'
+            AND s.reengagement_sent_at < $1
+            AND (e.last_engaged_at IS NULL OR e.last_engaged_at <= s.reengagement_sent_at)
+        RETURNING s.id, s.email
+        "#,
+        cutoff,
+    )
+    .fetch_all(transaction.as_mut())
+    .await?
+    .into_iter()
+    .map(|r| (r.id, r.email))
+    .unzip();
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET cancelled_at = NOW()
+        WHERE subscriber_email = ANY($1) AND cancelled_at IS NULL
+        "#,
+        &emails,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)",
+        &ids,
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+
+    Ok(emails.len() as u64)
+}
+
+/// Keeps a subscriber on the list, e.g. after they followed the link of a
+/// re-engagement email. Subscribers the policy already took off the list are
+/// put back on it.
+///
+/// Returns `false` if there is no such subscriber, or they never confirmed.
+#[tracing::instrument(skip(pool))]
+pub async fn keep_subscribed(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed', unsubscribed_at = NULL, reengaged_at = NOW()
+        WHERE id = $1 AND status IN ('confirmed', 'unsubscribed')
+        "#,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+
+    #[test]
+    fn sunset_runs_daily() {
+        assert_eq!(SUNSET_INTERVAL_HOURS, 24);
+    }
+
+    #[test]
+    fn keep_tokens_round_trip_through_the_link() {
+        let tracker = Tracker::new("http://localhost".into(), Secret::new("secret".into()));
+        let subscriber_id = Uuid::new_v4();
+
+        let url = tracker.keep_subscribed_url(subscriber_id);
+        let token = url
+            .strip_prefix("http://localhost/subscriptions/keep/")
+            .unwrap();
+
+        assert_some_eq!(tracker.parse_keep_token(token), subscriber_id);
+    }
+
+    #[test]
+    fn open_tokens_cannot_stand_in_for_keep_tokens() {
+        let tracker = Tracker::new("http://localhost".into(), Secret::new("secret".into()));
+        let token = tracker.sign("open", Uuid::new_v4().as_bytes());
+
+        assert_none!(tracker.parse_keep_token(&token));
+    }
+}
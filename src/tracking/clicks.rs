@@ -0,0 +1,184 @@
+use chrono::Utc;
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use reqwest::Url;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{classify_automated, get_delivered_at, Tracker};
+
+const CLICK_PURPOSE: &str = "click";
+
+/// A link as sent to one subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClickToken {
+    pub issue_id: Uuid,
+    pub subscriber_id: Uuid,
+    pub url: String,
+}
+
+impl Tracker {
+    pub fn click_url(&self, token: &ClickToken) -> String {
+        let mut payload = token.issue_id.as_bytes().to_vec();
+        payload.extend_from_slice(token.subscriber_id.as_bytes());
+        payload.extend_from_slice(token.url.as_bytes());
+
+        format!(
+            "{}/t/c/{}",
+            self.base_url(),
+            self.sign(CLICK_PURPOSE, &payload)
+        )
+    }
+
+    /// The link behind a token, as long as we signed it and it points to a
+    /// web page, so that the redirect cannot be abused to send people
+    /// anywhere else.
+    pub fn parse_click_token(&self, token: &str) -> Option<ClickToken> {
+        let payload = self.verify(CLICK_PURPOSE, token)?;
+        if payload.len() <= 32 {
+            return None;
+        }
+        let url = String::from_utf8(payload[32..].to_vec()).ok()?;
+        if !is_trackable(&url) {
+            return None;
+        }
+
+        Some(ClickToken {
+            issue_id: Uuid::from_slice(&payload[..16]).ok()?,
+            subscriber_id: Uuid::from_slice(&payload[16..32]).ok()?,
+            url,
+        })
+    }
+
+    /// Points every web link of an issue sent to `subscriber_id` at its
+    /// tracked redirect. Other links, like `mailto:` ones and anchors, are
+    /// left alone.
+    pub fn track_links(
+        &self,
+        html: &str,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+    ) -> Result<String, anyhow::Error> {
+        let html = rewrite_str(
+            html,
+            RewriteStrSettings {
+                element_content_handlers: vec![element!("a[href]", |el| {
+                    let href = el.get_attribute("href").unwrap_or_default();
+                    let url = htmlescape::decode_html(href.trim()).unwrap_or(href);
+                    if is_trackable(&url) {
+                        let tracked = self.click_url(&ClickToken {
+                            issue_id,
+                            subscriber_id,
+                            url,
+                        });
+                        el.set_attribute("href", &tracked)?;
+                    }
+                    Ok(())
+                })],
+                ..RewriteStrSettings::new()
+            },
+        )?;
+
+        Ok(html)
+    }
+}
+
+fn is_trackable(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Records that a tracked link was followed, flagging machine clicks.
+#[tracing::instrument(skip(pool, tracker, user_agent, ip))]
+pub async fn record_click(
+    pool: &PgPool,
+    tracker: &Tracker,
+    token: &ClickToken,
+    user_agent: Option<&str>,
+    ip: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let clicked_at = Utc::now();
+    let delivered_at = get_delivered_at(pool, token.issue_id, token.subscriber_id).await?;
+    let machine_click = classify_automated(user_agent, delivered_at.map(|d| clicked_at - d));
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_clicks (
+            newsletter_issue_id,
+            subscriber_id,
+            url,
+            clicked_at,
+            user_agent,
+            ip_hash,
+            machine_click
+        )
+        SELECT $1, $2, $3, $4, $5, $6, $7
+        WHERE EXISTS (SELECT 1 FROM subscriptions WHERE id = $2)
+        "#,
+        token.issue_id,
+        token.subscriber_id,
+        token.url,
+        clicked_at,
+        user_agent,
+        ip.map(|ip| tracker.hash_ip(ip)),
+        machine_click,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClickToken;
+    use crate::tracking::Tracker;
+    use claim::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn tracker() -> Tracker {
+        Tracker::new("http://localhost".into(), Secret::new("secret".into()))
+    }
+
+    fn token(url: &str) -> ClickToken {
+        ClickToken {
+            issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+            url: url.into(),
+        }
+    }
+
+    #[test]
+    fn click_tokens_round_trip_through_the_tracked_url() {
+        let token = token("https://example.com/a?b=c&d=e#f");
+
+        let url = tracker().click_url(&token);
+        let encoded = url.strip_prefix("http://localhost/t/c/").unwrap();
+
+        assert_some_eq!(tracker().parse_click_token(encoded), token);
+    }
+
+    #[test]
+    fn tokens_for_anything_but_web_pages_are_refused() {
+        let url = tracker().click_url(&token("javascript:alert(1)"));
+        let encoded = url.strip_prefix("http://localhost/t/c/").unwrap();
+
+        assert_none!(tracker().parse_click_token(encoded));
+    }
+
+    #[test]
+    fn only_web_links_are_tracked() {
+        let html = r##"<a href="https://example.com/?a=1&amp;b=2">Web</a>
+            <a href="mailto:me@example.com">Mail</a> <a href="#top">Top</a>"##;
+
+        let tracked = tracker()
+            .track_links(html, Uuid::new_v4(), Uuid::new_v4())
+            .unwrap();
+
+        let start = tracked.find("/t/c/").unwrap() + "/t/c/".len();
+        let end = start + tracked[start..].find('"').unwrap();
+        let token = tracker().parse_click_token(&tracked[start..end]).unwrap();
+        assert_eq!(token.url, "https://example.com/?a=1&b=2");
+        assert!(tracked.contains(r#"<a href="mailto:me@example.com">Mail</a>"#));
+        assert!(tracked.contains(r##"<a href="#top">Top</a>"##));
+    }
+}
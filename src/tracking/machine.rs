@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Requests this soon after delivery are made by software fetching the email,
+/// not by people reading it.
+const PREFETCH_SECONDS: i64 = 10;
+
+/// User agent fragments of link scanners and other automated fetchers.
+const MACHINE_USER_AGENTS: [&str; 10] = [
+    "bot",
+    "crawler",
+    "spider",
+    "preview",
+    "barracuda",
+    "mimecast",
+    "proofpoint",
+    "symantec",
+    "python-requests",
+    "curl/",
+];
+
+/// Why a tracking request looks automated rather than made by a person, if
+/// it does.
+///
+/// Security scanners announce themselves in their user agent, and anything
+/// fetched within seconds of delivery is prefetching.
+pub fn classify_automated(
+    user_agent: Option<&str>,
+    since_delivery: Option<chrono::Duration>,
+) -> Option<&'static str> {
+    let user_agent = user_agent.map(str::trim).unwrap_or_default();
+    let lowercase = user_agent.to_lowercase();
+    if user_agent.is_empty()
+        || MACHINE_USER_AGENTS
+            .iter()
+            .any(|fragment| lowercase.contains(fragment))
+    {
+        return Some("bot");
+    }
+
+    if since_delivery.is_some_and(|d| d < chrono::Duration::seconds(PREFETCH_SECONDS)) {
+        return Some("prefetch");
+    }
+
+    None
+}
+
+/// When an issue was delivered to a subscriber, if it was.
+#[tracing::instrument(skip(pool))]
+pub(super) async fn get_delivered_at(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT l.delivered_at
+        FROM issue_delivery_log l
+        JOIN subscriptions s ON s.email = l.subscriber_email
+        WHERE l.newsletter_issue_id = $1 AND s.id = $2
+        "#,
+        issue_id,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
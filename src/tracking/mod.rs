@@ -0,0 +1,9 @@
+mod clicks;
+mod machine;
+mod opens;
+mod tracker;
+
+pub use clicks::*;
+pub use machine::*;
+pub use opens::*;
+pub use tracker::*;
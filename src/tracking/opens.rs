@@ -0,0 +1,179 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{classify_automated, get_delivered_at, Tracker};
+
+const OPEN_PURPOSE: &str = "open";
+
+/// Who a tracking pixel was sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenToken {
+    pub issue_id: Uuid,
+    pub subscriber_id: Uuid,
+}
+
+impl Tracker {
+    pub fn open_pixel_url(&self, token: OpenToken) -> String {
+        let mut payload = token.issue_id.as_bytes().to_vec();
+        payload.extend_from_slice(token.subscriber_id.as_bytes());
+
+        format!(
+            "{}/t/o/{}",
+            self.base_url(),
+            self.sign(OPEN_PURPOSE, &payload)
+        )
+    }
+
+    pub fn parse_open_token(&self, token: &str) -> Option<OpenToken> {
+        let payload = self.verify(OPEN_PURPOSE, token)?;
+        if payload.len() != 32 {
+            return None;
+        }
+
+        Some(OpenToken {
+            issue_id: Uuid::from_slice(&payload[..16]).ok()?,
+            subscriber_id: Uuid::from_slice(&payload[16..]).ok()?,
+        })
+    }
+}
+
+/// Adds an invisible 1x1 image loading `pixel_url` at the end of the body.
+pub fn inject_open_pixel(html: &str, pixel_url: &str) -> String {
+    let pixel = format!(
+        r#"<img src="{}" width="1" height="1" alt="" style="display:block;width:1px;height:1px;border:0">"#,
+        htmlescape::encode_minimal(pixel_url)
+    );
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], pixel, &html[end..]),
+        None => format!("{}{}", html, pixel),
+    }
+}
+
+/// Why an open looks automated rather than a person reading the email, if it
+/// does.
+///
+/// Apple Mail Privacy Protection loads every image through Apple's proxies
+/// (17.0.0.0/8, with a bare `Mozilla/5.0` user agent) as soon as the email
+/// arrives, whether it is read or not. Otherwise see [`classify_automated`].
+pub fn classify_open(
+    user_agent: Option<&str>,
+    ip: Option<&str>,
+    since_delivery: Option<chrono::Duration>,
+) -> Option<&'static str> {
+    let user_agent = user_agent.map(str::trim).unwrap_or_default();
+    let from_apple = ip
+        .and_then(|ip| ip.parse::<std::net::IpAddr>().ok())
+        .is_some_and(|ip| matches!(ip, std::net::IpAddr::V4(v4) if v4.octets()[0] == 17));
+    if from_apple || user_agent == "Mozilla/5.0" {
+        return Some("apple_mpp");
+    }
+
+    classify_automated(Some(user_agent), since_delivery)
+}
+
+/// Records that the pixel of `token` was loaded, flagging machine opens.
+#[tracing::instrument(skip(pool, tracker, user_agent, ip))]
+pub async fn record_open(
+    pool: &PgPool,
+    tracker: &Tracker,
+    token: OpenToken,
+    user_agent: Option<&str>,
+    ip: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let opened_at = Utc::now();
+    let delivered_at = get_delivered_at(pool, token.issue_id, token.subscriber_id).await?;
+    let machine_open = classify_open(user_agent, ip, delivered_at.map(|d| opened_at - d));
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_opens (
+            newsletter_issue_id,
+            subscriber_id,
+            opened_at,
+            user_agent,
+            ip_hash,
+            machine_open
+        )
+        SELECT $1, $2, $3, $4, $5, $6
+        WHERE EXISTS (SELECT 1 FROM subscriptions WHERE id = $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        token.issue_id,
+        token.subscriber_id,
+        opened_at,
+        user_agent,
+        ip.map(|ip| tracker.hash_ip(ip)),
+        machine_open,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_open, inject_open_pixel, OpenToken};
+    use crate::tracking::Tracker;
+    use claim::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    const MAIL_CLIENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Thunderbird/115.0";
+
+    #[test]
+    fn open_tokens_round_trip_through_the_pixel_url() {
+        let tracker = Tracker::new("http://localhost".into(), Secret::new("secret".into()));
+        let token = OpenToken {
+            issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+        };
+
+        let url = tracker.open_pixel_url(token);
+        let encoded = url.strip_prefix("http://localhost/t/o/").unwrap();
+
+        assert_some_eq!(tracker.parse_open_token(encoded), token);
+    }
+
+    #[test]
+    fn the_pixel_goes_at_the_end_of_the_body() {
+        let html = inject_open_pixel("<html><body><p>Hi</p></BODY></html>", "http://x/t/o/a.b");
+        assert!(html.starts_with("<html><body><p>Hi</p><img src=\"http://x/t/o/a.b\""));
+        assert!(html.ends_with("</BODY></html>"));
+    }
+
+    #[test]
+    fn apple_privacy_proxies_are_flagged() {
+        assert_some_eq!(
+            classify_open(Some("Mozilla/5.0"), Some("203.0.113.1"), None),
+            "apple_mpp"
+        );
+        assert_some_eq!(
+            classify_open(Some(MAIL_CLIENT), Some("17.58.1.2"), None),
+            "apple_mpp"
+        );
+    }
+
+    #[test]
+    fn scanners_and_prefetches_are_flagged() {
+        assert_some_eq!(
+            classify_open(Some("Barracuda Sentinel (EE)"), None, None),
+            "bot"
+        );
+        assert_some_eq!(classify_open(None, None, None), "bot");
+        assert_some_eq!(
+            classify_open(Some(MAIL_CLIENT), None, Some(chrono::Duration::seconds(2))),
+            "prefetch"
+        );
+    }
+
+    #[test]
+    fn people_reading_the_email_are_not_flagged() {
+        assert_none!(classify_open(
+            Some(MAIL_CLIENT),
+            Some("203.0.113.1"),
+            Some(chrono::Duration::minutes(30))
+        ));
+    }
+}
@@ -39,6 +39,27 @@ where
     AppError::new(e.into(), StatusCode::BAD_REQUEST)
 }
 
+pub fn e404<T>(e: T) -> AppError
+where
+    T: Into<anyhow::Error>,
+{
+    AppError::new(e.into(), StatusCode::NOT_FOUND)
+}
+
+pub fn e409<T>(e: T) -> AppError
+where
+    T: Into<anyhow::Error>,
+{
+    AppError::new(e.into(), StatusCode::CONFLICT)
+}
+
+pub fn e422<T>(e: T) -> AppError
+where
+    T: Into<anyhow::Error>,
+{
+    AppError::new(e.into(), StatusCode::UNPROCESSABLE_ENTITY)
+}
+
 pub fn see_other(location: &str) -> Response {
     Redirect::to(location).into_response()
 }
@@ -1,5 +1,22 @@
 use askama::Template;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::allowed_origins::AllowedOrigin;
+use crate::analytics::{
+    EngagementWeek, GrowthWeek, IssueEngagement, SubscriberEngagement, TopLink,
+};
+use crate::domain::IssueStatus;
+use crate::duplicate_subscribers::DuplicateGroup;
+use crate::email_policy::{BlockedDomain, FlaggedSubscriber};
+use crate::feed_sources::FeedSource;
+use crate::newsletter_issues::{
+    ArchiveEntry, ArchivedIssue, DeadLetterGroup, DeliveryStats, DeliveryWave, IssueSummary,
+    Revision,
+};
 use crate::session_state::FlashMessage;
+use crate::signup_protection::SignupChallenge;
+use crate::site_theme::SiteTheme;
 
 #[derive(Template)]
 #[template(path = "web/login.html")]
@@ -25,3 +42,299 @@ pub struct NewslettersFormTemplate {
 pub struct ChangePasswordTemplate {
     pub flash_messages: Vec<FlashMessage>,
 }
+
+#[derive(Template)]
+#[template(path = "web/issues_list.html")]
+pub struct IssuesListTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub issues: Vec<IssueSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "web/analytics.html")]
+pub struct AnalyticsTemplate {
+    pub issues: Vec<IssueEngagement>,
+    pub top_links: Vec<TopLink>,
+    pub engagement: Vec<EngagementWeek>,
+    pub growth: Vec<GrowthWeek>,
+}
+
+#[derive(Template)]
+#[template(path = "web/subscribers.html")]
+pub struct SubscribersTemplate {
+    pub subscribers: Vec<SubscriberEngagement>,
+    /// Starts at 0.
+    pub page: i64,
+    pub has_next_page: bool,
+    pub scored_issues: i64,
+}
+
+#[derive(Template)]
+#[template(path = "web/duplicate_subscribers.html")]
+pub struct DuplicateSubscribersTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+#[derive(Template)]
+#[template(path = "web/dead_letters.html")]
+pub struct DeadLettersTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub groups: Vec<DeadLetterGroup>,
+}
+
+/// What a public page shows, besides the theme.
+pub enum PublicPage {
+    /// `error` tells why the last signup failed.
+    Signup {
+        error: Option<String>,
+        challenge: SignupChallenge,
+    },
+    CheckInbox,
+    Confirmed,
+    /// For confirmation links that are malformed or expired.
+    InvalidToken,
+    Unsubscribed,
+    /// Where subscribers pick the timezone issues reach them in. `notice`
+    /// tells how the last change went.
+    Preferences {
+        timezones: Vec<TimezoneOption>,
+        has_timezone: bool,
+        notice: Option<&'static str>,
+    },
+    /// Anything else subscribers need to be told, like a failure.
+    Notice {
+        title: &'static str,
+        message: &'static str,
+    },
+}
+
+impl PublicPage {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PublicPage::Signup { .. } => "Subscribe",
+            PublicPage::CheckInbox => "Check your inbox",
+            PublicPage::Confirmed => "You're subscribed!",
+            PublicPage::InvalidToken => "This link is not valid",
+            PublicPage::Unsubscribed => "You're unsubscribed",
+            PublicPage::Preferences { .. } => "Your preferences",
+            PublicPage::Notice { title, .. } => title,
+        }
+    }
+
+    pub fn is_signup(&self) -> bool {
+        matches!(self, PublicPage::Signup { .. })
+    }
+
+    /// The preferences page of a subscriber who set `timezone`, if any.
+    pub fn preferences(timezone: Option<&str>, notice: Option<&'static str>) -> Self {
+        PublicPage::Preferences {
+            timezones: chrono_tz::TZ_VARIANTS
+                .iter()
+                .map(|tz| TimezoneOption {
+                    name: tz.name(),
+                    selected: timezone == Some(tz.name()),
+                })
+                .collect(),
+            has_timezone: timezone.is_some(),
+            notice,
+        }
+    }
+}
+
+/// One choice of the timezone list of the preferences page.
+pub struct TimezoneOption {
+    pub name: &'static str,
+    pub selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "web/public_page.html")]
+pub struct PublicPageTemplate {
+    pub theme: SiteTheme,
+    pub base_url: String,
+    pub page: PublicPage,
+}
+
+#[derive(Template)]
+#[template(path = "web/signup_embed.html")]
+pub struct SignupEmbedTemplate {
+    pub challenge: SignupChallenge,
+}
+
+/// `config_json` is what the script needs to know as a JavaScript object
+/// literal: the signup endpoint and the challenge of its forms.
+#[derive(Template)]
+#[template(path = "embed/signup.js", escape = "none")]
+pub struct SignupScriptTemplate {
+    pub config_json: String,
+}
+
+#[derive(Template)]
+#[template(path = "web/signup_form.html")]
+pub struct SignupFormTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub origins: Vec<AllowedOrigin>,
+    pub base_url: String,
+}
+
+#[derive(Template)]
+#[template(path = "web/email_policy.html")]
+pub struct EmailPolicyTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub blocked_domains: Vec<BlockedDomain>,
+    pub flagged_subscribers: Vec<FlaggedSubscriber>,
+    pub block_disposable: bool,
+    pub disposable_domain_count: usize,
+    pub block_role_accounts: bool,
+}
+
+#[derive(Template)]
+#[template(path = "web/site_theme.html")]
+pub struct SiteThemeTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub theme: SiteTheme,
+}
+
+#[derive(Template)]
+#[template(path = "web/feed_sources.html")]
+pub struct FeedSourcesTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub sources: Vec<FeedSource>,
+}
+
+#[derive(Template)]
+#[template(path = "web/feed_source.html")]
+pub struct FeedSourceTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub source: FeedSource,
+}
+
+#[derive(Template)]
+#[template(path = "web/issue_editor.html")]
+pub struct IssueEditorTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub issue_id: Uuid,
+    pub status: IssueStatus,
+    pub editable: bool,
+    pub title: String,
+    pub preheader: String,
+    pub markdown: String,
+    pub text: String,
+    pub html: String,
+    pub track_opens: bool,
+    /// Subjects tested against the title.
+    pub subject_variants: Vec<String>,
+    pub ab_test_percent: i16,
+    pub ab_test_hours: i16,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Set when the issue goes out at this time in each subscriber's timezone.
+    pub local_send_at: Option<NaiveDateTime>,
+    pub waves: Vec<DeliveryWave>,
+    /// Set once the issue has started going out.
+    pub deliveries: Option<DeliveryStats>,
+    /// Where the issue is in the public archive, once it went out.
+    pub slug: Option<String>,
+    pub hide_from_archive: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl IssueEditorTemplate {
+    /// The subject variant inputs of the editor, filled or not.
+    pub fn subject_variant_inputs(&self) -> Vec<(&'static str, &str)> {
+        ["subject_b", "subject_c", "subject_d"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let value = self.subject_variants.get(i).map_or("", String::as_str);
+                (name, value)
+            })
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "web/issue_revisions.html")]
+pub struct IssueRevisionsTemplate {
+    pub flash_messages: Vec<FlashMessage>,
+    pub issue_id: Uuid,
+    pub title: String,
+    pub revisions: Vec<Revision>,
+}
+
+/// The changes to one field of an issue between two revisions.
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub lines: Vec<DiffLine>,
+}
+
+pub struct DiffLine {
+    /// One of `insert`, `delete` or `equal`.
+    pub kind: &'static str,
+    pub text: String,
+}
+
+#[derive(Template)]
+#[template(path = "web/issue_revision.html")]
+pub struct IssueRevisionTemplate {
+    pub issue_id: Uuid,
+    pub editable: bool,
+    pub revision: Revision,
+    pub diffs: Vec<FieldDiff>,
+}
+
+#[derive(Template)]
+#[template(path = "web/archive.html")]
+pub struct ArchiveTemplate {
+    pub entries: Vec<ArchiveEntry>,
+    /// Starts at 0.
+    pub page: i64,
+    pub has_next_page: bool,
+    /// The canonical address of the archive.
+    pub url: String,
+}
+
+#[derive(Template)]
+#[template(path = "web/archive_issue.html")]
+pub struct ArchiveIssueTemplate {
+    pub issue: ArchivedIssue,
+    /// The canonical address of the issue.
+    pub url: String,
+}
+
+/// An issue as feed readers get it.
+pub struct FeedEntry {
+    pub id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub published_at: DateTime<Utc>,
+    pub summary: String,
+    /// The issue's HTML, without what only makes sense in an inbox.
+    pub content: String,
+}
+
+#[derive(Template)]
+#[template(path = "feeds/rss.xml")]
+pub struct RssFeedTemplate {
+    pub base_url: String,
+    /// Latest first.
+    pub entries: Vec<FeedEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "feeds/atom.xml")]
+pub struct AtomFeedTemplate {
+    pub base_url: String,
+    /// Latest first.
+    pub entries: Vec<FeedEntry>,
+}
+
+impl AtomFeedTemplate {
+    /// When the feed last changed, as far as its entries tell.
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.entries
+            .first()
+            .map(|entry| entry.published_at)
+            .unwrap_or_default()
+    }
+}
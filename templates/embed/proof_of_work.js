@@ -0,0 +1,25 @@
+    // Finds a nonce that gives the SHA-256 hash of "<token>:<nonce>" `bits`
+    // leading zero bits, which is what the server checks.
+    function solveProofOfWork(token, bits) {
+        var encoder = new TextEncoder();
+        function leadingZeroBits(bytes) {
+            var count = 0;
+            for (var i = 0; i < bytes.length; i++) {
+                if (bytes[i] !== 0) {
+                    return count + Math.clz32(bytes[i]) - 24;
+                }
+                count += 8;
+            }
+            return count;
+        }
+        function attempt(nonce) {
+            return crypto.subtle
+                .digest("SHA-256", encoder.encode(token + ":" + nonce))
+                .then(function (hash) {
+                    return leadingZeroBits(new Uint8Array(hash)) >= bits
+                        ? String(nonce)
+                        : attempt(nonce + 1);
+                });
+        }
+        return attempt(0);
+    }
@@ -0,0 +1,137 @@
+// Turns every element with a data-newsletter-signup attribute into a signup
+// form for our newsletter. Only works on sites the newsletter admin allowed.
+(function () {
+    var config = {{ config_json }};
+
+{% include "embed/proof_of_work.js" %}
+
+    function field(type, name, placeholder) {
+        var input = document.createElement("input");
+        input.type = type;
+        input.name = name;
+        input.placeholder = placeholder;
+        input.required = true;
+        return input;
+    }
+
+    // Hidden from people, so only bots fill it in.
+    function honeypot() {
+        var container = document.createElement("div");
+        container.setAttribute("aria-hidden", "true");
+        container.style.cssText = "position: absolute; left: -10000px; width: 1px; height: 1px; overflow: hidden;";
+        var input = document.createElement("input");
+        input.type = "text";
+        input.name = "website";
+        input.tabIndex = -1;
+        input.autocomplete = "off";
+        container.appendChild(input);
+        return container;
+    }
+
+    function captchaWidget() {
+        var widget = document.createElement("div");
+        widget.className = config.captcha.widgetClass;
+        widget.setAttribute("data-sitekey", config.captcha.siteKey);
+        return widget;
+    }
+
+    function loadCaptchaScript() {
+        if (document.querySelector("script[data-newsletter-captcha]")) {
+            return;
+        }
+        var script = document.createElement("script");
+        script.src = config.captcha.scriptUrl;
+        script.async = true;
+        script.defer = true;
+        script.setAttribute("data-newsletter-captcha", "");
+        document.head.appendChild(script);
+    }
+
+    function resetCaptcha() {
+        if (window.hcaptcha) {
+            window.hcaptcha.reset();
+        }
+        if (window.turnstile) {
+            window.turnstile.reset();
+        }
+    }
+
+    function mount(container) {
+        var form = document.createElement("form");
+        form.className = "newsletter-signup";
+        var name = field("text", "name", "Your name");
+        var email = field("email", "email", "Your email");
+        var button = document.createElement("button");
+        button.type = "submit";
+        button.textContent = "Subscribe";
+        var status = document.createElement("p");
+        status.className = "newsletter-signup-status";
+        status.setAttribute("role", "status");
+        form.append(name, email, honeypot());
+        if (config.captcha) {
+            form.append(captchaWidget());
+        }
+        form.append(button, status);
+
+        form.addEventListener("submit", function (event) {
+            event.preventDefault();
+            button.disabled = true;
+            var captchaResponse = config.captcha
+                ? form.querySelector("[name=" + config.captcha.responseField + "]")
+                : null;
+            var proofOfWork = config.proofOfWorkBits > 0
+                ? solveProofOfWork(config.formToken, config.proofOfWorkBits)
+                : Promise.resolve(null);
+            proofOfWork
+                .then(function (nonce) {
+                    return fetch(config.endpoint, {
+                        method: "POST",
+                        headers: { "Content-Type": "application/json", "Accept": "application/json" },
+                        body: JSON.stringify({
+                            name: name.value,
+                            email: email.value,
+                            timezone: Intl.DateTimeFormat().resolvedOptions().timeZone,
+                            website: form.querySelector("[name=website]").value,
+                            form_token: config.formToken,
+                            proof_of_work: nonce,
+                            captcha_response: captchaResponse ? captchaResponse.value : null
+                        })
+                    });
+                })
+                .then(function (response) { return response.json(); })
+                .then(function (body) {
+                    status.textContent = body.fields
+                        ? Object.keys(body.fields).map(function (key) { return body.fields[key]; }).join(". ")
+                        : body.message;
+                    if (!body.error) {
+                        // The form token is used up, so the form is done.
+                        form.replaceWith(status);
+                    }
+                })
+                .catch(function () {
+                    status.textContent = "Something went wrong, please try again later";
+                })
+                .then(function () {
+                    button.disabled = false;
+                    if (config.captcha) {
+                        resetCaptcha();
+                    }
+                });
+        });
+
+        container.appendChild(form);
+    }
+
+    function mountAll() {
+        document.querySelectorAll("[data-newsletter-signup]").forEach(mount);
+        if (config.captcha) {
+            loadCaptchaScript();
+        }
+    }
+
+    if (document.readyState === "loading") {
+        document.addEventListener("DOMContentLoaded", mountAll);
+    } else {
+        mountAll();
+    }
+})();
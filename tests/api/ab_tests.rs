@@ -0,0 +1,125 @@
+use email_newsletter::issue_scheduler::{try_pick_due_winner, SchedulerOutcome};
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+use crate::tracking::{backdate_deliveries, tracking_path, MAIL_CLIENT};
+
+/// The issue emails sent so far, leaving out confirmation emails.
+async fn sent_issues(app: &TestApp) -> Vec<serde_json::Value> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .filter(|b: &serde_json::Value| b.get("Headers").is_some())
+        .collect()
+}
+
+#[tokio::test]
+async fn the_winning_subject_goes_to_the_rest_of_the_audience() {
+    let app = spawn_app().await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let body = serde_json::json!({
+        "title": "Subject A",
+        "subject_b": "Subject B",
+        "markdown": "Read [the story](https://example.com/story).",
+        "ab_test_percent": "20",
+        "ab_test_hours": "1",
+    });
+    let response = app.post_issue(issue_id, "/send", &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+    app.dispatch_all_pending_emails().await;
+
+    // 20% of the audience, one subscriber per subject.
+    let sent = sent_issues(&app).await;
+    let mut subjects: Vec<&str> = sent
+        .iter()
+        .map(|e| e["Subject"].as_str().unwrap())
+        .collect();
+    subjects.sort();
+    assert_eq!(subjects, ["Subject A", "Subject B"]);
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Subject line test"));
+    assert!(html_page.contains("goes to the other 8 subscribers"));
+
+    // The subscriber who got B clicks, nobody else does.
+    let b = sent.iter().find(|e| e["Subject"] == "Subject B").unwrap();
+    let link = tracking_path(b["HtmlBody"].as_str().unwrap(), "/t/c/").unwrap();
+    backdate_deliveries(&app).await;
+    app.get_tracking_link(&link, MAIL_CLIENT).await;
+
+    // Nothing to decide until the test is over.
+    assert!(matches!(
+        try_pick_due_winner(&app.db_pool).await.unwrap(),
+        SchedulerOutcome::NothingDue
+    ));
+    sqlx::query!("UPDATE newsletter_issues SET ab_test_decide_at = NOW()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(matches!(
+        try_pick_due_winner(&app.db_pool).await.unwrap(),
+        SchedulerOutcome::WinnerPicked
+    ));
+    app.dispatch_all_pending_emails().await;
+
+    let sent = sent_issues(&app).await;
+    assert_eq!(sent.len(), 10);
+    assert!(sent[2..].iter().all(|e| e["Subject"] == "Subject B"));
+    let variants = sqlx::query!(
+        r#"
+        SELECT subject_variant AS "subject_variant!", COUNT(*) AS "deliveries!"
+        FROM issue_delivery_log
+        GROUP BY subject_variant
+        ORDER BY subject_variant
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(variants.len(), 2);
+    assert_eq!(
+        (variants[0].subject_variant, variants[0].deliveries),
+        (0, 1)
+    );
+    assert_eq!(
+        (variants[1].subject_variant, variants[1].deliveries),
+        (1, 9)
+    );
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Subject 2 won"));
+    assert!(html_page.contains("Subject B <strong>(winner)</strong>"));
+}
+
+#[tokio::test]
+async fn subject_lines_cannot_be_tested_in_local_time_mode() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let issue_id = app.create_draft_issue().await;
+    let body = serde_json::json!({
+        "title": "Subject A",
+        "subject_b": "Subject B",
+        "markdown": "Hello",
+        "scheduled_at": "2099-01-01T09:00",
+        "timezone": "Europe/Paris",
+        "local_time": "on",
+    });
+    let response = app.post_issue(issue_id, "/schedule", &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Subject lines can only be tested on issues sent at once"));
+}
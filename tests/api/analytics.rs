@@ -0,0 +1,73 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::tracking::{
+    backdate_deliveries, open_pixel_path, send_issue, tracking_path, MAIL_CLIENT,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_analytics() {
+    let app = spawn_app().await;
+
+    let response = app.get_analytics().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_analytics_export("issues").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_analytics_show_opens_clicks_and_growth() {
+    let app = spawn_app().await;
+    let (issue_id, html) = send_issue(&app, true).await;
+    backdate_deliveries(&app).await;
+    app.get_tracking_link(&open_pixel_path(&html).unwrap(), MAIL_CLIENT)
+        .await;
+    app.get_tracking_link(&open_pixel_path(&html).unwrap(), "Mozilla/5.0")
+        .await;
+    let link = tracking_path(&html, "/t/c/").unwrap();
+    app.get_tracking_link(&link, MAIL_CLIENT).await;
+    app.get_tracking_link(&link, MAIL_CLIENT).await;
+
+    let html_page = app.get_analytics_html().await;
+    assert!(html_page.contains(&format!("/admin/issues/{}", issue_id)));
+    assert!(html_page.contains("https://example.com/story?from=newsletter&amp;issue=1"));
+
+    // One delivery, one person who opened and clicked twice; the Apple Mail
+    // open is only counted as automated.
+    let response = app.get_analytics_export("issues").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let csv = response.text().await.unwrap();
+    let row = csv.lines().nth(1).unwrap();
+    assert!(row.starts_with(&issue_id.to_string()));
+    assert!(row.ends_with(",1,1,1,1,100.0,2,1,100.0"), "{}", row);
+
+    let csv = app
+        .get_analytics_export("links")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(csv.contains("https://example.com/story?from=newsletter&issue=1,2,1"));
+
+    let csv = app
+        .get_analytics_export("growth")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let this_week = csv.lines().last().unwrap();
+    assert!(this_week.ends_with(",1,0,1,0.0"), "{}", this_week);
+}
+
+#[tokio::test]
+async fn unknown_reports_cannot_be_exported() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_analytics_export("passwords").await;
+
+    assert!(response.status().is_client_error());
+}
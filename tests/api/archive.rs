@@ -0,0 +1,152 @@
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+
+/// Sends an issue with the given Markdown to a single confirmed subscriber and
+/// returns its address in the archive.
+pub async fn publish_issue(app: &TestApp, title: &str, markdown: &str) -> (Uuid, String) {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let body = serde_json::json!({
+        "title": title,
+        "preheader": "All the news that fits",
+        "markdown": markdown,
+    });
+    app.post_issue(issue_id, "/send", &body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let slug = sqlx::query_scalar!(
+        "SELECT slug FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .expect("The issue went out without a slug");
+    (issue_id, format!("/archive/{}", slug))
+}
+
+#[tokio::test]
+async fn issues_that_went_out_are_listed_in_the_archive() {
+    let app = spawn_app().await;
+    let (_, path) = publish_issue(&app, "Weekly Digest #1", "Read all *about* it.").await;
+    assert!(path.starts_with("/archive/weekly-digest-1-"));
+
+    let html = app.get_archive("/archive").await.text().await.unwrap();
+    assert!(html.contains(&format!(r#"href="{}""#, path)));
+    assert!(html.contains("All the news that fits"));
+
+    let response = app.get_archive(&path).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<meta property="og:title" content="Weekly Digest #1">"#));
+    assert!(html.contains(r#"<meta name="description" content="All the news that fits">"#));
+    assert!(html.contains(&format!(
+        r#"<link rel="canonical" href="{}{}">"#,
+        app.base_url, path
+    )));
+    assert!(html.contains("Read all &lt;em&gt;about&lt;/em&gt; it."));
+}
+
+#[tokio::test]
+async fn drafts_and_unknown_issues_are_not_in_the_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.create_draft_issue().await;
+
+    let html = app.get_archive("/archive").await.text().await.unwrap();
+    assert!(html.contains("No issues yet."));
+
+    let response = app.get_archive("/archive/nothing-here").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn merge_tags_do_not_leak_subscriber_details() {
+    let app = spawn_app().await;
+    let (_, path) = publish_issue(
+        &app,
+        "Hello {{ name | friend }}",
+        "Hi {{ name | reader }}, this went to {{ email }}.",
+    )
+    .await;
+    let subscriber = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html = app.get_archive(&path).await.text().await.unwrap();
+
+    assert!(html.contains("<h1>Hello friend</h1>"));
+    assert!(html.contains("Hi reader, this went to ."));
+    assert!(!html.contains(&subscriber.email));
+    assert!(!html.contains(&htmlescape::encode_minimal(&subscriber.name)));
+    assert!(!html.contains("{{"));
+}
+
+#[tokio::test]
+async fn hidden_issues_are_left_out_of_the_archive() {
+    let app = spawn_app().await;
+    let (issue_id, path) = publish_issue(&app, "Weekly", "Read all about it.").await;
+
+    let response = app
+        .post_issue(issue_id, "/archive", &serde_json::json!({"hidden": true}))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+    assert!(app
+        .get_issue_html(issue_id)
+        .await
+        .contains("Hidden from the public archive"));
+
+    let html = app.get_archive("/archive").await.text().await.unwrap();
+    assert!(!html.contains(&path));
+    assert_eq!(app.get_archive(&path).await.status().as_u16(), 404);
+
+    app.post_issue(issue_id, "/archive", &serde_json::json!({"hidden": false}))
+        .await;
+    assert_eq!(app.get_archive(&path).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated() {
+    let app = spawn_app().await;
+    for i in 0..21 {
+        sqlx::query!(
+            r#"
+            INSERT INTO newsletter_issues (
+                newsletter_issue_id, title, text_content, html_content, status, published_at, slug
+            )
+            VALUES ($1, $2, '', '', 'sent', NOW() - $3 * interval '1 day', $2)
+            "#,
+            Uuid::new_v4(),
+            format!("issue-{}", i),
+            f64::from(i),
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let html = app.get_archive("/archive").await.text().await.unwrap();
+    assert!(html.contains(r#"href="/archive/issue-0""#));
+    assert!(html.contains(r#"href="/archive/issue-19""#));
+    assert!(!html.contains(r#"href="/archive/issue-20""#));
+    assert!(html.contains(r#"href="/archive?page=1""#));
+
+    let html = app
+        .get_archive("/archive?page=1")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(r#"href="/archive/issue-20""#));
+    assert!(!html.contains(r#"href="/archive?page=2""#));
+}
@@ -0,0 +1,138 @@
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+
+/// Sends an issue to every confirmed subscriber, then pretends every delivery
+/// failed for good.
+async fn send_issue_and_dead_letter_it(app: &TestApp) -> Uuid {
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(
+        issue_id,
+        "/send",
+        &serde_json::json!({ "title": "Launch", "markdown": "We are **live**" }),
+    )
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO dead_letter_queue
+            (newsletter_issue_id, subscriber_email, attempt_count, last_error, failed_at)
+        SELECT newsletter_issue_id, subscriber_email, 5, 'Provider unavailable', NOW()
+        FROM issue_delivery_log
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "DELETE FROM issue_delivery_log WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    issue_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_failed_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app.get_dead_letters().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn selected_failed_deliveries_can_be_requeued() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = send_issue_and_dead_letter_it(&app).await;
+    let emails = sqlx::query_scalar!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app.get_dead_letters_html().await;
+    assert!(html_page.contains("Provider unavailable"));
+    assert!(html_page.contains(&emails[0]));
+    assert!(html_page.contains(&emails[1]));
+
+    let response = app
+        .post_dead_letters(&[
+            ("issue_id", issue_id.to_string().as_str()),
+            ("subscriber_email", &emails[0]),
+            ("action", "requeue_selected"),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/dead-letters");
+    let html_page = app.get_dead_letters_html().await;
+    assert!(html_page.contains("Requeued 1 deliveries"));
+    assert!(!html_page.contains(&emails[0]));
+
+    // The requeued email goes out again and the issue is done once more.
+    app.dispatch_all_pending_emails().await;
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "sent");
+
+    let response = app
+        .post_dead_letters(&[
+            ("issue_id", issue_id.to_string().as_str()),
+            ("subscriber_email", &emails[1]),
+            ("action", "discard_selected"),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/dead-letters");
+    let html_page = app.get_dead_letters_html().await;
+    assert!(html_page.contains("Discarded 1 deliveries"));
+    assert!(html_page.contains("No failed deliveries."));
+}
+
+#[tokio::test]
+async fn all_failed_deliveries_of_an_issue_can_be_requeued() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(4)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = send_issue_and_dead_letter_it(&app).await;
+
+    let response = app
+        .post_dead_letters(&[
+            ("issue_id", issue_id.to_string().as_str()),
+            ("action", "requeue_all"),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/dead-letters");
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("2 of 2 emails sent"));
+}
@@ -0,0 +1,235 @@
+use chrono::{Duration, Utc};
+use email_newsletter::configuration::Settings;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+async fn subscribe(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_subscriptions_json(&serde_json::json!({ "name": "le guin", "email": email }))
+        .await
+}
+
+async fn when_confirmation_emails_are_sent(app: &TestApp, emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn stored_emails(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+/// A subscriber stored before addresses were normalized, which the unique
+/// index on the normalized address does not catch.
+async fn insert_legacy_subscriber(app: &TestApp, email: &str, status: &str, days_ago: i64) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, normalized_email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', $4, $5)
+        "#,
+        id,
+        email,
+        id.to_string(),
+        Utc::now() - Duration::days(days_ago),
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id
+}
+
+async fn merge(app: &TestApp, normalized_email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/subscribers/duplicates", &app.address))
+        .form(&serde_json::json!({ "normalized_email": normalized_email }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn deliveries(app: &TestApp) -> Vec<(Uuid, String)> {
+    sqlx::query!("SELECT newsletter_issue_id, subscriber_email FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.newsletter_issue_id, r.subscriber_email))
+        .collect()
+}
+
+#[tokio::test]
+async fn addresses_differing_in_case_are_one_subscriber() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app, 2).await;
+
+    subscribe(&app, "Ursula_Le_Guin@Gmail.COM").await;
+    let response = subscribe(&app, "ursula_le_guin@gmail.com").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(stored_emails(&app).await, ["Ursula_Le_Guin@gmail.com"]);
+}
+
+#[tokio::test]
+async fn internationalized_domains_are_stored_in_ascii() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app, 2).await;
+
+    subscribe(&app, "ursula@bücher.example").await;
+    subscribe(&app, "ursula@xn--bcher-kva.example").await;
+
+    assert_eq!(stored_emails(&app).await, ["ursula@xn--bcher-kva.example"]);
+}
+
+#[tokio::test]
+async fn gmail_dots_and_tags_count_only_when_configured() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app, 2).await;
+    subscribe(&app, "u.le.guin+news@gmail.com").await;
+    subscribe(&app, "uleguin@googlemail.com").await;
+    assert_eq!(stored_emails(&app).await.len(), 2);
+
+    let app = spawn_app_with(|c: &mut Settings| c.email_policy.normalize_gmail = true).await;
+    when_confirmation_emails_are_sent(&app, 2).await;
+    subscribe(&app, "u.le.guin+news@gmail.com").await;
+    subscribe(&app, "uleguin@googlemail.com").await;
+    assert_eq!(stored_emails(&app).await, ["u.le.guin+news@gmail.com"]);
+}
+
+#[tokio::test]
+async fn the_report_lists_duplicates_with_the_confirmed_one_first() {
+    let app = spawn_app().await;
+    insert_legacy_subscriber(&app, "ursula@example.com", "pending_confirmation", 10).await;
+    insert_legacy_subscriber(&app, "Ursula@Example.com", "confirmed", 1).await;
+    insert_legacy_subscriber(&app, "ged@example.com", "confirmed", 1).await;
+    app.test_user.login(&app).await;
+
+    let html = app.get_html("/admin/subscribers/duplicates").await;
+
+    let kept = html.find("Ursula@Example.com (kept)").unwrap();
+    let merged = html.find("<td>ursula@example.com</td>").unwrap();
+    assert!(kept < merged);
+    assert!(!html.contains("ged@example.com"));
+}
+
+#[tokio::test]
+async fn merging_keeps_one_subscriber_per_inbox() {
+    let app = spawn_app().await;
+    insert_legacy_subscriber(&app, "ursula@example.com", "pending_confirmation", 10).await;
+    insert_legacy_subscriber(&app, "Ursula@Example.com", "confirmed", 1).await;
+    app.test_user.login(&app).await;
+
+    let response = merge(&app, "ursula@example.com").await;
+    assert_is_redirect_to(&response, "/admin/subscribers/duplicates");
+
+    let html = app.get_html("/admin/subscribers/duplicates").await;
+    assert!(html.contains("Merged 1 subscriber into Ursula@Example.com"));
+    assert!(html.contains("No duplicates found"));
+    let subscriber = sqlx::query!("SELECT email, normalized_email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.email, "Ursula@Example.com");
+    assert_eq!(subscriber.normalized_email, "ursula@example.com");
+    assert_eq!(subscriber.status, "confirmed");
+
+    // New signups of either address find the kept subscriber.
+    when_confirmation_emails_are_sent(&app, 1).await;
+    subscribe(&app, "ursula@example.com").await;
+    assert_eq!(stored_emails(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn merging_moves_deliveries_to_the_kept_subscriber() {
+    let app = spawn_app().await;
+    insert_legacy_subscriber(&app, "ursula@example.com", "pending_confirmation", 10).await;
+    insert_legacy_subscriber(&app, "URSULA@example.com", "unsubscribed", 5).await;
+    insert_legacy_subscriber(&app, "Ursula@Example.com", "confirmed", 1).await;
+    app.test_user.login(&app).await;
+    let both = app.create_draft_issue().await;
+    let merged_only = app.create_draft_issue().await;
+    for (issue_id, email) in [
+        (both, "Ursula@Example.com"),
+        (both, "ursula@example.com"),
+        (both, "URSULA@example.com"),
+        (merged_only, "ursula@example.com"),
+        (merged_only, "URSULA@example.com"),
+    ] {
+        sqlx::query!(
+            "INSERT INTO issue_delivery_log VALUES ($1, $2, NOW())",
+            issue_id,
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    merge(&app, "ursula@example.com").await;
+
+    let mut deliveries = deliveries(&app).await;
+    deliveries.sort();
+    let mut expected = vec![
+        (both, "Ursula@Example.com".to_owned()),
+        (merged_only, "Ursula@Example.com".to_owned()),
+    ];
+    expected.sort();
+    assert_eq!(deliveries, expected);
+}
+
+#[tokio::test]
+async fn issues_are_not_sent_to_duplicates() {
+    let app = spawn_app().await;
+    let kept = insert_legacy_subscriber(&app, "Ursula@Example.com", "confirmed", 1).await;
+    let duplicate = insert_legacy_subscriber(&app, "ursula@example.com", "confirmed", 10).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET duplicate_of = $1 WHERE id = $2",
+        kept,
+        duplicate
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(
+        issue_id,
+        "/send",
+        &serde_json::json!({ "title": "Weekly", "markdown": "Hello" }),
+    )
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let delivered: Vec<String> = deliveries(&app).await.into_iter().map(|(_, e)| e).collect();
+    assert_eq!(delivered, ["Ursula@Example.com"]);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_duplicates() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers/duplicates", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,138 @@
+use email_newsletter::configuration::Settings;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+async fn subscribe(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_subscriptions_json(&serde_json::json!({ "name": "le guin", "email": email }))
+        .await
+}
+
+async fn email_error(response: reqwest::Response) -> String {
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "validation_failed");
+    body["fields"]["email"].as_str().unwrap().to_owned()
+}
+
+async fn block_domain(app: &TestApp, domain: &str) -> reqwest::Response {
+    app.post_email_policy(&serde_json::json!({ "domain": domain, "action": "add" }))
+        .await
+}
+
+async fn when_confirmation_emails_are_sent(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn disposable_addresses_are_refused() {
+    let app = spawn_app().await;
+
+    let error = email_error(subscribe(&app, "ursula@mailinator.com").await).await;
+
+    assert!(error.contains("Disposable"));
+}
+
+#[tokio::test]
+async fn role_accounts_are_refused() {
+    let app = spawn_app().await;
+
+    let error = email_error(subscribe(&app, "postmaster@earthsea.org").await).await;
+
+    assert!(error.contains("postmaster@"));
+}
+
+#[tokio::test]
+async fn the_policy_can_be_turned_off() {
+    let app = spawn_app_with(|c: &mut Settings| {
+        c.email_policy.block_disposable = false;
+        c.email_policy.block_role_accounts = false;
+    })
+    .await;
+    when_confirmation_emails_are_sent(&app).await;
+
+    assert_eq!(
+        subscribe(&app, "ursula@mailinator.com")
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+    assert_eq!(
+        subscribe(&app, "postmaster@earthsea.org")
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn admins_can_block_and_unblock_domains() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = block_domain(&app, "@Spam.Example").await;
+    assert_is_redirect_to(&response, "/admin/email-policy");
+    assert!(app
+        .get_html("/admin/email-policy")
+        .await
+        .contains("Addresses at spam.example can no longer subscribe"));
+
+    let error = email_error(subscribe(&app, "ursula@mail.spam.example").await).await;
+    assert!(error.contains("this domain"));
+
+    app.post_email_policy(&serde_json::json!({ "domain": "spam.example", "action": "remove" }))
+        .await;
+    assert_eq!(
+        subscribe(&app, "ursula@mail.spam.example")
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn invalid_domains_cannot_be_blocked() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    block_domain(&app, "not a domain").await;
+
+    let html = app.get_html("/admin/email-policy").await;
+    assert!(html.contains("not a domain is not a valid domain"));
+    assert!(html.contains("No domains blocked"));
+}
+
+#[tokio::test]
+async fn existing_subscribers_at_blocked_domains_are_flagged() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app).await;
+    subscribe(&app, "ursula@spam.example").await;
+    subscribe(&app, "ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    block_domain(&app, "spam.example").await;
+
+    let html = app.get_html("/admin/email-policy").await;
+    let flagged = &html[html.find("Flagged subscribers").unwrap()..];
+    assert!(flagged.contains("ursula@spam.example"));
+    assert!(flagged.contains("Email addresses at this domain cannot subscribe"));
+    assert!(!flagged.contains("ged@earthsea.org"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_the_policy() {
+    let app = spawn_app().await;
+
+    let response = block_domain(&app, "spam.example").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
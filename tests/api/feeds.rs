@@ -0,0 +1,85 @@
+use crate::archive::publish_issue;
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_rss_feed_carries_full_issues() {
+    let app = spawn_app().await;
+    let (issue_id, path) = publish_issue(&app, "Weekly & more", "Read all *about* it.").await;
+
+    let response = app.get_feed("/feed.xml", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let xml = response.text().await.unwrap();
+
+    assert!(xml.contains("<title>Weekly &amp; more</title>"));
+    assert!(xml.contains(&format!("<link>{}{}</link>", app.base_url, path)));
+    assert!(xml.contains(&format!(
+        r#"<guid isPermaLink="false">urn:uuid:{}</guid>"#,
+        issue_id
+    )));
+    assert!(xml.contains("<description>All the news that fits</description>"));
+    assert!(xml.contains("&lt;em&gt;about&lt;"));
+    // The hidden preheader only makes sense in an inbox.
+    assert!(!xml.contains("display: none"));
+
+    let pub_date = xml
+        .split("<pubDate>")
+        .nth(1)
+        .and_then(|rest| rest.split("</pubDate>").next())
+        .unwrap();
+    let pub_date = chrono::DateTime::parse_from_rfc2822(pub_date).unwrap();
+    assert!((chrono::Utc::now() - pub_date.to_utc()).num_minutes() < 5);
+}
+
+#[tokio::test]
+async fn the_atom_feed_carries_full_issues() {
+    let app = spawn_app().await;
+    let (issue_id, path) = publish_issue(&app, "Weekly", "Read all *about* it.").await;
+
+    let response = app.get_feed("/atom.xml", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let xml = response.text().await.unwrap();
+
+    assert!(xml.contains(&format!("<id>urn:uuid:{}</id>", issue_id)));
+    assert!(xml.contains(&format!(r#"<link href="{}{}"/>"#, app.base_url, path)));
+    assert!(xml.contains(r#"<content type="html">&lt;p&gt;Read all"#));
+}
+
+#[tokio::test]
+async fn feeds_readers_already_have_are_not_sent_again() {
+    let app = spawn_app().await;
+    publish_issue(&app, "Weekly", "Read all about it.").await;
+
+    for feed in ["/feed.xml", "/atom.xml"] {
+        let response = app.get_feed(feed, None).await;
+        let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+
+        let response = app.get_feed(feed, Some(&etag)).await;
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        assert!(response.text().await.unwrap().is_empty());
+
+        let response = app.get_feed(feed, Some("\"stale\"")).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn feeds_change_when_an_issue_goes_out() {
+    let app = spawn_app().await;
+    let response = app.get_feed("/feed.xml", None).await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+
+    publish_issue(&app, "Weekly", "Read all about it.").await;
+
+    let response = app.get_feed("/feed.xml", Some(&etag)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(response.headers()["etag"], etag.as_str());
+}
@@ -5,10 +5,15 @@ use once_cell::sync::Lazy;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 
-use email_newsletter::configuration::{get_configuration, DatabaseSettings};
+use email_newsletter::configuration::{
+    get_configuration, DatabaseSettings, Settings, SunsetSettings,
+};
+use email_newsletter::feed_sources::{feed_http_client, poll_feed_sources, PollOutcome};
 use email_newsletter::issue_delivery_queue::{try_execute_tasks, ExecutionOutcome};
 use email_newsletter::startup::{get_connection_pool, Application};
+use email_newsletter::sunset::{apply_sunset_policy, SunsetOutcome};
 use email_newsletter::telemetry::{get_subscriber, init_subscriber};
+use email_newsletter::tracking::Tracker;
 use wiremock::MockServer;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -31,12 +36,14 @@ pub struct ConfirmationLinks {
 
 pub struct TestApp {
     pub address: String,
+    pub base_url: String,
     pub port: u16,
     pub db_pool: PgPool,
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub tracker: Tracker,
     shutdown_tx: Option<tokio::sync::oneshot::Sender<()>>,
     server_task: tokio::task::JoinHandle<Result<(), std::io::Error>>,
 }
@@ -55,7 +62,7 @@ impl Drop for TestApp {
 impl TestApp {
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -63,6 +70,15 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -84,8 +100,8 @@ impl TestApp {
             confirmation_link
         };
 
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 
@@ -94,7 +110,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .form(body)
             .send()
@@ -102,9 +118,262 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn post_newsletter_preview<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/preview", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn get_newsletters_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Creates an empty draft and returns its id.
+    pub async fn create_draft_issue(&self) -> Uuid {
+        let response = self
+            .api_client
+            .post(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request");
+        let location = response.headers()["Location"].to_str().unwrap();
+        location
+            .trim_start_matches("/admin/issues/")
+            .parse()
+            .unwrap()
+    }
+
+    pub async fn get_issues_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/issues/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_html(&self, issue_id: Uuid) -> String {
+        self.get_issue(issue_id).await.text().await.unwrap()
+    }
+
+    /// Posts the editor form to `/admin/issues/{issue_id}{action}`, e.g. with
+    /// an empty action to save or `/autosave`, `/send`.
+    pub async fn post_issue<Body>(
+        &self,
+        issue_id: Uuid,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}{}",
+                &self.address, issue_id, action
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Follows a tracking link found in an email, e.g. `/t/o/{token}`, as a
+    /// mail client with the given user agent would.
+    pub async fn get_tracking_link(&self, path: &str, user_agent: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .header(reqwest::header::USER_AGENT, user_agent)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_analytics(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/analytics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_analytics_html(&self) -> String {
+        self.get_analytics().await.text().await.unwrap()
+    }
+
+    pub async fn get_analytics_export(&self, report: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/analytics/export/{}",
+                &self.address, report
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// A page of the public archive, e.g. `/archive?page=1`.
+    pub async fn get_archive(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_feed(&self, path: &str, if_none_match: Option<&str>) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}{}", &self.address, path));
+        if let Some(etag) = if_none_match {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    pub async fn get_subscribers_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_feed_sources<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/feeds", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_feed_sources_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/feeds", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_signup_form<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/signup-form", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_signup_form_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/signup-form", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_public_pages<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/public-pages", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_email_policy<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/email-policy", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_html(&self, path: &str) -> String {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_dead_letters(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dead-letters", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_dead_letters_html(&self) -> String {
+        self.get_dead_letters().await.text().await.unwrap()
+    }
+
+    pub async fn post_dead_letters<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .post(format!("{}/admin/dead-letters", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_revisions_html(&self, issue_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/issues/{}/revisions",
+                &self.address, issue_id
+            ))
             .send()
             .await
             .expect("Failed to execute request")
@@ -118,7 +387,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
@@ -127,7 +396,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -138,7 +407,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -154,7 +423,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -165,7 +434,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -174,7 +443,7 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -182,7 +451,7 @@ impl TestApp {
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
             if let ExecutionOutcome::EmptyQueue =
-                try_execute_tasks(&self.db_pool, &self.email_client)
+                try_execute_tasks(&self.db_pool, &self.email_client, &self.tracker)
                     .await
                     .unwrap()
             {
@@ -190,6 +459,26 @@ impl TestApp {
             }
         }
     }
+
+    /// Polls every feed source once.
+    pub async fn poll_feeds(&self) -> PollOutcome {
+        poll_feed_sources(&self.db_pool, &feed_http_client(), &self.base_url)
+            .await
+            .unwrap()
+    }
+
+    /// Runs the sunset policy once, for subscribers who left a single issue
+    /// unread and were given 14 days to answer.
+    pub async fn apply_sunset_policy(&self) -> SunsetOutcome {
+        let policy = SunsetSettings {
+            enabled: true,
+            inactive_issues: 1,
+            grace_days: 14,
+        };
+        apply_sunset_policy(&self.db_pool, &self.email_client, &self.tracker, &policy)
+            .await
+            .unwrap()
+    }
 }
 
 pub struct TestUser {
@@ -241,6 +530,12 @@ impl TestUser {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the application with the test configuration, changed by
+/// `configure`.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -250,6 +545,7 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        configure(&mut c);
 
         c
     };
@@ -282,12 +578,14 @@ pub async fn spawn_app() -> TestApp {
 
     let test_app = TestApp {
         address,
+        base_url: configuration.application.base_url.clone(),
         port: application_port,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        tracker: configuration.application.tracker(),
         shutdown_tx: Some(shutdown_tx),
         server_task,
     };
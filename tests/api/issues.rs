@@ -0,0 +1,749 @@
+use chrono::{Duration, Utc};
+use email_newsletter::issue_delivery_queue::try_execute_tasks;
+use email_newsletter::issue_scheduler::{
+    try_dispatch_due_issue, try_enqueue_due_wave, SchedulerOutcome,
+};
+use uuid::Uuid;
+use wiremock::matchers::body_partial_json;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+
+fn schedule_body(scheduled_at: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Tuesday issue",
+        "markdown": "See you on *Tuesday*",
+        "scheduled_at": scheduled_at,
+        "timezone": "Europe/Paris",
+    })
+}
+
+fn draft_body(title: &str, markdown: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": title,
+        "preheader": "",
+        "markdown": markdown,
+        "text": "",
+        "html": "",
+    })
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_issues() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/issues", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn drafts_are_listed_and_not_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let response = app
+        .post_issue(issue_id, "", &draft_body("Work in progress", "# Hello"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Draft saved"));
+    assert!(html_page.contains("# Hello"));
+
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("Work in progress"));
+    assert!(html_page.contains("draft"));
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn autosaves_update_the_draft_and_are_coalesced_in_the_history() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.create_draft_issue().await;
+
+    for markdown in ["H", "He", "Hello"] {
+        let response = app
+            .post_issue(issue_id, "/autosave", &draft_body("Title", markdown))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["saved_at"].is_string());
+    }
+
+    let saved = sqlx::query!(
+        "SELECT markdown_content FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.markdown_content.as_deref(), Some("Hello"));
+
+    let revisions = sqlx::query!(
+        "SELECT markdown_content FROM newsletter_issue_revisions WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0].markdown_content.as_deref(), Some("Hello"));
+}
+
+#[tokio::test]
+async fn an_earlier_revision_can_be_restored() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.create_draft_issue().await;
+
+    app.post_issue(issue_id, "", &draft_body("First", "Original wording"))
+        .await;
+    app.post_issue(issue_id, "", &draft_body("Second", "New wording"))
+        .await;
+
+    let first_revision = sqlx::query_scalar!(
+        r#"
+        SELECT revision_id
+        FROM newsletter_issue_revisions
+        WHERE newsletter_issue_id = $1
+        ORDER BY created_at ASC
+        LIMIT 1
+        "#,
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    let html_page = app.get_issue_revisions_html(issue_id).await;
+    assert!(html_page.contains(&first_revision.to_string()));
+
+    let diff_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/issues/{}/revisions/{}",
+            &app.address, issue_id, first_revision
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(diff_page.contains("- Original wording"));
+    assert!(diff_page.contains("+ New wording"));
+
+    let response = app
+        .post_issue(
+            issue_id,
+            &format!("/revisions/{}/restore", first_revision),
+            &serde_json::json!({}),
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Restored the revision"));
+    assert!(html_page.contains("Original wording"));
+}
+
+#[tokio::test]
+async fn sending_a_draft_delivers_it_and_freezes_it() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let response = app
+        .post_issue(issue_id, "/send", &draft_body("Launch", "We are **live**"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    app.dispatch_all_pending_emails().await;
+
+    let saved = sqlx::query!(
+        "SELECT status, html_content FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.status, "sent");
+    assert!(saved.html_content.contains("<strong>live</strong>"));
+
+    // Sent issues can no longer be changed, nor sent again.
+    let response = app
+        .post_issue(issue_id, "/autosave", &draft_body("Launch", "Edited"))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_issue(issue_id, "/send", &draft_body("Launch", "Edited"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("can no longer be edited"));
+}
+
+#[tokio::test]
+async fn editing_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_issue(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn issues_cannot_be_scheduled_in_the_past() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.create_draft_issue().await;
+
+    let response = app
+        .post_issue(issue_id, "/schedule", &schedule_body("2020-01-07T09:00"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("The scheduled time must be in the future"));
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "draft");
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_delivered_once_they_are_due() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let response = app
+        .post_issue(issue_id, "/schedule", &schedule_body("2099-01-06T09:00"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let saved = sqlx::query!(
+        "SELECT status, scheduled_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.status, "scheduled");
+    // 9:00 in Paris is 8:00 UTC in winter.
+    assert_eq!(
+        saved.scheduled_at.unwrap().to_rfc3339(),
+        "2099-01-06T08:00:00+00:00"
+    );
+
+    // Not due yet.
+    let outcome = try_dispatch_due_issue(&app.db_pool, &app.base_url)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, SchedulerOutcome::NothingDue));
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = NOW() WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let outcome = try_dispatch_due_issue(&app.db_pool, &app.base_url)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, SchedulerOutcome::IssueDispatched));
+    let outcome = try_dispatch_due_issue(&app.db_pool, &app.base_url)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, SchedulerOutcome::NothingDue));
+
+    app.dispatch_all_pending_emails().await;
+
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "sent");
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_turned_back_into_a_draft() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(issue_id, "/schedule", &schedule_body("2099-01-06T09:00"))
+        .await;
+
+    let response = app
+        .post_issue(issue_id, "/unschedule", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let saved = sqlx::query!(
+        "SELECT status, scheduled_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.status, "draft");
+    assert!(saved.scheduled_at.is_none());
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("the issue is a draft again"));
+}
+
+#[tokio::test]
+async fn local_time_issues_cannot_be_scheduled_for_a_time_some_subscribers_are_past() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET timezone = 'Pacific/Kiritimati'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+    let issue_id = app.create_draft_issue().await;
+
+    // An hour from now in Pago Pago was yesterday in Kiritimati.
+    let in_an_hour =
+        (Utc::now() + Duration::hours(1)).with_timezone(&chrono_tz::Pacific::Pago_Pago);
+    let mut body = schedule_body(&in_an_hour.format("%Y-%m-%dT%H:%M").to_string());
+    body["timezone"] = "Pacific/Pago_Pago".into();
+    body["local_time"] = "on".into();
+    let response = app.post_issue(issue_id, "/schedule", &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("It is already past that time in Pacific"));
+    assert!(html_page.contains("Kiritimati"));
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "draft");
+}
+
+#[tokio::test]
+async fn local_time_issues_go_out_in_timezone_waves() {
+    let app = spawn_app().await;
+    for timezone in ["Asia/Tokyo", "America/New_York"] {
+        create_confirmed_subscriber(&app).await;
+        sqlx::query!(
+            "UPDATE subscriptions SET timezone = $1 WHERE timezone IS NULL",
+            timezone
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let mut body = schedule_body("2099-01-06T08:00");
+    body["local_time"] = "on".into();
+    let response = app.post_issue(issue_id, "/schedule", &body).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    // The issue is due when the first timezone reaches 8:00.
+    let scheduled_at = sqlx::query_scalar!(
+        "SELECT scheduled_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        scheduled_at.unwrap().to_rfc3339(),
+        "2099-01-05T23:00:00+00:00"
+    );
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = NOW() WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let outcome = try_dispatch_due_issue(&app.db_pool, &app.base_url)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, SchedulerOutcome::IssueDispatched));
+
+    for timezone in ["Asia/Tokyo", "America/New_York"] {
+        // Waves go out one at a time, when their local time comes.
+        let outcome = try_enqueue_due_wave(&app.db_pool).await.unwrap();
+        assert!(matches!(outcome, SchedulerOutcome::NothingDue));
+        sqlx::query!(
+            "UPDATE issue_delivery_waves SET send_at = NOW() WHERE timezone = $1",
+            timezone
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        let outcome = try_enqueue_due_wave(&app.db_pool).await.unwrap();
+        assert!(matches!(outcome, SchedulerOutcome::WaveEnqueued));
+        app.dispatch_all_pending_emails().await;
+
+        let html_page = app.get_issue_html(issue_id).await;
+        assert!(html_page.contains("1 of 1 sent"));
+    }
+
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "sent");
+}
+
+#[tokio::test]
+async fn subscribers_who_move_between_waves_get_the_issue_once() {
+    let app = spawn_app().await;
+    for timezone in ["Asia/Tokyo", "America/New_York", "Australia/Sydney"] {
+        create_confirmed_subscriber(&app).await;
+        sqlx::query!(
+            "UPDATE subscriptions SET timezone = $1 WHERE timezone IS NULL",
+            timezone
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    // Confirms once the waves are planned.
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'pending_confirmation' WHERE timezone = 'Australia/Sydney'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let mut body = schedule_body("2099-01-06T08:00");
+    body["local_time"] = "on".into();
+    let response = app.post_issue(issue_id, "/schedule", &body).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = NOW() WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    try_dispatch_due_issue(&app.db_pool, &app.base_url)
+        .await
+        .unwrap();
+
+    let fire_wave = |timezone: &'static str| {
+        let app = &app;
+        async move {
+            sqlx::query!(
+                "UPDATE issue_delivery_waves SET send_at = NOW() WHERE timezone = $1",
+                timezone
+            )
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+            let outcome = try_enqueue_due_wave(&app.db_pool).await.unwrap();
+            assert!(matches!(outcome, SchedulerOutcome::WaveEnqueued));
+            app.dispatch_all_pending_emails().await;
+        }
+    };
+    fire_wave("Asia/Tokyo").await;
+
+    // The Tokyo subscriber moves to the wave that is still to come, the New
+    // York one to a timezone without a wave, and someone confirms in yet
+    // another timezone.
+    for (from, to) in [
+        ("America/New_York", "Europe/Paris"),
+        ("Asia/Tokyo", "America/New_York"),
+    ] {
+        sqlx::query!(
+            "UPDATE subscriptions SET timezone = $2 WHERE timezone = $1",
+            from,
+            to
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'confirmed' WHERE timezone = 'Australia/Sydney'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    fire_wave("America/New_York").await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("2 of 2 sent"));
+    let delivered = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM issue_delivery_log WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivered, Some(3));
+}
+
+#[tokio::test]
+async fn test_sends_are_personalized_and_leave_the_issue_alone() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let mut body = draft_body("News for {{ name | you }}", "Hi {{ name | there }}!");
+    body["test_recipients"] = format!("{}, stranger@example.com", subscriber.email).into();
+    let response = app.post_issue(issue_id, "/test", &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains(&format!(
+        "Test email sent to {}, stranger@example.com",
+        subscriber.email
+    )));
+
+    // Both recipients get the email the delivery worker would send them.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let sent: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .filter(|b: &serde_json::Value| b.get("Headers").is_some())
+        .collect();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0]["To"], subscriber.email.as_str());
+    assert_eq!(sent[0]["Subject"], format!("News for {}", subscriber.name));
+    assert!(sent[0]["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains(&format!("Hi {}!", subscriber.name)));
+    assert_eq!(sent[1]["Subject"], "News for you");
+    assert!(sent[1]["HtmlBody"].as_str().unwrap().contains("Hi there!"));
+    assert_eq!(sent[1]["Headers"][0]["Value"], issue_id.to_string());
+
+    // Nothing was queued and the issue is still a draft.
+    let queued = sqlx::query_scalar!("SELECT COUNT(*) FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued, Some(0));
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "draft");
+}
+
+#[tokio::test]
+async fn test_sends_to_invalid_addresses_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    let mut body = draft_body("Tuesday issue", "See you on *Tuesday*");
+    body["test_recipients"] = "me@example.com, not-an-address".into();
+    let response = app.post_issue(issue_id, "/test", &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("not-an-address is not a valid subscriber email"));
+}
+
+#[tokio::test]
+async fn paused_issues_are_not_delivered_until_resumed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(issue_id, "/send", &draft_body("Launch", "We are **live**"))
+        .await;
+    let response = app
+        .post_issue(issue_id, "/pause", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Sending paused - 0 of 2 emails sent so far"));
+
+    {
+        let _mock_guard = when_sending_an_email()
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_issue(issue_id, "/resume", &serde_json::json!({}))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("2 of 2 emails sent"));
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(status, "sent");
+}
+
+#[tokio::test]
+async fn cancelled_issues_are_not_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(issue_id, "/send", &draft_body("Launch", "We are **live**"))
+        .await;
+    let response = app
+        .post_issue(issue_id, "/cancel", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/issues/{}", issue_id));
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Sending cancelled - 0 of 2 emails sent, 2 will not go out"));
+    assert!(html_page.contains("2 cancelled"));
+
+    // A cancelled issue cannot be resumed.
+    app.post_issue(issue_id, "/resume", &serde_json::json!({}))
+        .await;
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Only a paused issue can be resumed"));
+}
+
+#[tokio::test]
+async fn the_issue_page_shows_delivery_progress() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let emails = sqlx::query_scalar!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .and(body_partial_json(serde_json::json!({ "To": emails[0] })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    when_sending_an_email()
+        .and(body_partial_json(serde_json::json!({ "To": emails[1] })))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(issue_id, "/send", &draft_body("Launch", "We are **live**"))
+        .await;
+    try_execute_tasks(&app.db_pool, &app.email_client, &app.tracker)
+        .await
+        .unwrap();
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("1 of 2 emails sent"));
+    assert!(html_page.contains("1 pending (1 retrying)"));
+    assert!(html_page.contains("Emails sent per minute"));
+    assert!(html_page.contains(&emails[1]));
+}
@@ -1,8 +1,25 @@
+mod ab_tests;
 mod admin_dashboard;
+mod analytics;
+mod archive;
 mod change_password;
+mod dead_letters;
+mod duplicate_subscribers;
+mod email_policy;
+mod feeds;
 mod health_check;
 mod helpers;
+mod issues;
 mod login;
 mod newsletter;
+mod public_pages;
+mod rate_limits;
+mod rss_to_email;
+mod signup_embed;
+mod signup_protection;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod sunset;
+mod tracking;
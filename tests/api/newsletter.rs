@@ -1,20 +1,21 @@
 use std::time::Duration;
 
 use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use email_newsletter::issue_delivery_queue::try_execute_tasks;
 use fake::faker::internet::en::SafeEmail;
 use fake::faker::name::en::Name;
 use fake::Fake;
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, MockBuilder, ResponseTemplate};
 
-fn when_sending_an_email() -> MockBuilder {
+pub fn when_sending_an_email() -> MockBuilder {
     Mock::given(path("/email")).and(method("POST"))
 }
 
 async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
     let name: String = Name().fake();
     let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
+    let body = serde_urlencoded::to_string(serde_json::json!({
         "name": name,
         "email": email,
     }))
@@ -27,7 +28,7 @@ async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
         .expect(1)
         .mount_as_scoped(&app.email_server)
         .await;
-    app.post_subscriptions(body.into())
+    app.post_subscriptions(body)
         .await
         .error_for_status()
         .unwrap();
@@ -40,10 +41,10 @@ async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
         .pop()
         .unwrap();
 
-    app.get_confirmation_links(&email_request)
+    app.get_confirmation_links(email_request)
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
+pub async fn create_confirmed_subscriber(app: &TestApp) {
     // We can then reuse the same helper and just add
     // an extra step to actually call the confirmation link!
     let confirmation_link = create_unconfirmed_subscriber(app).await;
@@ -111,6 +112,181 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     app.dispatch_all_pending_emails().await;
 }
 
+#[tokio::test]
+async fn failed_deliveries_are_kept_in_the_queue_for_a_retry() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "TITLE",
+        "text": "content",
+        "html": "<p>content</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_newsletters(&newsletter_request_body).await;
+
+    // The worker used to wait forever on the row lock its own task held.
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        try_execute_tasks(&app.db_pool, &app.email_client, &app.tracker),
+    )
+    .await
+    .expect("The delivery worker hung after a failed send")
+    .unwrap();
+
+    let task = sqlx::query!("SELECT attempt_count, error_message FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(task.attempt_count, 1);
+    assert!(task.error_message.is_some());
+}
+
+#[tokio::test]
+async fn newsletters_written_in_markdown_are_delivered_as_html_and_text() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let markdown = "# Weekly update\n\nRead [the post](https://example.com/post).";
+    let newsletter_request_body = serde_json::json!({
+        "title": "TITLE",
+        "markdown": markdown,
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let saved = sqlx::query!("SELECT markdown_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved newsletter issue");
+    assert_eq!(saved.markdown_content.as_deref(), Some(markdown));
+
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(html_body.contains("<h1>Weekly update</h1>"));
+    assert!(text_body.starts_with("Weekly update\n============="));
+    assert!(text_body.contains("[1] https://example.com/post"));
+}
+
+#[tokio::test]
+async fn plain_text_is_generated_when_only_html_is_provided() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "TITLE",
+        "html": "<h2>News</h2><p>Visit <a href=\"https://example.com\">our site</a></p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    // Subscribers' copies end with the link to their preferences.
+    let (text_body, _) = body["TextBody"]
+        .as_str()
+        .unwrap()
+        .split_once("\n\nManage your preferences:")
+        .unwrap();
+    assert_eq!(
+        text_body,
+        "News\n----\n\nVisit our site [1]\n\n[1] https://example.com"
+    );
+}
+
+#[tokio::test]
+async fn issue_html_is_made_email_safe_before_it_is_stored() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "TITLE",
+        "preheader": "This week in brief",
+        "html": "<html><head><style>p { color: red; }</style></head>\
+                 <body><p>Hello</p><script>alert(1)</script>\
+                 <a href=\"/archive\">Archive</a></body></html>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let saved = sqlx::query!("SELECT html_content, text_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved newsletter issue");
+    let html = saved.html_content;
+    assert!(html.contains(r#"<p style="color: red;">Hello</p>"#));
+    assert!(html.contains(r#"href="http://127.0.0.1/archive""#));
+    assert!(html.contains("This week in brief"));
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains("<style>"));
+    assert!(!saved.text_content.contains("This week in brief"));
+}
+
+#[tokio::test]
+async fn newsletter_preview_renders_markdown() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletter_preview(&serde_json::json!({
+            "markdown": "Some **bold** text <script>alert(1)</script>",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let rendered: serde_json::Value = response.json().await.unwrap();
+    let html = rendered["html"].as_str().unwrap();
+    let text = rendered["text"].as_str().unwrap();
+    assert!(html.contains("<strong>bold</strong>"));
+    assert!(!html.contains("<script>"));
+    assert!(text.starts_with("Some bold text"));
+}
+
 #[tokio::test]
 async fn newsletters_returns_400_for_invalid_data() {
     let app = spawn_app().await;
@@ -0,0 +1,154 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+fn theme() -> serde_json::Value {
+    serde_json::json!({
+        "newsletter_name": "The Lathe of Heaven",
+        "logo_url": "",
+        "background_color": "#FDF6E3",
+        "text_color": "#333333",
+        "accent_color": "#1d4ed8",
+        "signup_copy": "Dreams, weekly.",
+        "check_inbox_copy": "Look for our email.",
+        "confirmed_copy": "Welcome aboard.",
+        "unsubscribed_copy": "Farewell.",
+    })
+}
+
+async fn save_theme(app: &TestApp, theme: &serde_json::Value) -> reqwest::Response {
+    app.test_user.login(app).await;
+    app.post_public_pages(theme).await
+}
+
+#[tokio::test]
+async fn the_signup_page_posts_to_the_subscriptions_endpoint() {
+    let app = spawn_app().await;
+
+    let html = app.get_html("/").await;
+
+    assert!(html.contains("Our newsletter"));
+    assert!(html.contains(r#"action="/subscriptions""#));
+    assert!(html.contains(&format!("{}/subscriptions/check-inbox", app.base_url)));
+}
+
+#[tokio::test]
+async fn signing_up_from_the_page_leads_to_check_your_inbox() {
+    let app = spawn_app().await;
+    save_theme(&app, &theme()).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&success_url={}%2Fsubscriptions%2Fcheck-inbox",
+            app.base_url
+        ))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("{}/subscriptions/check-inbox", app.base_url),
+    );
+
+    let html = app.get_html("/subscriptions/check-inbox").await;
+    assert!(html.contains("Check your inbox"));
+    assert!(html.contains("Look for our email."));
+}
+
+#[tokio::test]
+async fn failed_signups_are_explained_on_the_signup_page() {
+    let app = spawn_app().await;
+
+    let html = app
+        .get_html("/?error=validation_failed&field=email&field=name")
+        .await;
+
+    assert!(html.contains("Please check your email address and name"));
+}
+
+#[tokio::test]
+async fn confirming_shows_the_confirmed_page() {
+    let app = spawn_app().await;
+    save_theme(&app, &theme()).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("You&#x27;re subscribed!"));
+    assert!(html.contains("Welcome aboard."));
+}
+
+#[tokio::test]
+async fn unknown_tokens_show_the_invalid_link_page() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        app.address,
+        "a".repeat(25)
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("This link is not valid"));
+}
+
+#[tokio::test]
+async fn pages_use_the_saved_theme() {
+    let app = spawn_app().await;
+
+    let response = save_theme(&app, &theme()).await;
+    assert_is_redirect_to(&response, "/admin/public-pages");
+
+    let html = app.get_html("/").await;
+    assert!(html.contains("The Lathe of Heaven"));
+    assert!(html.contains("Dreams, weekly."));
+    assert!(html.contains("background-color: #fdf6e3;"));
+    assert!(app
+        .get_html("/admin/public-pages")
+        .await
+        .contains("Public pages updated"));
+}
+
+#[tokio::test]
+async fn invalid_themes_are_rejected() {
+    let app = spawn_app().await;
+    let mut theme = theme();
+    theme["accent_color"] = "red; } body { display: none".into();
+
+    save_theme(&app, &theme).await;
+
+    assert!(app
+        .get_html("/admin/public-pages")
+        .await
+        .contains("The accent color must look like #1a2b3c"));
+    assert!(app.get_html("/").await.contains("Our newsletter"));
+}
+
+#[tokio::test]
+async fn the_theme_is_admin_only() {
+    let app = spawn_app().await;
+
+    let response = app.post_public_pages(&theme()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,175 @@
+use std::num::NonZeroU32;
+
+use email_newsletter::configuration::{RateLimit, Settings};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app_with, TestApp};
+
+fn limit(requests: u32) -> RateLimit {
+    RateLimit {
+        requests,
+        window_seconds: NonZeroU32::new(3600).unwrap(),
+    }
+}
+
+async fn subscribe_from(app: &TestApp, forwarded_for: &str, body: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-For", forwarded_for)
+        .body(body.to_owned())
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+fn assert_is_rate_limited(response: &reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=3600).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn subscribing_too_often_from_one_address_is_refused() {
+    let app = spawn_app_with(|c: &mut Settings| c.rate_limit.subscribe_per_ip = limit(2)).await;
+
+    for _ in 0..2 {
+        let response = app.post_subscriptions("name=le%20guin&email=".into()).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+    let response = app.post_subscriptions("name=le%20guin&email=".into()).await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn one_email_cannot_be_signed_up_over_and_over() {
+    let app = spawn_app_with(|c: &mut Settings| c.rate_limit.subscribe_per_email = limit(1)).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let first = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let again = app
+        .post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into())
+        .await;
+    let other = app
+        .post_subscriptions("name=le%20guin&email=ged%40earthsea.org".into())
+        .await;
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_is_rate_limited(&again);
+    assert_eq!(other.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn clients_behind_a_trusted_proxy_are_told_apart() {
+    let app = spawn_app_with(|c: &mut Settings| {
+        c.rate_limit.subscribe_per_ip = limit(1);
+        c.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+
+    let first = subscribe_from(&app, "198.51.100.1", "name=le%20guin&email=").await;
+    let other = subscribe_from(&app, "198.51.100.2", "name=le%20guin&email=").await;
+    let again = subscribe_from(&app, "203.0.113.9, 198.51.100.1", "name=le%20guin&email=").await;
+
+    assert_eq!(first.status().as_u16(), 400);
+    assert_eq!(other.status().as_u16(), 400);
+    assert_is_rate_limited(&again);
+}
+
+#[tokio::test]
+async fn forwarded_addresses_from_untrusted_peers_are_ignored() {
+    let app = spawn_app_with(|c: &mut Settings| c.rate_limit.subscribe_per_ip = limit(1)).await;
+
+    subscribe_from(&app, "198.51.100.1", "name=le%20guin&email=").await;
+    let response = subscribe_from(&app, "198.51.100.2", "name=le%20guin&email=").await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn confirmation_attempts_are_limited() {
+    let app = spawn_app_with(|c: &mut Settings| c.rate_limit.confirm_per_ip = limit(1)).await;
+    let confirm = || {
+        app.api_client
+            .get(format!("{}/subscriptions/confirm", &app.address))
+            .send()
+    };
+
+    assert_eq!(confirm().await.unwrap().status().as_u16(), 400);
+    assert_is_rate_limited(&confirm().await.unwrap());
+}
+
+#[tokio::test]
+async fn login_attempts_are_limited_per_address() {
+    let app = spawn_app_with(|c: &mut Settings| c.rate_limit.login_per_ip = limit(1)).await;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+
+    app.post_login(&login_body).await;
+    let response = app.post_login(&login_body).await;
+
+    assert_is_rate_limited(&response);
+    // The login page itself stays up.
+    assert_eq!(
+        app.api_client
+            .get(format!("{}/login", &app.address))
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn login_attempts_are_limited_per_username() {
+    let app = spawn_app_with(|c: &mut Settings| {
+        c.rate_limit.login_per_username = limit(1);
+        c.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+    let login_from = |forwarded_for: &'static str| {
+        app.api_client
+            .post(format!("{}/login", &app.address))
+            .header("X-Forwarded-For", forwarded_for)
+            .form(&serde_json::json!({
+                "username": &app.test_user.username,
+                "password": "wrong-password",
+            }))
+            .send()
+    };
+
+    login_from("198.51.100.1").await.unwrap();
+    let response = login_from("198.51.100.2").await.unwrap();
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn limits_can_be_turned_off() {
+    let app = spawn_app_with(|c: &mut Settings| {
+        c.rate_limit.enabled = false;
+        c.rate_limit.subscribe_per_ip = limit(0);
+    })
+    .await;
+
+    let response = app.post_subscriptions("name=le%20guin&email=".into()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
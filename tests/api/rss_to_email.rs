@@ -0,0 +1,271 @@
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+use email_newsletter::feed_sources::PollOutcome;
+
+/// An RSS feed with the given `(guid, title)` posts, latest first.
+fn rss(posts: &[(&str, &str)]) -> String {
+    let items: String = posts
+        .iter()
+        .enumerate()
+        .map(|(i, (guid, title))| {
+            format!(
+                r#"<item>
+                    <guid isPermaLink="false">{guid}</guid>
+                    <title>{title}</title>
+                    <link>https://blog.example.com/{guid}</link>
+                    <description>&lt;p&gt;All about &lt;em&gt;{title}&lt;/em&gt;.&lt;/p&gt;</description>
+                    <pubDate>Mon, {day:02} Oct 2026 09:00:00 +0000</pubDate>
+                </item>"#,
+                day = 28 - i,
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+        <rss version="2.0"><channel>
+            <title>Our blog</title>
+            <link>https://blog.example.com</link>
+            <description>News</description>
+            {items}
+        </channel></rss>"#
+    )
+}
+
+/// Serves `posts` as the feed, in place of whatever was served before.
+async fn serve_feed(feed_server: &MockServer, posts: &[(&str, &str)]) {
+    feed_server.reset().await;
+    Mock::given(method("GET"))
+        .and(path("/feed.xml"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Content-Type", "application/rss+xml")
+                .set_body_string(rss(posts)),
+        )
+        .mount(feed_server)
+        .await;
+}
+
+/// Adds the feed of `feed_server` and polls it for the first time.
+async fn add_feed(app: &TestApp, feed_server: &MockServer, publish_mode: &str, digest_size: &str) {
+    app.test_user.login(app).await;
+    let response = app
+        .post_feed_sources(&serde_json::json!({
+            "name": "Our blog",
+            "url": format!("{}/feed.xml", feed_server.uri()),
+            "publish_mode": publish_mode,
+            "digest_size": digest_size,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    app.poll_feeds().await;
+}
+
+struct StoredIssue {
+    title: String,
+    status: String,
+    markdown_content: Option<String>,
+}
+
+async fn stored_issues(app: &TestApp) -> Vec<StoredIssue> {
+    sqlx::query_as!(
+        StoredIssue,
+        "SELECT title, status, markdown_content FROM newsletter_issues ORDER BY created_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn posts_already_in_the_feed_are_skipped() {
+    let app = spawn_app().await;
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, &[("2", "Second post"), ("1", "First post")]).await;
+
+    add_feed(&app, &feed_server, "draft", "").await;
+
+    assert!(stored_issues(&app).await.is_empty());
+    let html = app.get_feed_sources_html().await;
+    assert!(html.contains("Our blog"));
+    assert!(!html.contains("Never"));
+}
+
+#[tokio::test]
+async fn new_posts_become_drafts_once() {
+    let app = spawn_app().await;
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, &[("1", "First post")]).await;
+    add_feed(&app, &feed_server, "draft", "").await;
+
+    serve_feed(&feed_server, &[("2", "Second post"), ("1", "First post")]).await;
+    let outcome = app.poll_feeds().await;
+    assert_eq!(
+        outcome,
+        PollOutcome {
+            new_posts: 1,
+            issues: 1
+        }
+    );
+    assert_eq!(app.poll_feeds().await, PollOutcome::default());
+
+    let issues = stored_issues(&app).await;
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].title, "Second post");
+    assert_eq!(issues[0].status, "draft");
+    assert_eq!(
+        issues[0].markdown_content.as_deref(),
+        Some(
+            "## [Second post](https://blog.example.com/2)\n\nAll about Second post.\n\n\
+             [Read the post](https://blog.example.com/2)"
+        )
+    );
+}
+
+#[tokio::test]
+async fn published_posts_go_out_to_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, &[]).await;
+    add_feed(&app, &feed_server, "publish", "").await;
+
+    serve_feed(&feed_server, &[("1", "Launch day")]).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.poll_feeds().await;
+    app.dispatch_all_pending_emails().await;
+
+    let issues = stored_issues(&app).await;
+    assert_eq!(issues[0].status, "sent");
+    let requests = app.email_server.received_requests().await.unwrap();
+    let request = requests.last().unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(email["Subject"], "Launch day");
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("All about Launch day."));
+}
+
+#[tokio::test]
+async fn digests_bundle_the_latest_posts_once_a_week() {
+    let app = spawn_app().await;
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, &[]).await;
+    add_feed(&app, &feed_server, "draft", "2").await;
+
+    serve_feed(
+        &feed_server,
+        &[("3", "Third"), ("2", "Second"), ("1", "First")],
+    )
+    .await;
+    let outcome = app.poll_feeds().await;
+    assert_eq!(outcome.new_posts, 3);
+    assert_eq!(outcome.issues, 0);
+
+    sqlx::query!("UPDATE feed_sources SET last_digest_at = last_digest_at - interval '7 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(app.poll_feeds().await.issues, 1);
+    assert_eq!(app.poll_feeds().await.issues, 0);
+
+    let issues = stored_issues(&app).await;
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].title, "Third");
+    let markdown = issues[0].markdown_content.as_deref().unwrap();
+    assert!(markdown.contains("[Third]") && markdown.contains("[Second]"));
+    assert!(!markdown.contains("[First]"));
+}
+
+#[tokio::test]
+async fn unchanged_feeds_are_not_downloaded_again() {
+    let app = spawn_app().await;
+    let feed_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/feed.xml"))
+        .and(header("If-None-Match", r#""v1""#))
+        .respond_with(ResponseTemplate::new(304))
+        .expect(1)
+        .with_priority(1)
+        .mount(&feed_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/feed.xml"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", r#""v1""#)
+                .set_body_string(rss(&[("1", "First post")])),
+        )
+        .expect(1)
+        .mount(&feed_server)
+        .await;
+    add_feed(&app, &feed_server, "draft", "").await;
+
+    assert_eq!(app.poll_feeds().await, PollOutcome::default());
+}
+
+#[tokio::test]
+async fn feeds_that_fail_show_the_error() {
+    let app = spawn_app().await;
+    let feed_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&feed_server)
+        .await;
+
+    add_feed(&app, &feed_server, "draft", "").await;
+
+    let html = app.get_feed_sources_html().await;
+    assert!(html.contains("Failed to fetch the feed"));
+    assert!(html.contains("Never"));
+}
+
+#[tokio::test]
+async fn invalid_and_duplicate_feeds_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let feed = serde_json::json!({
+        "name": "Our blog",
+        "url": "https://blog.example.com/feed.xml",
+        "publish_mode": "draft",
+        "digest_size": "",
+    });
+    app.post_feed_sources(&feed).await;
+
+    let response = app.post_feed_sources(&feed).await;
+    assert_is_redirect_to(&response, "/admin/feeds");
+    assert!(app
+        .get_feed_sources_html()
+        .await
+        .contains("This feed was already added"));
+
+    let mut feed = feed;
+    feed["url"] = "not a url".into();
+    app.post_feed_sources(&feed).await;
+    assert!(app
+        .get_feed_sources_html()
+        .await
+        .contains("The feed URL must be an http or https URL"));
+}
+
+#[tokio::test]
+async fn feeds_are_admin_only() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_feed_sources(&serde_json::json!({
+            "name": "Our blog",
+            "url": "https://blog.example.com/feed.xml",
+            "publish_mode": "draft",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
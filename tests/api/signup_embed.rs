@@ -0,0 +1,218 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+const SITE: &str = "https://www.example.com";
+
+async fn allow_site(app: &TestApp, origin: &str) {
+    app.test_user.login(app).await;
+    app.post_signup_form(&serde_json::json!({ "origin": origin, "action": "add" }))
+        .await;
+}
+
+async fn preflight(app: &TestApp, origin: &str) -> reqwest::Response {
+    app.api_client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/subscriptions", &app.address),
+        )
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn subscriber_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn allowed_sites_pass_the_preflight_check() {
+    let app = spawn_app().await;
+    allow_site(&app, "https://www.example.com/newsletter").await;
+
+    let response = preflight(&app, SITE).await;
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://www.example.com"
+    );
+}
+
+#[tokio::test]
+async fn other_sites_get_no_cors_headers() {
+    let app = spawn_app().await;
+    allow_site(&app, SITE).await;
+
+    let preflight = preflight(&app, "https://evil.example.net").await;
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Origin", "https://evil.example.net")
+        .json(&serde_json::json!({ "name": "le guin" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(preflight
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn allowed_sites_can_fetch_a_signup_challenge() {
+    let app = spawn_app().await;
+    allow_site(&app, SITE).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/subscriptions/challenge", &app.address))
+        .header("Origin", SITE)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["access-control-allow-origin"], SITE);
+}
+
+#[tokio::test]
+async fn forms_on_allowed_sites_redirect_to_their_success_page() {
+    let app = spawn_app().await;
+    allow_site(&app, SITE).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com\
+            &success_url=https%3A%2F%2Fwww.example.com%2Fthanks\
+            &error_url=https%3A%2F%2Fwww.example.com%2Foops"
+                .into(),
+        )
+        .await;
+
+    assert_is_redirect_to(&response, "https://www.example.com/thanks");
+    assert_eq!(subscriber_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn failed_signups_redirect_to_the_error_page_with_the_reason() {
+    let app = spawn_app().await;
+    allow_site(&app, SITE).await;
+
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=not-an-email\
+            &error_url=https%3A%2F%2Fwww.example.com%2Foops%3Fform%3Dfooter"
+                .into(),
+        )
+        .await;
+
+    assert_is_redirect_to(
+        &response,
+        "https://www.example.com/oops?form=footer&error=validation_failed&field=email",
+    );
+}
+
+#[tokio::test]
+async fn redirects_to_other_sites_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com\
+            &success_url=https%3A%2F%2Fevil.example.net%2F"
+                .into(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn the_hosted_form_and_its_script_are_served() {
+    let app = spawn_app().await;
+
+    let page = app
+        .api_client
+        .get(format!("{}/subscriptions/embed", &app.address))
+        .send()
+        .await
+        .unwrap();
+    let script = app
+        .api_client
+        .get(format!("{}/subscriptions/embed.js", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(page.status().as_u16(), 200);
+    assert!(page
+        .text()
+        .await
+        .unwrap()
+        .contains("data-newsletter-signup"));
+    assert_eq!(script.status().as_u16(), 200);
+    assert!(script.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/javascript"));
+    assert!(script
+        .text()
+        .await
+        .unwrap()
+        .contains(&format!("\"{}/subscriptions\"", app.base_url)));
+}
+
+#[tokio::test]
+async fn sites_can_be_removed_and_invalid_ones_are_rejected() {
+    let app = spawn_app().await;
+    allow_site(&app, "https://WWW.example.com:443/").await;
+    assert!(app.get_signup_form_html().await.contains(SITE));
+
+    let response = app
+        .post_signup_form(&serde_json::json!({ "origin": "example.com", "action": "add" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/signup-form");
+    assert!(app
+        .get_signup_form_html()
+        .await
+        .contains("example.com is not a valid URL"));
+
+    app.post_signup_form(&serde_json::json!({ "origin": SITE, "action": "remove" }))
+        .await;
+    let response = preflight(&app, SITE).await;
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn allowed_sites_are_admin_only() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_signup_form(&serde_json::json!({ "origin": SITE, "action": "add" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,327 @@
+use chrono::Utc;
+use email_newsletter::configuration::{CaptchaProvider, CaptchaSettings, Settings};
+use secrecy::Secret;
+use sha2::{Digest, Sha256};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+const SIGNUP: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+/// A token for a form served long enough ago to pass the fill time check.
+fn form_token(app: &TestApp) -> String {
+    app.tracker
+        .signup_form_token(Utc::now() - chrono::Duration::seconds(10))
+}
+
+fn solve_proof_of_work(token: &str, bits: u32) -> String {
+    (0u64..)
+        .map(|n| n.to_string())
+        .find(|nonce| {
+            let hash = Sha256::digest(format!("{}:{}", token, nonce).as_bytes());
+            let value = u32::from_be_bytes(hash[..4].try_into().unwrap());
+            value.leading_zeros() >= bits
+        })
+        .unwrap()
+}
+
+async fn when_confirmation_emails_are_sent(app: &TestApp, emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn subscriber_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+fn protected(c: &mut Settings) {
+    c.signup_protection.enabled = true;
+}
+
+fn with_proof_of_work(c: &mut Settings) {
+    protected(c);
+    c.signup_protection.proof_of_work_bits = 8;
+}
+
+async fn get_challenge(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/subscriptions/challenge", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+fn with_captcha(app_verify_url: String) -> impl FnOnce(&mut Settings) {
+    move |c: &mut Settings| {
+        protected(c);
+        c.signup_protection.captcha = Some(CaptchaSettings {
+            provider: CaptchaProvider::HCaptcha,
+            site_key: "site-key".into(),
+            secret_key: Secret::new("secret-key".into()),
+            verify_url: Some(app_verify_url),
+        })
+    }
+}
+
+#[tokio::test]
+async fn filling_in_the_honeypot_looks_like_success_but_signs_nobody_up() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app, 0).await;
+
+    let response = app
+        .post_subscriptions(format!("{}&website=https%3A%2F%2Fspam.example", SIGNUP))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn signups_without_a_form_token_are_rejected() {
+    let app = spawn_app_with(protected).await;
+
+    let response = app.post_subscriptions(SIGNUP.into()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.text().await.unwrap().contains("reload the page"));
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn forms_sent_too_fast_are_rejected() {
+    let app = spawn_app_with(protected).await;
+    let token = app.tracker.signup_form_token(Utc::now());
+
+    let response = app
+        .post_subscriptions(format!("{}&form_token={}", SIGNUP, token))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Please take a moment"));
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn forms_filled_in_at_human_speed_are_accepted() {
+    let app = spawn_app_with(protected).await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+
+    let response = app
+        .post_subscriptions(format!("{}&form_token={}", SIGNUP, form_token(&app)))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn form_tokens_can_only_be_used_once() {
+    let app = spawn_app_with(protected).await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+    let body = format!("{}&form_token={}", SIGNUP, form_token(&app));
+
+    let first = app.post_subscriptions(body.clone()).await;
+    let replayed = app.post_subscriptions(body).await;
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(replayed.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn forged_form_tokens_are_rejected() {
+    let app = spawn_app_with(protected).await;
+
+    let response = app
+        .post_subscriptions(format!("{}&form_token=bm9wZQ.bm9wZQ", SIGNUP))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn a_proof_of_work_is_needed_when_configured() {
+    let app = spawn_app_with(with_proof_of_work).await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+    let token = form_token(&app);
+
+    let without = app
+        .post_subscriptions(format!("{}&form_token={}", SIGNUP, token))
+        .await;
+    let wrong = app
+        .post_subscriptions(format!("{}&form_token={}&proof_of_work=x", SIGNUP, token))
+        .await;
+    let nonce = solve_proof_of_work(&token, 8);
+    let solved = app
+        .post_subscriptions(format!(
+            "{}&form_token={}&proof_of_work={}",
+            SIGNUP, token, nonce
+        ))
+        .await;
+
+    assert_eq!(without.status().as_u16(), 400);
+    assert!(without.text().await.unwrap().contains("JavaScript"));
+    assert_eq!(wrong.status().as_u16(), 400);
+    assert_eq!(solved.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn proofs_of_work_cannot_be_sent_again() {
+    let app = spawn_app_with(with_proof_of_work).await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+    let token = form_token(&app);
+    let nonce = solve_proof_of_work(&token, 8);
+    let body = format!("{}&form_token={}&proof_of_work={}", SIGNUP, token, nonce);
+
+    let first = app.post_subscriptions(body.clone()).await;
+    let replayed = app.post_subscriptions(body).await;
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(replayed.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn json_signups_pass_with_a_challenge_from_the_api() {
+    let app = spawn_app_with(|c: &mut Settings| {
+        with_proof_of_work(c);
+        c.signup_protection.min_fill_seconds = 0;
+    })
+    .await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+
+    let response = get_challenge(&app).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["cache-control"], "no-store");
+    let challenge: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(challenge["proof_of_work_bits"], 8);
+    assert!(challenge["captcha"].is_null());
+    let token = challenge["form_token"].as_str().unwrap();
+
+    let without = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+    let solved = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "form_token": token,
+            "proof_of_work": solve_proof_of_work(token, 8),
+        }))
+        .await;
+
+    assert_eq!(without.status().as_u16(), 400);
+    assert_eq!(solved.status().as_u16(), 200);
+    assert_eq!(subscriber_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn the_challenge_names_the_captcha_to_show() {
+    let app = spawn_app_with(with_captcha("http://localhost/siteverify".into())).await;
+
+    let challenge: serde_json::Value = get_challenge(&app).await.json().await.unwrap();
+
+    assert_eq!(challenge["captcha"]["site_key"], "site-key");
+    assert_eq!(challenge["captcha"]["widget_class"], "h-captcha");
+}
+
+#[tokio::test]
+async fn signups_are_not_checked_unless_protection_is_turned_on() {
+    let app = spawn_app().await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+
+    let response = app.post_subscriptions(SIGNUP.into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn captcha_answers_are_checked_with_the_provider() {
+    let verify_server = wiremock::MockServer::start().await;
+    let app = spawn_app_with(with_captcha(format!("{}/siteverify", verify_server.uri()))).await;
+    when_confirmation_emails_are_sent(&app, 1).await;
+    Mock::given(path("/siteverify"))
+        .and(wiremock::matchers::body_string_contains("response=good"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({ "success": true })),
+        )
+        .mount(&verify_server)
+        .await;
+    Mock::given(path("/siteverify"))
+        .and(wiremock::matchers::body_string_contains("response=bad"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({ "success": false })),
+        )
+        .mount(&verify_server)
+        .await;
+
+    let token = form_token(&app);
+
+    let missing = app
+        .post_subscriptions(format!("{}&form_token={}", SIGNUP, token))
+        .await;
+    let bad = app
+        .post_subscriptions(format!(
+            "{}&form_token={}&h-captcha-response=bad",
+            SIGNUP, token
+        ))
+        .await;
+    let good = app
+        .post_subscriptions(format!(
+            "{}&form_token={}&h-captcha-response=good",
+            SIGNUP, token
+        ))
+        .await;
+
+    assert_eq!(missing.status().as_u16(), 400);
+    assert_eq!(bad.status().as_u16(), 400);
+    assert_eq!(good.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn json_signups_report_failed_checks() {
+    let app = spawn_app_with(protected).await;
+    let token = app.tracker.signup_form_token(Utc::now());
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "form_token": token,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "bot_check_failed");
+}
+
+#[tokio::test]
+async fn signup_forms_carry_the_challenge() {
+    let verify_server = wiremock::MockServer::start().await;
+    let app = spawn_app_with(with_captcha(verify_server.uri())).await;
+
+    for html in [
+        app.get_html("/").await,
+        app.get_html("/subscriptions/embed").await,
+    ] {
+        assert!(html.contains(r#"name="form_token""#));
+        assert!(html.contains(r#"name="website""#));
+        assert!(html.contains(r#"class="h-captcha" data-sitekey="site-key""#));
+    }
+    let script = app.get_html("/subscriptions/embed.js").await;
+    assert!(script.contains(r#""formToken":"#));
+    assert!(script.contains(r#""siteKey":"site-key""#));
+}
@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
@@ -44,6 +44,32 @@ async fn subscribe_persists_the_new_subscriber() {
     assert_eq!(saved.status, "pending_confirmation");
 }
 
+#[tokio::test]
+async fn subscribe_persists_the_subscriber_timezone() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&timezone=America%2FLos_Angeles";
+    app.post_subscriptions(body.into()).await;
+    let body = "name=tolkien&email=tolkien%40gmail.com&timezone=Middle%20Earth";
+    let response = app.post_subscriptions(body.into()).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let saved = sqlx::query!("SELECT email, timezone FROM subscriptions ORDER BY email;")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions");
+
+    assert_eq!(saved[0].email, "tolkien@gmail.com");
+    assert_eq!(saved[0].timezone, None);
+    assert_eq!(saved[1].timezone.as_deref(), Some("America/Los_Angeles"));
+}
+
 #[tokio::test]
 async fn subscribe_returns_a_400_when_data_is_missing() {
     let app = spawn_app().await;
@@ -119,11 +145,54 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
 
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
 
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
 }
 
+#[tokio::test]
+async fn the_confirmation_email_starts_with_a_preheader() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+
+    assert!(html_body.contains(
+        "<body style=\"font-family: Arial, sans-serif; line-height: 1.6; color: #333;\">\
+         <div style=\"display: none; max-height: 0; overflow: hidden; mso-hide: all;\">\
+         One click to confirm your subscription</div>"
+    ));
+}
+
+#[tokio::test]
+async fn subscribe_fails_if_the_confirmation_email_cannot_be_prepared() {
+    // An unparseable base URL makes the links in the email impossible to
+    // resolve, which used to panic the handler.
+    let app = spawn_app_with(|c| c.application.base_url = "not a url".into()).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 500);
+}
+
 #[tokio::test]
 async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     let app = spawn_app().await;
@@ -138,3 +207,137 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn concurrent_signups_for_the_same_address_store_one_subscriber() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let responses = tokio::join!(
+        app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into()),
+        app.post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into()),
+        app.post_subscriptions("name=le%20guin&email=URSULA_LE_GUIN%40gmail.com".into()),
+    );
+
+    for response in [responses.0, responses.1, responses.2] {
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers, 1);
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "timezone": "Europe/Paris",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["message"].is_string());
+    let saved = sqlx::query!("SELECT email, name, timezone FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.timezone.as_deref(), Some("Europe/Paris"));
+}
+
+#[tokio::test]
+async fn json_signups_get_an_error_for_each_invalid_field() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "email": "definitely-not-an-email",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "validation_failed");
+    assert_eq!(
+        body["fields"]["email"],
+        "definitely-not-an-email is not a valid subscriber email"
+    );
+    assert_eq!(body["fields"]["name"], "The name is required");
+}
+
+#[tokio::test]
+async fn malformed_json_gets_a_json_error() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/json")
+        .body("{\"email\": ")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "invalid_body");
+}
+
+#[tokio::test]
+async fn forms_that_accept_json_get_json_errors() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "application/json")
+        .body("name=&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["fields"]["name"], "The name is required");
+    assert!(body["fields"].get("email").is_none());
+}
+
+#[tokio::test]
+async fn json_clients_are_not_shown_internal_errors() {
+    let app = spawn_app().await;
+    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "internal_error");
+    assert!(!body["message"].as_str().unwrap().contains("email"));
+}
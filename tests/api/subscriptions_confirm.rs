@@ -3,7 +3,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, ConfirmationLinks, TestApp};
 
 #[tokio::test]
 async fn subscribe_without_token_are_rejected_with_a_400() {
@@ -51,7 +51,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 
     app.post_subscriptions(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
     reqwest::get(confirmation_links.html)
@@ -70,3 +70,56 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+/// Signs up the same person every time and returns the link of the
+/// confirmation email they got.
+async fn sign_up(app: &TestApp) -> ConfirmationLinks {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    app.get_confirmation_links(&email_request.unwrap())
+}
+
+#[tokio::test]
+async fn confirmation_links_only_work_once() {
+    let app = spawn_app().await;
+    let confirmation_links = sign_up(&app).await;
+
+    let first = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+    let second = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_who_sign_up_again_are_back_on_the_list() {
+    let app = spawn_app().await;
+    let confirmation_links = sign_up(&app).await;
+    reqwest::get(confirmation_links.html).await.unwrap();
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed', unsubscribed_at = NOW()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let confirmation_links = sign_up(&app).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    assert!(saved.unsubscribed_at.is_none());
+}
@@ -0,0 +1,88 @@
+use crate::helpers::{spawn_app, TestApp};
+use crate::tracking::{send_issue, tracking_path};
+
+/// The path of the preferences link at the end of the issue `html`.
+fn preferences_path(html: &str) -> String {
+    tracking_path(html, "/subscriptions/preferences/").expect("No preferences link in the email")
+}
+
+async fn save_timezone(app: &TestApp, path: &str, timezone: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}{}", &app.address, path))
+        .form(&serde_json::json!({ "timezone": timezone }))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn stored_timezone(app: &TestApp) -> Option<String> {
+    sqlx::query_scalar!("SELECT timezone FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn issues_end_with_a_link_to_the_preferences_of_the_subscriber() {
+    let app = spawn_app().await;
+
+    let (_, html) = send_issue(&app, false).await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let path = preferences_path(&html);
+    assert!(email["TextBody"].as_str().unwrap().contains(&path));
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_timezone() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, false).await;
+    let path = preferences_path(&html);
+
+    let page = app.get_html(&path).await;
+    assert!(page.contains(r#"<option value="">Pick your timezone</option>"#));
+
+    let response = save_timezone(&app, &path, "Asia/Tokyo").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your timezone was saved"));
+    assert_eq!(stored_timezone(&app).await.as_deref(), Some("Asia/Tokyo"));
+
+    let page = app.get_html(&path).await;
+    assert!(page.contains(r#"<option value="Asia/Tokyo" selected>"#));
+    assert!(!page.contains("Pick your timezone"));
+}
+
+#[tokio::test]
+async fn unknown_timezones_are_refused() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, false).await;
+    let path = preferences_path(&html);
+
+    let response = save_timezone(&app, &path, "Mars/Olympus_Mons").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Please pick a timezone from the list"));
+    assert_eq!(stored_timezone(&app).await, None);
+}
+
+#[tokio::test]
+async fn forged_links_are_rejected() {
+    let app = spawn_app().await;
+
+    for response in [
+        app.get_tracking_link("/subscriptions/preferences/not-a-token", "")
+            .await,
+        save_timezone(&app, "/subscriptions/preferences/not-a-token", "Asia/Tokyo").await,
+    ] {
+        assert_eq!(response.status().as_u16(), 400);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::helpers::spawn_app;
+use crate::tracking::{send_issue, tracking_path};
+
+/// The path of the unsubscribe link at the end of the issue `html`.
+fn unsubscribe_path(html: &str) -> String {
+    tracking_path(html, "/subscriptions/unsubscribe/").expect("No unsubscribe link in the email")
+}
+
+#[tokio::test]
+async fn issues_carry_an_unsubscribe_link_and_header() {
+    let app = spawn_app().await;
+
+    let (_, html) = send_issue(&app, false).await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let path = unsubscribe_path(&html);
+    assert!(email["TextBody"].as_str().unwrap().contains(&path));
+    let header = |name: &str| {
+        email["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|header| header["Name"] == name)
+            .map(|header| header["Value"].as_str().unwrap().to_owned())
+    };
+    assert_eq!(
+        header("List-Unsubscribe"),
+        Some(format!("<{}{}>", app.base_url, path))
+    );
+    assert_eq!(
+        header("List-Unsubscribe-Post").as_deref(),
+        Some("List-Unsubscribe=One-Click")
+    );
+}
+
+#[tokio::test]
+async fn following_the_link_unsubscribes_the_subscriber() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, false).await;
+
+    let page = app.get_html(&unsubscribe_path(&html)).await;
+
+    assert!(page.contains("Sorry to see you go"));
+    let subscriber = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "unsubscribed");
+    assert!(subscriber.unsubscribed_at.is_some());
+}
+
+#[tokio::test]
+async fn mail_clients_can_unsubscribe_in_one_click() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, false).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}{}", &app.address, unsubscribe_path(&html)))
+        .body("List-Unsubscribe=One-Click")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn forged_unsubscribe_links_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_tracking_link("/subscriptions/unsubscribe/not-a-token", "")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
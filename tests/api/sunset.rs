@@ -0,0 +1,185 @@
+use email_newsletter::sunset::SunsetOutcome;
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{spawn_app, TestApp};
+use crate::newsletter::when_sending_an_email;
+use crate::tracking::{
+    backdate_deliveries, open_pixel_path, send_issue, tracking_path, MAIL_CLIENT,
+};
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+/// Applies the sunset policy, expecting it to send `emails` re-engagement
+/// emails, and returns the link to stay of the last one.
+async fn apply_sunset_policy(app: &TestApp, emails: u64) -> (SunsetOutcome, Option<String>) {
+    app.email_server.reset().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(emails)
+        .mount(&app.email_server)
+        .await;
+
+    let outcome = app.apply_sunset_policy().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let keep_link = requests.last().map(|request| {
+        let email: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        tracking_path(email["HtmlBody"].as_str().unwrap(), "/subscriptions/keep/").unwrap()
+    });
+    (outcome, keep_link)
+}
+
+async fn end_grace_period(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE subscriptions SET reengagement_sent_at = reengagement_sent_at - interval '15 days'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn inactive_subscribers_are_asked_once_then_unsubscribed() {
+    let app = spawn_app().await;
+    send_issue(&app, true).await;
+
+    let (outcome, keep_link) = apply_sunset_policy(&app, 1).await;
+    assert_eq!(outcome.reminded, 1);
+    assert!(keep_link.is_some());
+
+    let (outcome, _) = apply_sunset_policy(&app, 0).await;
+    assert_eq!(outcome, SunsetOutcome::default());
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+
+    end_grace_period(&app).await;
+    let (outcome, _) = apply_sunset_policy(&app, 0).await;
+    assert_eq!(outcome.unsubscribed, 1);
+    let subscriber = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "unsubscribed");
+    assert!(subscriber.unsubscribed_at.is_some());
+}
+
+#[tokio::test]
+async fn old_confirmation_links_do_not_put_unsubscribed_people_back() {
+    let app = spawn_app().await;
+    send_issue(&app, true).await;
+    // A link that was still around when the subscriber was unsubscribed.
+    let stale_token = "staleConfirmationToken123";
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id)
+        SELECT $1, id FROM subscriptions",
+        stale_token
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    apply_sunset_policy(&app, 1).await;
+    end_grace_period(&app).await;
+    apply_sunset_policy(&app, 0).await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        app.address, stale_token
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(subscriber_status(&app).await, "unsubscribed");
+}
+
+#[tokio::test]
+async fn following_the_link_keeps_the_subscriber_on_the_list() {
+    let app = spawn_app().await;
+    send_issue(&app, true).await;
+    let (_, keep_link) = apply_sunset_policy(&app, 1).await;
+
+    let response = app
+        .get_tracking_link(&keep_link.unwrap(), MAIL_CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    end_grace_period(&app).await;
+    let (outcome, _) = apply_sunset_policy(&app, 0).await;
+    assert_eq!(outcome, SunsetOutcome::default());
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn following_the_link_late_puts_the_subscriber_back_on_the_list() {
+    let app = spawn_app().await;
+    send_issue(&app, true).await;
+    let (_, keep_link) = apply_sunset_policy(&app, 1).await;
+    end_grace_period(&app).await;
+    apply_sunset_policy(&app, 0).await;
+
+    app.get_tracking_link(&keep_link.unwrap(), MAIL_CLIENT)
+        .await;
+
+    let subscriber = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+    assert!(subscriber.unsubscribed_at.is_none());
+}
+
+#[tokio::test]
+async fn readers_are_left_alone() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, true).await;
+    backdate_deliveries(&app).await;
+    app.get_tracking_link(&open_pixel_path(&html).unwrap(), MAIL_CLIENT)
+        .await;
+
+    let (outcome, _) = apply_sunset_policy(&app, 0).await;
+
+    assert_eq!(outcome, SunsetOutcome::default());
+}
+
+#[tokio::test]
+async fn issues_without_open_tracking_do_not_count_towards_inactivity() {
+    let app = spawn_app().await;
+    send_issue(&app, false).await;
+
+    let (outcome, _) = apply_sunset_policy(&app, 0).await;
+
+    assert_eq!(outcome, SunsetOutcome::default());
+}
+
+#[tokio::test]
+async fn forged_links_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_tracking_link("/subscriptions/keep/bm9wZQ.bm9wZQ", MAIL_CLIENT)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn the_subscriber_list_shows_engagement_scores() {
+    let app = spawn_app().await;
+    let (_, email) = send_issue(&app, true).await;
+    backdate_deliveries(&app).await;
+    app.get_tracking_link(&open_pixel_path(&email).unwrap(), MAIL_CLIENT)
+        .await;
+
+    let html = app.get_subscribers_html().await;
+    assert!(html.contains(r#"<td class="numeric">50</td>"#));
+    assert!(html.contains("1 / 1"));
+
+    app.get_tracking_link(&tracking_path(&email, "/t/c/").unwrap(), MAIL_CLIENT)
+        .await;
+    let html = app.get_subscribers_html().await;
+    assert!(html.contains(r#"<td class="numeric">100</td>"#));
+}
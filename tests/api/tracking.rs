@@ -0,0 +1,224 @@
+use wiremock::ResponseTemplate;
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, when_sending_an_email};
+
+pub const MAIL_CLIENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Thunderbird/115.0";
+const STORY: &str = "https://example.com/story?from=newsletter&issue=1";
+
+fn draft_body(track_opens: bool) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "title": "Weekly",
+        "markdown": format!("Read all *about* it [here]({}).", STORY),
+    });
+    if track_opens {
+        body["track_opens"] = "on".into();
+    }
+    body
+}
+
+/// Sends an issue to a single confirmed subscriber and returns the HTML body of
+/// the email they got.
+pub async fn send_issue(app: &TestApp, track_opens: bool) -> (uuid::Uuid, String) {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.create_draft_issue().await;
+    app.post_issue(issue_id, "/send", &draft_body(track_opens))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    (issue_id, email["HtmlBody"].as_str().unwrap().to_owned())
+}
+
+/// The first tracking URL path in an email starting with `prefix`, e.g. the
+/// open tracking pixel with `/t/o/`.
+pub fn tracking_path(html: &str, prefix: &str) -> Option<String> {
+    let start = html.find(prefix)?;
+    let end = start + html[start..].find('"')?;
+    Some(html[start..end].to_owned())
+}
+
+pub fn open_pixel_path(html: &str) -> Option<String> {
+    tracking_path(html, "/t/o/")
+}
+
+pub async fn backdate_deliveries(app: &TestApp) {
+    // Anything loaded right after delivery counts as prefetching.
+    sqlx::query!("UPDATE issue_delivery_log SET delivered_at = NOW() - interval '1 hour'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn issues_without_open_tracking_have_no_pixel() {
+    let app = spawn_app().await;
+
+    let (_, html) = send_issue(&app, false).await;
+
+    assert!(open_pixel_path(&html).is_none());
+}
+
+#[tokio::test]
+async fn opens_are_recorded_and_machine_opens_flagged() {
+    let app = spawn_app().await;
+    let (issue_id, html) = send_issue(&app, true).await;
+    let pixel = open_pixel_path(&html).expect("No tracking pixel in the email");
+    backdate_deliveries(&app).await;
+
+    let response = app.get_tracking_link(&pixel, MAIL_CLIENT).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "image/gif");
+    assert!(response.headers()["cache-control"]
+        .to_str()
+        .unwrap()
+        .contains("no-store"));
+    app.get_tracking_link(&pixel, "Mozilla/5.0").await;
+
+    let opens = sqlx::query!(
+        r#"
+        SELECT o.machine_open, o.user_agent
+        FROM issue_opens o
+        JOIN subscriptions s ON s.id = o.subscriber_id
+        WHERE o.newsletter_issue_id = $1
+        ORDER BY o.opened_at
+        "#,
+        issue_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(opens.len(), 2);
+    assert_eq!(opens[0].machine_open, None);
+    assert_eq!(opens[0].user_agent.as_deref(), Some(MAIL_CLIENT));
+    assert_eq!(opens[1].machine_open.as_deref(), Some("apple_mpp"));
+}
+
+async fn open_from(app: &TestApp, pixel: &str, forwarded_for: &str) {
+    app.api_client
+        .get(format!("{}{}", &app.address, pixel))
+        .header(reqwest::header::USER_AGENT, MAIL_CLIENT)
+        .header("X-Forwarded-For", forwarded_for)
+        .send()
+        .await
+        .expect("Failed to execute request");
+}
+
+async fn machine_opens(app: &TestApp) -> Vec<Option<String>> {
+    sqlx::query_scalar!("SELECT machine_open FROM issue_opens ORDER BY opened_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn forwarded_addresses_from_untrusted_peers_are_ignored() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, true).await;
+    let pixel = open_pixel_path(&html).unwrap();
+    backdate_deliveries(&app).await;
+
+    // Claiming to be one of Apple's proxies does not get a real open hidden.
+    open_from(&app, &pixel, "17.58.1.2").await;
+
+    assert_eq!(machine_opens(&app).await, vec![None]);
+}
+
+#[tokio::test]
+async fn forwarded_addresses_from_trusted_proxies_are_used() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+    let (_, html) = send_issue(&app, true).await;
+    let pixel = open_pixel_path(&html).unwrap();
+    backdate_deliveries(&app).await;
+
+    open_from(&app, &pixel, "17.58.1.2").await;
+
+    assert_eq!(
+        machine_opens(&app).await,
+        vec![Some("apple_mpp".to_owned())]
+    );
+}
+
+#[tokio::test]
+async fn tampered_pixels_are_served_but_not_recorded() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, true).await;
+    let pixel = open_pixel_path(&html).unwrap();
+    let (payload, signature) = pixel.rsplit_once('.').unwrap();
+    let tampered = format!("{}A.{}", payload, signature);
+
+    for path in [tampered.as_str(), "/t/o/not-a-token"] {
+        let response = app.get_tracking_link(path, MAIL_CLIENT).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["content-type"], "image/gif");
+    }
+
+    let opens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_opens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(opens, 0);
+}
+
+#[tokio::test]
+async fn links_redirect_to_their_destination_and_record_the_click() {
+    let app = spawn_app().await;
+    let (issue_id, html) = send_issue(&app, false).await;
+    assert!(!html.contains(r#"href="https://example.com"#));
+    let link = tracking_path(&html, "/t/c/").expect("No tracked link in the email");
+    backdate_deliveries(&app).await;
+
+    let response = app.get_tracking_link(&link, MAIL_CLIENT).await;
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers()["location"], STORY);
+    app.get_tracking_link(&link, "Mimecast Link Scanner").await;
+
+    let clicks = sqlx::query!(
+        r#"
+        SELECT url, machine_click
+        FROM issue_clicks
+        WHERE newsletter_issue_id = $1
+        ORDER BY clicked_at
+        "#,
+        issue_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(clicks.len(), 2);
+    assert_eq!(clicks[0].url, STORY);
+    assert_eq!(clicks[0].machine_click, None);
+    assert_eq!(clicks[1].machine_click.as_deref(), Some("bot"));
+}
+
+#[tokio::test]
+async fn tampered_links_are_refused() {
+    let app = spawn_app().await;
+    let (_, html) = send_issue(&app, false).await;
+    let link = tracking_path(&html, "/t/c/").unwrap();
+    let (payload, signature) = link.rsplit_once('.').unwrap();
+    let tampered = format!("{}A.{}", payload, signature);
+
+    for path in [tampered.as_str(), "/t/c/not-a-token"] {
+        let response = app.get_tracking_link(path, MAIL_CLIENT).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert!(response.headers().get("location").is_none());
+    }
+
+    let clicks = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_clicks"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks, 0);
+}